@@ -18,7 +18,24 @@ use rayon::prelude::*;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace};
 
+pub mod align;
+pub mod chop;
+pub mod cleanup;
+pub mod compact;
+pub mod diff;
+pub mod fingerprint;
+pub mod inject;
+pub mod isomorphism;
+pub mod liftover;
+pub mod merge;
+pub mod minimizers;
+pub mod normalize;
+pub mod path_distance;
+pub mod poa;
+pub mod prune;
+pub mod subgraph;
 pub mod unchop;
+pub mod untangle;
 
 pub fn simple_components(
     graph: &PackedGraph,
@@ -0,0 +1,777 @@
+/*!
+
+Sequence-to-graph alignment with affine gaps, in the style of GSSW
+and partial order alignment.
+
+The graph is first ordered as a DAG of oriented handles: a depth
+first search follows edges to the right, starting from the handles
+without left neighbors, and any edge that would close a cycle is
+dropped. Only the orientations reachable from the roots of the search
+are included, so to align to the other strand of a linear graph,
+align the reverse complement of the query. Each base of each oriented
+handle then becomes a column in
+the dynamic programming matrices, with the columns of the previous
+handles in the DAG as predecessors of the first base of a handle.
+
+The DP is a straightforward, unbanded and SIMD-free Gotoh recurrence
+over all columns, so it's only meant for small subgraphs, e.g. the
+neighborhood of a variant site.
+
+*/
+
+use crate::{
+    handle::{Direction, Handle},
+    handlegraph::*,
+};
+
+use fnv::FnvHashMap;
+
+use std::fmt::Write;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlignmentScoring {
+    /// The score added for each matching base.
+    pub match_score: i32,
+    /// The penalty for each mismatching base.
+    pub mismatch: i32,
+    /// The penalty for the first base of a gap.
+    pub gap_open: i32,
+    /// The penalty for each further base of a gap.
+    pub gap_extend: i32,
+}
+
+impl Default for AlignmentScoring {
+    fn default() -> Self {
+        Self {
+            match_score: 1,
+            mismatch: 4,
+            gap_open: 6,
+            gap_extend: 1,
+        }
+    }
+}
+
+impl AlignmentScoring {
+    #[inline]
+    fn substitution(&self, a: u8, b: u8) -> i32 {
+        if a.eq_ignore_ascii_case(&b) {
+            self.match_score
+        } else {
+            -self.mismatch
+        }
+    }
+
+    /// The penalty of a gap of length `len`.
+    #[inline]
+    fn gap(&self, len: usize) -> i32 {
+        if len == 0 {
+            0
+        } else {
+            self.gap_open + (len as i32 - 1) * self.gap_extend
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignmentMode {
+    /// Smith-Waterman style local alignment; both ends of the query
+    /// may be soft clipped.
+    Local,
+    /// The entire query is aligned, but the alignment may start and
+    /// end anywhere in the graph.
+    Glocal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EditOp {
+    Match,
+    Mismatch,
+    /// Query bases that aren't in the graph.
+    Insertion,
+    /// Graph bases that aren't in the query.
+    Deletion,
+}
+
+impl EditOp {
+    #[inline]
+    pub fn cigar_char(self) -> char {
+        match self {
+            EditOp::Match => '=',
+            EditOp::Mismatch => 'X',
+            EditOp::Insertion => 'I',
+            EditOp::Deletion => 'D',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edit {
+    pub op: EditOp,
+    pub len: usize,
+}
+
+/// The part of an alignment on a single handle, starting at `offset`
+/// on the handle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    pub handle: Handle,
+    pub offset: usize,
+    pub edits: Vec<Edit>,
+}
+
+impl Mapping {
+    /// The number of graph bases covered by the mapping.
+    pub fn graph_len(&self) -> usize {
+        self.edits
+            .iter()
+            .filter(|e| e.op != EditOp::Insertion)
+            .map(|e| e.len)
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphAlignment {
+    pub score: i32,
+    /// The start of the aligned part of the query.
+    pub query_start: usize,
+    /// The end, exclusive, of the aligned part of the query.
+    pub query_end: usize,
+    pub query_len: usize,
+    pub path: Vec<Mapping>,
+}
+
+impl GraphAlignment {
+    /// Return the edits of the alignment as a CIGAR string, using
+    /// `=` and `X` for matches and mismatches, and `S` for the soft
+    /// clipped ends of the query.
+    pub fn cigar(&self) -> String {
+        let mut ops: Vec<Edit> = Vec::new();
+
+        let mut push = |op: EditOp, len: usize| match ops.last_mut() {
+            Some(last) if last.op == op => last.len += len,
+            _ if len > 0 => ops.push(Edit { op, len }),
+            _ => (),
+        };
+
+        for mapping in self.path.iter() {
+            for edit in mapping.edits.iter() {
+                push(edit.op, edit.len);
+            }
+        }
+
+        let mut cigar = String::new();
+        if self.query_start > 0 {
+            write!(cigar, "{}S", self.query_start).unwrap();
+        }
+        for edit in ops {
+            write!(cigar, "{}{}", edit.len, edit.op.cigar_char()).unwrap();
+        }
+        if self.query_end < self.query_len {
+            write!(cigar, "{}S", self.query_len - self.query_end).unwrap();
+        }
+        cigar
+    }
+}
+
+/// The graph as a DAG of oriented handles, with the predecessors of
+/// each handle given as indices into `handles`.
+struct DagOrder {
+    handles: Vec<Handle>,
+    preds: Vec<Vec<usize>>,
+}
+
+fn dag_order<G: HandleGraphRef>(graph: G) -> DagOrder {
+    let mut all_handles = graph.handles().collect::<Vec<_>>();
+    all_handles.sort();
+
+    // prefer starting from the handles without left neighbors, so
+    // that a linear graph is traversed from its start
+    let mut roots = all_handles
+        .iter()
+        .copied()
+        .filter(|&h| graph.degree(h, Direction::Left) == 0)
+        .collect::<Vec<_>>();
+    roots.extend(all_handles.iter().copied());
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Mark {
+        Active,
+        Done,
+    }
+
+    let sorted_neighbors = |handle: Handle| {
+        let mut neighbors = graph
+            .neighbors(handle, Direction::Right)
+            .collect::<Vec<_>>();
+        neighbors.sort();
+        neighbors.into_iter()
+    };
+
+    let mut marks: FnvHashMap<Handle, Mark> = FnvHashMap::default();
+    let mut postorder: Vec<Handle> = Vec::new();
+    let mut dag_edges: Vec<(Handle, Handle)> = Vec::new();
+
+    for root in roots {
+        if marks.contains_key(&root) {
+            continue;
+        }
+
+        marks.insert(root, Mark::Active);
+        let mut stack = vec![(root, sorted_neighbors(root))];
+
+        while let Some((handle, neighbors)) = stack.last_mut() {
+            let handle = *handle;
+            match neighbors.next() {
+                Some(next) => match marks.get(&next) {
+                    Some(Mark::Active) => (),
+                    Some(Mark::Done) => dag_edges.push((handle, next)),
+                    None => {
+                        dag_edges.push((handle, next));
+                        marks.insert(next, Mark::Active);
+                        stack.push((next, sorted_neighbors(next)));
+                    }
+                },
+                None => {
+                    marks.insert(handle, Mark::Done);
+                    postorder.push(handle);
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    let handles = postorder.into_iter().rev().collect::<Vec<_>>();
+    let index: FnvHashMap<Handle, usize> =
+        handles.iter().enumerate().map(|(ix, &h)| (h, ix)).collect();
+
+    let mut preds = vec![Vec::new(); handles.len()];
+    for (from, to) in dag_edges {
+        preds[index[&to]].push(index[&from]);
+    }
+    for p in preds.iter_mut() {
+        p.sort_unstable();
+        p.dedup();
+    }
+
+    DagOrder { handles, preds }
+}
+
+/// A single base of an oriented handle in the DP. Every column also
+/// has the virtual start column as a predecessor, so that alignments
+/// can start anywhere in the graph.
+struct Column {
+    node: usize,
+    offset: usize,
+    base: u8,
+    preds: Vec<usize>,
+}
+
+fn build_columns<G: HandleGraphRef>(graph: G, dag: &DagOrder) -> Vec<Column> {
+    let mut columns: Vec<Column> = Vec::new();
+    // the last column of each node, or of its predecessors if the
+    // node is empty
+    let mut last_cols: Vec<Vec<usize>> = Vec::with_capacity(dag.handles.len());
+
+    for (node, &handle) in dag.handles.iter().enumerate() {
+        let mut in_cols = dag.preds[node]
+            .iter()
+            .flat_map(|&p| last_cols[p].iter().copied())
+            .collect::<Vec<_>>();
+        in_cols.sort_unstable();
+        in_cols.dedup();
+
+        let seq = graph.sequence_vec(handle);
+        if seq.is_empty() {
+            last_cols.push(in_cols);
+            continue;
+        }
+
+        for (offset, base) in seq.into_iter().enumerate() {
+            let preds = if offset == 0 {
+                std::mem::take(&mut in_cols)
+            } else {
+                vec![columns.len() - 1]
+            };
+            columns.push(Column {
+                node,
+                offset,
+                base,
+                preds,
+            });
+        }
+        last_cols.push(vec![columns.len() - 1]);
+    }
+
+    columns
+}
+
+const NEG_INF: i32 = i32::MIN / 2;
+
+struct Matrices {
+    rows: usize,
+    h: Vec<i32>,
+    e: Vec<i32>,
+    f: Vec<i32>,
+}
+
+impl Matrices {
+    #[inline]
+    fn ix(&self, col: usize, row: usize) -> usize {
+        col * self.rows + row
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    H,
+    E,
+    F,
+}
+
+struct Aligner<'a> {
+    query: &'a [u8],
+    scoring: &'a AlignmentScoring,
+    mode: AlignmentMode,
+    columns: Vec<Column>,
+}
+
+impl<'a> Aligner<'a> {
+    /// The H score of the virtual start column at `row`.
+    #[inline]
+    fn start_score(&self, row: usize) -> i32 {
+        match self.mode {
+            AlignmentMode::Local => 0,
+            AlignmentMode::Glocal => -self.scoring.gap(row),
+        }
+    }
+
+    fn fill(&self) -> Matrices {
+        let rows = self.query.len() + 1;
+        let size = rows * self.columns.len();
+
+        let mut m = Matrices {
+            rows,
+            h: vec![NEG_INF; size],
+            e: vec![NEG_INF; size],
+            f: vec![NEG_INF; size],
+        };
+
+        let open = self.scoring.gap_open;
+        let ext = self.scoring.gap_extend;
+
+        for (j, col) in self.columns.iter().enumerate() {
+            for i in 0..rows {
+                let mut e = NEG_INF;
+                for &p in col.preds.iter() {
+                    let pi = m.ix(p, i);
+                    e = e.max(m.h[pi] - open).max(m.e[pi] - ext);
+                }
+                e = e.max(self.start_score(i) - open);
+
+                let f = if i == 0 {
+                    NEG_INF
+                } else {
+                    let ci = m.ix(j, i - 1);
+                    (m.h[ci] - open).max(m.f[ci] - ext)
+                };
+
+                let h = if i == 0 {
+                    0
+                } else {
+                    let mut diag = NEG_INF;
+                    for &p in col.preds.iter() {
+                        diag = diag.max(m.h[m.ix(p, i - 1)]);
+                    }
+                    diag = diag.max(self.start_score(i - 1));
+                    let sub =
+                        self.scoring.substitution(self.query[i - 1], col.base);
+                    let h = (diag + sub).max(e).max(f);
+                    match self.mode {
+                        AlignmentMode::Local => h.max(0),
+                        AlignmentMode::Glocal => h,
+                    }
+                };
+
+                let ix = m.ix(j, i);
+                m.h[ix] = h;
+                m.e[ix] = e;
+                m.f[ix] = f;
+            }
+        }
+
+        m
+    }
+
+    /// Find the cell the alignment ends at.
+    fn best_end(&self, m: &Matrices) -> (usize, usize) {
+        let rows = match self.mode {
+            AlignmentMode::Local => 0..m.rows,
+            AlignmentMode::Glocal => (m.rows - 1)..m.rows,
+        };
+
+        let mut best = (NEG_INF, 0, 0);
+        for j in 0..self.columns.len() {
+            for i in rows.clone() {
+                let h = m.h[m.ix(j, i)];
+                if h > best.0 {
+                    best = (h, j, i);
+                }
+            }
+        }
+        (best.1, best.2)
+    }
+
+    /// Trace back from the end cell, returning the start row and the
+    /// edits, in order, with the column of each edit that consumes a
+    /// graph base.
+    fn traceback(
+        &self,
+        m: &Matrices,
+        end_col: usize,
+        end_row: usize,
+    ) -> (usize, Vec<(EditOp, Option<usize>)>) {
+        let open = self.scoring.gap_open;
+        let ext = self.scoring.gap_extend;
+
+        let mut ops = Vec::new();
+        let mut state = State::H;
+        let (mut j, mut i) = (end_col, end_row);
+
+        loop {
+            let col = &self.columns[j];
+            let ix = m.ix(j, i);
+
+            match state {
+                State::H => {
+                    let h = m.h[ix];
+                    if i == 0 || (self.mode == AlignmentMode::Local && h == 0) {
+                        break;
+                    }
+
+                    let sub =
+                        self.scoring.substitution(self.query[i - 1], col.base);
+                    let op =
+                        if self.query[i - 1].eq_ignore_ascii_case(&col.base) {
+                            EditOp::Match
+                        } else {
+                            EditOp::Mismatch
+                        };
+
+                    if let Some(&p) = col
+                        .preds
+                        .iter()
+                        .find(|&&p| m.h[m.ix(p, i - 1)] + sub == h)
+                    {
+                        ops.push((op, Some(j)));
+                        j = p;
+                        i -= 1;
+                    } else if self.start_score(i - 1) + sub == h {
+                        ops.push((op, Some(j)));
+                        i -= 1;
+                        if self.mode == AlignmentMode::Glocal {
+                            for _ in 0..i {
+                                ops.push((EditOp::Insertion, None));
+                            }
+                            i = 0;
+                        }
+                        break;
+                    } else if h == m.e[ix] {
+                        state = State::E;
+                    } else {
+                        state = State::F;
+                    }
+                }
+                State::E => {
+                    let e = m.e[ix];
+                    ops.push((EditOp::Deletion, Some(j)));
+
+                    let next = col.preds.iter().find_map(|&p| {
+                        let pi = m.ix(p, i);
+                        if m.e[pi] - ext == e {
+                            Some((p, State::E))
+                        } else if m.h[pi] - open == e {
+                            Some((p, State::H))
+                        } else {
+                            None
+                        }
+                    });
+
+                    match next {
+                        Some((p, s)) => {
+                            j = p;
+                            state = s;
+                        }
+                        None => {
+                            // from the virtual start column
+                            if self.mode == AlignmentMode::Glocal {
+                                for _ in 0..i {
+                                    ops.push((EditOp::Insertion, None));
+                                }
+                                i = 0;
+                            }
+                            break;
+                        }
+                    }
+                }
+                State::F => {
+                    let f = m.f[ix];
+                    ops.push((EditOp::Insertion, None));
+                    let ci = m.ix(j, i - 1);
+                    state = if m.f[ci] - ext == f {
+                        State::F
+                    } else {
+                        State::H
+                    };
+                    i -= 1;
+                }
+            }
+        }
+
+        ops.reverse();
+        (i, ops)
+    }
+}
+
+/// Group the traced back edits into mappings on each handle.
+fn build_path(
+    dag: &DagOrder,
+    columns: &[Column],
+    ops: Vec<(EditOp, Option<usize>)>,
+) -> Vec<Mapping> {
+    let mut path: Vec<Mapping> = Vec::new();
+    // insertions before the first graph base are attached to the
+    // first mapping
+    let mut pending: Vec<Edit> = Vec::new();
+
+    let push_edit = |edits: &mut Vec<Edit>, op: EditOp| match edits.last_mut() {
+        Some(last) if last.op == op => last.len += 1,
+        _ => edits.push(Edit { op, len: 1 }),
+    };
+
+    for (op, col) in ops {
+        match col {
+            None => match path.last_mut() {
+                Some(mapping) => push_edit(&mut mapping.edits, op),
+                None => push_edit(&mut pending, op),
+            },
+            Some(col) => {
+                let column = &columns[col];
+                let handle = dag.handles[column.node];
+                let same_node = path.last().map(|m| m.handle) == Some(handle);
+                if !same_node {
+                    path.push(Mapping {
+                        handle,
+                        offset: column.offset,
+                        edits: std::mem::take(&mut pending),
+                    });
+                }
+                let mapping = path.last_mut().unwrap();
+                push_edit(&mut mapping.edits, op);
+            }
+        }
+    }
+
+    path
+}
+
+/// Align `query` to `graph`, treating the graph as a DAG of oriented
+/// handles as described in the module documentation. Returns `None`
+/// if the graph contains no sequence.
+pub fn align_to_graph<G: HandleGraphRef>(
+    graph: G,
+    query: &[u8],
+    scoring: &AlignmentScoring,
+    mode: AlignmentMode,
+) -> Option<GraphAlignment> {
+    let dag = dag_order(graph);
+    let columns = build_columns(graph, &dag);
+
+    if columns.is_empty() {
+        return None;
+    }
+
+    let aligner = Aligner {
+        query,
+        scoring,
+        mode,
+        columns,
+    };
+
+    let matrices = aligner.fill();
+    let (end_col, end_row) = aligner.best_end(&matrices);
+    let score = matrices.h[matrices.ix(end_col, end_row)];
+
+    let (query_start, ops) = aligner.traceback(&matrices, end_col, end_row);
+    let path = build_path(&dag, &aligner.columns, ops);
+
+    Some(GraphAlignment {
+        score,
+        query_start,
+        query_end: end_row,
+        query_len: query.len(),
+        path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::handle::Edge;
+    use crate::hashgraph::HashGraph;
+    use crate::mutablehandlegraph::*;
+    use crate::packedgraph::PackedGraph;
+    use crate::util::test::{edge, hnd, seq_graph, small_graph};
+
+    fn build_graph<G>() -> G
+    where
+        G: Default + AdditiveHandleGraph,
+    {
+        let seqs: [&[u8]; 4] = [b"ACGT", b"A", b"G", b"TTCA"];
+        let edges =
+            [edge(1, 2), edge(1, 3), edge(2, 4), edge(3, 4), edge(1, 4)];
+        seq_graph(&seqs, &edges)
+    }
+
+    /// Check that the edits of the alignment are consistent with the
+    /// graph sequence and the query.
+    fn check_edits<G: HandleGraphRef>(
+        graph: G,
+        query: &[u8],
+        aln: &GraphAlignment,
+    ) {
+        let mut q = aln.query_start;
+        for mapping in aln.path.iter() {
+            let seq = graph.sequence_vec(mapping.handle);
+            let mut g = mapping.offset;
+            for edit in mapping.edits.iter() {
+                for _ in 0..edit.len {
+                    match edit.op {
+                        EditOp::Match => {
+                            assert_eq!(seq[g], query[q]);
+                            g += 1;
+                            q += 1;
+                        }
+                        EditOp::Mismatch => {
+                            assert_ne!(seq[g], query[q]);
+                            g += 1;
+                            q += 1;
+                        }
+                        EditOp::Insertion => q += 1,
+                        EditOp::Deletion => g += 1,
+                    }
+                }
+            }
+            assert!(g <= seq.len());
+        }
+        assert_eq!(q, aln.query_end);
+    }
+
+    fn align<G: HandleGraphRef>(
+        graph: G,
+        query: &[u8],
+        mode: AlignmentMode,
+    ) -> GraphAlignment {
+        let scoring = AlignmentScoring::default();
+        let aln = align_to_graph(graph, query, &scoring, mode).unwrap();
+        check_edits(graph, query, &aln);
+        aln
+    }
+
+    fn check_alignments<G: HandleGraphRef>(graph: G) {
+        use AlignmentMode::*;
+
+        let h = |id: u64| Handle::pack(id, false);
+
+        let aln = align(graph, b"ACGTGTTCA", Glocal);
+        assert_eq!(aln.score, 9);
+        assert_eq!(aln.cigar(), "9=");
+        let handles = aln.path.iter().map(|m| m.handle).collect::<Vec<_>>();
+        assert_eq!(handles, vec![h(1), h(3), h(4)]);
+
+        // the deletion edge is used rather than a gap
+        let aln = align(graph, b"ACGTTTCA", Glocal);
+        assert_eq!(aln.score, 8);
+        assert_eq!(aln.cigar(), "8=");
+        let handles = aln.path.iter().map(|m| m.handle).collect::<Vec<_>>();
+        assert_eq!(handles, vec![h(1), h(4)]);
+
+        let aln = align(graph, b"ACGTCTTCA", Glocal);
+        assert_eq!(aln.score, 4);
+        assert_eq!(aln.cigar(), "4=1X4=");
+
+        let aln = align(graph, b"ACGTACCTTCA", Glocal);
+        assert_eq!(aln.score, 9 - 7);
+        assert_eq!(aln.cigar(), "5=2I4=");
+        assert_eq!(aln.path[1].edits.len(), 2);
+
+        let aln = align(graph, b"GTGTT", Glocal);
+        assert_eq!(aln.score, 5);
+        assert_eq!(
+            aln.path
+                .iter()
+                .map(|m| (m.handle, m.offset, m.graph_len()))
+                .collect::<Vec<_>>(),
+            vec![(h(1), 2, 2), (h(3), 0, 1), (h(4), 0, 2)]
+        );
+
+        // gaps in the graph are opened and extended
+        let aln = align(graph, b"ACGTGTTCAACGTGTTCA", Glocal);
+        assert!(aln.score < 9);
+
+        let aln = align(graph, b"GGGGACGTG", Local);
+        assert_eq!(aln.score, 5);
+        assert_eq!(aln.query_start, 4);
+        assert_eq!(aln.cigar(), "4S5=");
+
+        let aln = align(graph, b"CCCC", Local);
+        assert_eq!(aln.score, 1);
+
+        let aln = align(graph, b"", Glocal);
+        assert_eq!(aln.score, 0);
+        assert!(aln.path.is_empty());
+    }
+
+    #[test]
+    fn align_packedgraph() {
+        let graph: PackedGraph = build_graph();
+        check_alignments(&graph);
+    }
+
+    #[test]
+    fn align_hashgraph() {
+        let graph: HashGraph = build_graph();
+        check_alignments(&graph);
+    }
+
+    #[test]
+    fn align_inversion_and_cycle() {
+        let mut graph: HashGraph = small_graph();
+        let (n1, n2, n3) = (hnd(1), hnd(2), hnd(3));
+
+        // n2 can be inverted, and there's a cycle back to n1
+        graph.create_edge(Edge(n1, n2.flip()));
+        graph.create_edge(Edge(n2.flip(), n3));
+        graph.create_edge(Edge(n3, n1));
+
+        let aln = align(&graph, b"AAACCCTCA", AlignmentMode::Glocal);
+        assert_eq!(aln.score, 9);
+        assert_eq!(aln.cigar(), "9=");
+        let handles = aln.path.iter().map(|m| m.handle).collect::<Vec<_>>();
+        assert_eq!(handles, vec![n1, n2.flip(), n3]);
+
+        let empty = HashGraph::new();
+        let scoring = AlignmentScoring::default();
+        assert!(align_to_graph(
+            &empty,
+            b"ACGT",
+            &scoring,
+            AlignmentMode::Local
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,218 @@
+/*!
+
+Split the nodes of a graph so that no node is longer than a given
+length, the inverse of [`unchop`](super::unchop::unchop).
+
+Each node that's too long is divided once, at every multiple of the
+maximum length, so the paths on the node are only updated once. The
+new nodes are then given IDs directly after the node they were split
+from, shifting the IDs of the following nodes, so the nodes keep their
+relative order, and chopping a graph twice gives the same IDs.
+
+*/
+
+use crate::{handle::NodeId, handlegraph::*, mutablehandlegraph::*};
+
+use crate::packedgraph::*;
+
+use fnv::FnvHashMap;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// Divide every node in `graph` that is longer than `max_len` into
+/// nodes of length `max_len`, with the last part of each node holding
+/// the remainder. Paths are updated to step through all the parts.
+///
+/// The parts of a node have consecutive IDs, starting with the
+/// original ID of the node, and the IDs of the other nodes are
+/// increased by the number of nodes added before them. Returns the
+/// number of nodes that were added.
+///
+/// Panics if `max_len` is zero.
+pub fn chop(graph: &mut PackedGraph, max_len: usize) -> usize {
+    assert!(max_len > 0, "cannot chop nodes to length zero");
+
+    let to_chop = graph
+        .handles()
+        .filter_map(|handle| {
+            let len = graph.node_len(handle);
+            if len > max_len {
+                Some((handle, len))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if to_chop.is_empty() {
+        return 0;
+    }
+
+    debug!("chopping {} nodes to length {}", to_chop.len(), max_len);
+
+    // the IDs given to the new nodes by `divide_handle`, and their
+    // position in the node they're part of
+    let mut parts: FnvHashMap<NodeId, (NodeId, u64)> = FnvHashMap::default();
+    // the number of parts added to each chopped node
+    let mut added: Vec<(NodeId, u64)> = Vec::with_capacity(to_chop.len());
+
+    for (handle, len) in to_chop {
+        let offsets = (max_len..len).step_by(max_len).collect::<Vec<_>>();
+        let divided = graph.divide_handle(handle, &offsets);
+
+        for (ix, part) in divided.iter().enumerate().skip(1) {
+            parts.insert(part.id(), (handle.id(), ix as u64));
+        }
+        added.push((handle.id(), offsets.len() as u64));
+    }
+
+    // the IDs of every node after a chopped node are shifted by the
+    // total number of parts added up to and including that node
+    let mut total = 0;
+    let shifts: Vec<(NodeId, u64)> = added
+        .into_iter()
+        .map(|(id, count)| {
+            total += count;
+            (id, total)
+        })
+        .collect();
+
+    let shifted = |id: NodeId| -> u64 {
+        let ix = shifts.partition_point(|&(chopped, _)| chopped < id);
+        let shift = if ix == 0 { 0 } else { shifts[ix - 1].1 };
+        u64::from(id) + shift
+    };
+
+    graph.transform_node_ids(|id| match parts.get(&id) {
+        Some(&(node, ix)) => NodeId::from(shifted(node) + ix),
+        None => NodeId::from(shifted(id)),
+    });
+
+    total as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        conversion::fasta::path_sequence,
+        handle::{Direction, Edge, Handle},
+        pathhandlegraph::*,
+        util::test::{add_path, edge, hnd, r_hnd, seq_graph},
+    };
+
+    use super::super::unchop::unchop;
+
+    const PATH1_SEQ: &[u8] = b"GATTACACTAAGGTTCCTAGCTA";
+    const PATH2_SEQ: &[u8] = b"CTAAAGGTTCCTGTAATC";
+
+    fn test_graph() -> (PackedGraph, PathId, PathId) {
+        let seqs: [&[u8]; 4] = [b"GATTACA", b"CT", b"AAGGTTCC", b"TAG"];
+        let edges = [
+            edge(1, 2),
+            Edge(hnd(1), r_hnd(3)),
+            edge(2, 3),
+            edge(3, 4),
+            Edge(r_hnd(3), hnd(4)),
+            Edge(hnd(4), r_hnd(4)),
+        ];
+        let mut graph: PackedGraph = seq_graph(&seqs, &edges);
+
+        let p1 = add_path(
+            &mut graph,
+            b"path1",
+            &[hnd(1), hnd(2), hnd(3), hnd(4), r_hnd(4)],
+        );
+        let p2 = add_path(&mut graph, b"path2", &[r_hnd(4), hnd(3), r_hnd(1)]);
+
+        (graph, p1, p2)
+    }
+
+    #[test]
+    fn chop_max_length() {
+        let (mut graph, p1, p2) = test_graph();
+        assert_eq!(path_sequence(&graph, p1).unwrap(), PATH1_SEQ);
+        assert_eq!(path_sequence(&graph, p2).unwrap(), PATH2_SEQ);
+
+        let added = chop(&mut graph, 3);
+        assert_eq!(added, 4);
+        assert_eq!(graph.node_count(), 8);
+        assert_eq!(path_sequence(&graph, p1).unwrap(), PATH1_SEQ);
+        assert_eq!(path_sequence(&graph, p2).unwrap(), PATH2_SEQ);
+
+        let nodes = graph
+            .handles()
+            .map(|h| (h.id().into(), graph.sequence_vec(h)))
+            .collect::<Vec<(u64, _)>>();
+
+        let expected: Vec<(u64, &[u8])> = vec![
+            (1, b"GAT"),
+            (2, b"TAC"),
+            (3, b"A"),
+            (4, b"CT"),
+            (5, b"AAG"),
+            (6, b"GTT"),
+            (7, b"CC"),
+            (8, b"TAG"),
+        ];
+        for ((id, seq), (exp_id, exp_seq)) in nodes.iter().zip(expected) {
+            assert_eq!(*id, exp_id);
+            assert_eq!(seq.as_slice(), exp_seq);
+        }
+
+        // the edges of the original nodes are on the outer parts
+        let rights = |h: Handle| {
+            let mut ns =
+                graph.neighbors(h, Direction::Right).collect::<Vec<_>>();
+            ns.sort();
+            ns
+        };
+        assert_eq!(rights(hnd(3)), vec![hnd(4), r_hnd(7)]);
+        assert_eq!(rights(hnd(7)), vec![r_hnd(3), hnd(8)]);
+        assert_eq!(rights(r_hnd(5)), vec![r_hnd(4), hnd(8)]);
+        assert_eq!(rights(hnd(8)), vec![r_hnd(8)]);
+
+        // chopping again changes nothing
+        assert_eq!(chop(&mut graph, 3), 0);
+        assert_eq!(graph.node_count(), 8);
+    }
+
+    #[test]
+    fn chop_unchop_roundtrip() {
+        let (mut graph, p1, p2) = test_graph();
+        assert_eq!(path_sequence(&graph, p1).unwrap(), PATH1_SEQ);
+        assert_eq!(path_sequence(&graph, p2).unwrap(), PATH2_SEQ);
+
+        chop(&mut graph, 1);
+        assert_eq!(graph.node_count(), 20);
+        assert!(graph.handles().all(|h| graph.node_len(h) == 1));
+        assert_eq!(path_sequence(&graph, p1).unwrap(), PATH1_SEQ);
+        assert_eq!(path_sequence(&graph, p2).unwrap(), PATH2_SEQ);
+
+        unchop(&mut graph);
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(path_sequence(&graph, p1).unwrap(), PATH1_SEQ);
+        assert_eq!(path_sequence(&graph, p2).unwrap(), PATH2_SEQ);
+    }
+
+    #[test]
+    fn chop_keeps_id_gaps() {
+        let mut graph = PackedGraph::default();
+        graph.create_handle(b"ACGTACGT", 3);
+        graph.create_handle(b"AC", 10);
+        graph.create_handle(b"GGGGG", 12);
+        graph.create_edge(Edge(hnd(3), hnd(10)));
+        graph.create_edge(Edge(hnd(10), hnd(12)));
+
+        chop(&mut graph, 4);
+
+        let ids = graph.handles().map(|h| h.id().into()).collect::<Vec<u64>>();
+        assert_eq!(ids, vec![3, 4, 11, 13, 14]);
+        assert!(graph.has_edge(hnd(3), hnd(4)));
+        assert!(graph.has_edge(hnd(4), hnd(11)));
+        assert!(graph.has_edge(hnd(11), hnd(13)));
+        assert!(graph.has_edge(hnd(13), hnd(14)));
+    }
+}
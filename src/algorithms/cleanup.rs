@@ -0,0 +1,323 @@
+/*!
+
+Remove short dead ends and small disconnected fragments from a graph,
+as commonly found in graphs built from assemblies.
+
+A tip is a chain of nodes that starts at a dead end, i.e. an oriented
+node with no edges on its left, and continues unbranched until it
+joins the rest of the graph at a node that has other neighbors on the
+same side. Removing a tip can leave a new one behind, so tips are
+removed until none shorter than the limit remain. When every branch
+into a node is a tip, the longest is kept, so that tip removal only
+shortens the graph's dead ends rather than erasing linear parts of it.
+
+*/
+
+use crate::{
+    handle::{Direction, Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+};
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// A dead-end chain of nodes, and the node it joins.
+struct Tip {
+    nodes: Vec<Handle>,
+    length: usize,
+    joins: Handle,
+}
+
+/// Follow the chain from the dead end `start` to the right, and
+/// return it if it's a tip shorter than `max_len`.
+fn find_tip<G: HandleGraphRef>(
+    graph: G,
+    start: Handle,
+    max_len: usize,
+) -> Option<Tip> {
+    let mut nodes = vec![start];
+    let mut length = graph.node_len(start);
+    let mut handle = start;
+
+    loop {
+        if length >= max_len || graph.degree(handle, Direction::Right) != 1 {
+            return None;
+        }
+
+        let next = graph.neighbors(handle, Direction::Right).next()?;
+        if nodes.iter().any(|h| h.id() == next.id()) {
+            return None;
+        }
+
+        if graph.degree(next, Direction::Left) > 1 {
+            return Some(Tip {
+                nodes,
+                length,
+                joins: next,
+            });
+        }
+
+        length += graph.node_len(next);
+        nodes.push(next);
+        handle = next;
+    }
+}
+
+/// Iteratively remove tips shorter than `max_len` bases, leaving the
+/// nodes on the paths named in `protected` in place. Returns the IDs
+/// of the nodes of each removed tip, starting from the dead end.
+///
+/// Other paths on the removed nodes may be destroyed, depending on
+/// the graph implementation.
+pub fn remove_tips<G>(
+    graph: &mut G,
+    max_len: usize,
+    protected: &[&[u8]],
+) -> Vec<Vec<NodeId>>
+where
+    G: SubtractiveHandleGraph,
+    for<'a> &'a G: HandleGraphRef + GraphPathNames + GraphPathsSteps,
+{
+    let protected: FnvHashSet<NodeId> = protected
+        .iter()
+        .filter_map(|name| graph.get_path_id(name))
+        .filter_map(|path| graph.path_steps(path))
+        .flat_map(|steps| steps.map(|s| s.handle().id()))
+        .collect();
+
+    let mut removed = Vec::new();
+
+    loop {
+        let mut tips: FnvHashMap<Handle, Vec<Tip>> = FnvHashMap::default();
+
+        for handle in graph.handles() {
+            for &start in [handle, handle.flip()].iter() {
+                if graph.degree(start, Direction::Left) != 0 {
+                    continue;
+                }
+                if let Some(tip) = find_tip(&*graph, start, max_len) {
+                    if tip.nodes.iter().all(|h| !protected.contains(&h.id())) {
+                        tips.entry(tip.joins).or_default().push(tip);
+                    }
+                }
+            }
+        }
+
+        let mut to_remove: Vec<Tip> = Vec::new();
+
+        for (joins, mut joining) in tips {
+            // keep the longest tip if there's no other way into the
+            // node, breaking ties by the IDs of the dead ends
+            joining.sort_by_key(|tip| (tip.length, tip.nodes[0].id()));
+            if joining.len() == graph.degree(joins, Direction::Left) {
+                joining.pop();
+            }
+            to_remove.extend(joining);
+        }
+
+        if to_remove.is_empty() {
+            break;
+        }
+
+        for tip in to_remove {
+            for &handle in tip.nodes.iter() {
+                graph.remove_handle(handle);
+            }
+            removed.push(tip.nodes.iter().map(|h| h.id()).collect());
+        }
+    }
+
+    removed.sort();
+
+    debug!("removed {} tips", removed.len());
+
+    removed
+}
+
+/// The nodes in each connected component of `graph`, in sorted order.
+pub fn connected_components<G: HandleGraphRef>(graph: G) -> Vec<Vec<NodeId>> {
+    let mut visited: FnvHashSet<NodeId> = FnvHashSet::default();
+    let mut components = Vec::new();
+
+    let mut handles = graph.handles().collect::<Vec<_>>();
+    handles.sort();
+
+    for handle in handles {
+        if !visited.insert(handle.id()) {
+            continue;
+        }
+
+        let mut component = Vec::new();
+        let mut stack = vec![handle];
+
+        while let Some(current) = stack.pop() {
+            component.push(current.id());
+            for &dir in [Direction::Left, Direction::Right].iter() {
+                for next in graph.neighbors(current, dir) {
+                    if visited.insert(next.id()) {
+                        stack.push(next.forward());
+                    }
+                }
+            }
+        }
+
+        component.sort();
+        components.push(component);
+    }
+
+    components
+}
+
+/// Remove the connected components whose nodes have fewer than
+/// `min_len` bases in total. Returns the IDs of the nodes of each
+/// removed component.
+pub fn remove_small_components<G>(
+    graph: &mut G,
+    min_len: usize,
+) -> Vec<Vec<NodeId>>
+where
+    G: SubtractiveHandleGraph,
+    for<'a> &'a G: HandleGraphRef,
+{
+    let removed = connected_components(&*graph)
+        .into_iter()
+        .filter(|component| {
+            let length: usize = component
+                .iter()
+                .map(|&id| graph.node_len(Handle::pack(id, false)))
+                .sum();
+            length < min_len
+        })
+        .collect::<Vec<_>>();
+
+    for &id in removed.iter().flatten() {
+        graph.remove_handle(Handle::pack(id, false));
+    }
+
+    debug!("removed {} small components", removed.len());
+
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{handle::Edge, hashgraph::HashGraph, packedgraph::PackedGraph};
+
+    fn hnd(x: u64) -> Handle {
+        Handle::pack(x, false)
+    }
+
+    fn ids(ids: &[u64]) -> Vec<NodeId> {
+        ids.iter().map(|&i| NodeId::from(i)).collect()
+    }
+
+    fn test_graph<G>() -> G
+    where
+        G: AdditiveHandleGraph + MutableGraphPaths + Default,
+    {
+        let mut graph = G::default();
+
+        let seqs: [&[u8]; 14] = [
+            b"GATTACAGAT",
+            b"TACAGATTAC",
+            b"AGATTACAGA",
+            b"TTACAGATTA",
+            b"AC",
+            b"A",
+            b"CG",
+            b"T",
+            b"GG",
+            b"ACGT",
+            b"AC",
+            b"A",
+            b"T",
+            b"G",
+        ];
+        for seq in seqs.iter() {
+            graph.append_handle(seq);
+        }
+
+        // a chain of four long nodes with tips on 2, 3, and 4, and a
+        // separate component of 10 and 11
+        let edges = [
+            (1, 2),
+            (2, 3),
+            (3, 4),
+            (5, 2),
+            (6, 7),
+            (7, 3),
+            (3, 8),
+            (2, 9),
+            (10, 11),
+            (12, 13),
+            (14, 13),
+            (13, 4),
+        ];
+        for &(l, r) in edges.iter() {
+            graph.create_edge(Edge(hnd(l), hnd(r)));
+        }
+
+        let path = graph.create_path(b"protected", false).unwrap();
+        for &h in [hnd(1), hnd(2), hnd(9)].iter() {
+            graph.path_append_step(path, h);
+        }
+
+        graph
+    }
+
+    fn check_cleanup<G>()
+    where
+        G: HandleGraph
+            + AdditiveHandleGraph
+            + SubtractiveHandleGraph
+            + MutableGraphPaths
+            + Default,
+        for<'a> &'a G: HandleGraphRef + GraphPathNames + GraphPathsSteps,
+    {
+        let mut graph: G = test_graph();
+
+        assert_eq!(
+            connected_components(&graph),
+            vec![
+                ids(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 12, 13, 14]),
+                ids(&[10, 11])
+            ]
+        );
+
+        // 12 and 14 are the only ways into 13, so 14 is kept until 12
+        // is removed, and is then removed along with 13
+        let tips = remove_tips(&mut graph, 5, &[b"protected"]);
+        let expected = [&[5][..], &[6, 7], &[8], &[12], &[14, 13]];
+        assert_eq!(
+            tips,
+            expected.iter().map(|tip| ids(tip)).collect::<Vec<_>>()
+        );
+        assert_eq!(graph.node_count(), 7);
+        assert!(graph.has_node(9u64));
+        assert!(graph.get_path_id(b"protected").is_some());
+
+        // a longer limit doesn't remove the ends of the chain
+        assert!(remove_tips(&mut graph, 11, &[b"protected"]).is_empty());
+
+        let components = remove_small_components(&mut graph, 10);
+        assert_eq!(components, vec![ids(&[10, 11])]);
+        assert_eq!(graph.node_count(), 5);
+        assert_eq!(graph.edge_count(), 4);
+    }
+
+    #[test]
+    fn cleanup_packedgraph() {
+        check_cleanup::<PackedGraph>();
+    }
+
+    #[test]
+    fn cleanup_hashgraph() {
+        check_cleanup::<HashGraph>();
+    }
+}
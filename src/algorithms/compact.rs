@@ -0,0 +1,174 @@
+/*!
+
+Renumbering the nodes of a graph to the dense ID range `1..=N`.
+
+Unlike `PackedGraph::compact_ids`, these work on any graph that
+implements `TransformNodeIds`, and return the map from the old to the
+new node IDs, so that annotations that refer to the nodes by ID can be
+updated to match.
+
+*/
+
+use crate::{
+    handle::{Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+};
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+fn renumber<G>(graph: &mut G, ids: Vec<NodeId>) -> FnvHashMap<NodeId, NodeId>
+where
+    G: TransformNodeIds,
+{
+    let new_ids: FnvHashMap<NodeId, NodeId> = ids
+        .into_iter()
+        .enumerate()
+        .map(|(ix, id)| (id, NodeId::from(ix as u64 + 1)))
+        .collect();
+
+    graph.transform_node_ids(|id| new_ids.get(&id).copied().unwrap_or(id));
+
+    new_ids
+}
+
+/// Renumber the nodes of `graph` to `1..=N`, keeping the order of
+/// their IDs. Returns the new ID of each node, by its old ID.
+pub fn compact_node_ids<G>(graph: &mut G) -> FnvHashMap<NodeId, NodeId>
+where
+    G: TransformNodeIds,
+    for<'a> &'a G: IntoHandles,
+{
+    let mut ids = graph.handles().map(|h| h.id()).collect::<Vec<_>>();
+    ids.sort();
+    renumber(graph, ids)
+}
+
+/// Renumber the nodes of `graph` to `1..=N`, so that the node of
+/// `order[i]` gets the ID `i + 1`. The orientations of the handles in
+/// `order` are ignored. Returns the new ID of each node, by its old
+/// ID, or `None`, leaving the graph unchanged, if `order` doesn't
+/// contain every node in the graph exactly once.
+pub fn compact_node_ids_ordered<G>(
+    graph: &mut G,
+    order: &[Handle],
+) -> Option<FnvHashMap<NodeId, NodeId>>
+where
+    G: TransformNodeIds,
+    for<'a> &'a G: IntoHandles,
+{
+    let nodes: FnvHashSet<NodeId> = graph.handles().map(|h| h.id()).collect();
+
+    let mut seen: FnvHashSet<NodeId> = FnvHashSet::default();
+    let valid = order.len() == nodes.len()
+        && order
+            .iter()
+            .all(|h| nodes.contains(&h.id()) && seen.insert(h.id()));
+
+    if !valid {
+        return None;
+    }
+
+    let ids = order.iter().map(|h| h.id()).collect();
+    Some(renumber(graph, ids))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        handle::Edge, hashgraph::HashGraph, packedgraph::PackedGraph,
+        pathhandlegraph::*,
+    };
+
+    fn hnd(x: u64) -> Handle {
+        Handle::pack(x, false)
+    }
+
+    fn r_hnd(x: u64) -> Handle {
+        Handle::pack(x, true)
+    }
+
+    fn test_graph<G>() -> G
+    where
+        G: AdditiveHandleGraph + MutableGraphPaths + Default,
+    {
+        let mut graph = G::default();
+        graph.create_handle(b"GAT", 10u64);
+        graph.create_handle(b"TA", 4u64);
+        graph.create_handle(b"CA", 25u64);
+
+        graph.create_edge(Edge(hnd(10), hnd(4)));
+        graph.create_edge(Edge(hnd(4), r_hnd(25)));
+
+        let path = graph.create_path(b"path", false).unwrap();
+        for &h in [hnd(10), hnd(4), r_hnd(25)].iter() {
+            graph.path_append_step(path, h);
+        }
+
+        graph
+    }
+
+    fn path_handles<G>(graph: &G) -> Vec<Handle>
+    where
+        for<'a> &'a G: GraphPathNames + GraphPathsSteps,
+    {
+        let path = graph.get_path_id(b"path").unwrap();
+        graph
+            .path_steps(path)
+            .unwrap()
+            .map(|s| s.handle())
+            .collect()
+    }
+
+    fn check_compact<G>()
+    where
+        G: HandleGraph
+            + AdditiveHandleGraph
+            + MutableGraphPaths
+            + TransformNodeIds
+            + Default,
+        for<'a> &'a G: HandleGraphRef + GraphPathNames + GraphPathsSteps,
+    {
+        let mut graph: G = test_graph();
+        let map = compact_node_ids(&mut graph);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&NodeId::from(4)], NodeId::from(1));
+        assert_eq!(map[&NodeId::from(10)], NodeId::from(2));
+        assert_eq!(map[&NodeId::from(25)], NodeId::from(3));
+
+        assert_eq!(graph.min_node_id(), NodeId::from(1));
+        assert_eq!(graph.max_node_id(), NodeId::from(3));
+        assert_eq!(graph.sequence_vec(hnd(2)), b"GAT");
+        assert!(graph.has_edge(hnd(2), hnd(1)));
+        assert!(graph.has_edge(hnd(1), r_hnd(3)));
+        assert_eq!(path_handles(&graph), vec![hnd(2), hnd(1), r_hnd(3)]);
+
+        let mut graph: G = test_graph();
+        let order = [r_hnd(25), hnd(10), hnd(4)];
+        let map = compact_node_ids_ordered(&mut graph, &order).unwrap();
+        assert_eq!(map[&NodeId::from(25)], NodeId::from(1));
+        assert_eq!(graph.sequence_vec(hnd(1)), b"CA");
+        assert!(graph.has_edge(hnd(2), hnd(3)));
+        assert_eq!(path_handles(&graph), vec![hnd(2), hnd(3), r_hnd(1)]);
+
+        // the order must contain each node once
+        let mut graph: G = test_graph();
+        let invalid = [hnd(10), hnd(4)];
+        assert!(compact_node_ids_ordered(&mut graph, &invalid).is_none());
+        let invalid = [hnd(10), hnd(4), r_hnd(4)];
+        assert!(compact_node_ids_ordered(&mut graph, &invalid).is_none());
+        assert_eq!(path_handles(&graph), vec![hnd(10), hnd(4), r_hnd(25)]);
+    }
+
+    #[test]
+    fn compact_packedgraph() {
+        check_compact::<PackedGraph>();
+    }
+
+    #[test]
+    fn compact_hashgraph() {
+        check_compact::<HashGraph>();
+    }
+}
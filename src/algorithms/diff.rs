@@ -0,0 +1,777 @@
+/*!
+
+Structural comparison of two graphs.
+
+[`diff_graphs`] reports the nodes and edges that were removed from
+and added to the first graph to get the second, the nodes that
+changed sequence, and how the embedded paths differ.
+
+Nodes are matched either by ID, or, with [`NodeMatching::BySequence`],
+by their sequence, so that two graphs that only differ in how their
+nodes are numbered have an empty diff. Removed nodes, edges, and
+steps are always given in the IDs of the first graph, and added ones
+in the IDs of the second.
+
+*/
+
+use crate::{
+    handle::{Edge, Handle, NodeId},
+    handlegraph::*,
+    pathhandlegraph::*,
+    transaction::AddDelDelta,
+};
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// How nodes in the first graph are paired with nodes in the second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeMatching {
+    /// Nodes with the same ID are the same node, even if their
+    /// sequences differ.
+    ById,
+    /// Nodes are paired with nodes that have the same sequence,
+    /// preferring nodes with the same ID. If several nodes share a
+    /// sequence, the remaining ones are paired in order of ID. Nodes
+    /// that are left over are then paired by ID, as with `ById`.
+    BySequence,
+}
+
+/// A node that is in both graphs, but with different sequences.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SequenceChange {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub old_seq: Vec<u8>,
+    pub new_seq: Vec<u8>,
+}
+
+/// The difference between a path in the first graph and a path in
+/// the second.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PathDiff {
+    /// The path is only in the first graph.
+    Removed { name: Vec<u8> },
+    /// The path is only in the second graph.
+    Added { name: Vec<u8> },
+    /// The path has a different name in the second graph, but is
+    /// otherwise identical.
+    Renamed { from: Vec<u8>, to: Vec<u8> },
+    /// The path is in both graphs, but with different steps or
+    /// circularity. The steps are given with their rank on the path
+    /// they belong to, and together form a minimal edit script.
+    Changed {
+        name: Vec<u8>,
+        /// The circularity in the second graph, if it changed.
+        circular: Option<bool>,
+        removed_steps: Vec<(usize, Handle)>,
+        added_steps: Vec<(usize, Handle)>,
+    },
+}
+
+/// The structural difference between two graphs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GraphDiff {
+    pub nodes: AddDelDelta<NodeId>,
+    /// The edges are in the canonical orientation given by
+    /// [`Edge::edge_handle`].
+    pub edges: AddDelDelta<Edge>,
+    pub sequences: Vec<SequenceChange>,
+    /// The pairs of matched nodes that have different IDs in the two
+    /// graphs; always empty with `NodeMatching::ById`.
+    pub renumbered: Vec<(NodeId, NodeId)>,
+    pub paths: Vec<PathDiff>,
+}
+
+impl GraphDiff {
+    /// `true` if the graphs are identical, up to the renumbering of
+    /// nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+            && self.edges.is_empty()
+            && self.sequences.is_empty()
+            && self.paths.is_empty()
+    }
+}
+
+/// Pairs up the nodes of the two graphs, returning the map from the
+/// IDs of the first to those of the second.
+fn match_nodes(
+    from_nodes: &[(NodeId, Vec<u8>)],
+    to_nodes: &[(NodeId, Vec<u8>)],
+    matching: NodeMatching,
+) -> FnvHashMap<NodeId, NodeId> {
+    let to_seqs: FnvHashMap<NodeId, &[u8]> = to_nodes
+        .iter()
+        .map(|(id, seq)| (*id, seq.as_slice()))
+        .collect();
+
+    let mut node_map: FnvHashMap<NodeId, NodeId> = FnvHashMap::default();
+
+    if matching == NodeMatching::BySequence {
+        let mut matched: FnvHashSet<NodeId> = FnvHashSet::default();
+
+        for (id, seq) in from_nodes {
+            if to_seqs.get(id) == Some(&seq.as_slice()) {
+                node_map.insert(*id, *id);
+                matched.insert(*id);
+            }
+        }
+
+        let mut by_seq: FnvHashMap<&[u8], Vec<NodeId>> = FnvHashMap::default();
+        for (id, seq) in to_nodes.iter().rev() {
+            if !matched.contains(id) {
+                by_seq.entry(seq.as_slice()).or_default().push(*id);
+            }
+        }
+
+        for (id, seq) in from_nodes {
+            if node_map.contains_key(id) {
+                continue;
+            }
+            if let Some(to_id) =
+                by_seq.get_mut(seq.as_slice()).and_then(|ids| ids.pop())
+            {
+                node_map.insert(*id, to_id);
+                matched.insert(to_id);
+            }
+        }
+
+        for (id, _) in from_nodes {
+            if !node_map.contains_key(id)
+                && to_seqs.contains_key(id)
+                && !matched.contains(id)
+            {
+                node_map.insert(*id, *id);
+            }
+        }
+    } else {
+        for (id, _) in from_nodes {
+            if to_seqs.contains_key(id) {
+                node_map.insert(*id, *id);
+            }
+        }
+    }
+
+    node_map
+}
+
+fn sorted_nodes<G: HandleGraphRef>(graph: G) -> Vec<(NodeId, Vec<u8>)> {
+    let mut nodes = graph
+        .handles()
+        .map(|h| (h.id(), graph.sequence_vec(h)))
+        .collect::<Vec<_>>();
+    nodes.sort();
+    nodes
+}
+
+fn canonical_edges<G: HandleGraphRef>(graph: G) -> Vec<Edge> {
+    let mut edges = graph
+        .edges()
+        .map(|Edge(l, r)| Edge::edge_handle(l, r))
+        .collect::<Vec<_>>();
+    edges.sort();
+    edges.dedup();
+    edges
+}
+
+fn map_handle(
+    node_map: &FnvHashMap<NodeId, NodeId>,
+    handle: Handle,
+) -> Option<Handle> {
+    let id = node_map.get(&handle.id())?;
+    Some(Handle::pack(*id, handle.is_reverse()))
+}
+
+/// The ranks in `from` that must be removed, and the ranks in `to`
+/// that must be added, to turn one sequence into the other, found
+/// with the linear space variant of Myers' O(ND) difference
+/// algorithm.
+fn edit_script<F>(n: usize, m: usize, eq: F) -> (Vec<usize>, Vec<usize>)
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    edit_script_range(&eq, (0, n), (0, m), &mut removed, &mut added);
+    (removed, added)
+}
+
+/// Find the edit script between the ranges `from` and `to` of the
+/// two sequences, by splitting them at a point that an optimal script
+/// passes through, and recursing on either side of it.
+fn edit_script_range<F>(
+    eq: &F,
+    from: (usize, usize),
+    to: (usize, usize),
+    removed: &mut Vec<usize>,
+    added: &mut Vec<usize>,
+) where
+    F: Fn(usize, usize) -> bool,
+{
+    let ((mut from_start, mut from_end), (mut to_start, mut to_end)) =
+        (from, to);
+
+    // the common prefix and suffix are kept as they are
+    while from_start < from_end && to_start < to_end && eq(from_start, to_start)
+    {
+        from_start += 1;
+        to_start += 1;
+    }
+    while from_start < from_end
+        && to_start < to_end
+        && eq(from_end - 1, to_end - 1)
+    {
+        from_end -= 1;
+        to_end -= 1;
+    }
+
+    if from_start == from_end {
+        added.extend(to_start..to_end);
+        return;
+    }
+    if to_start == to_end {
+        removed.extend(from_start..from_end);
+        return;
+    }
+
+    let n = from_end - from_start;
+    let m = to_end - to_start;
+    let eq_mid = |x: usize, y: usize| eq(from_start + x, to_start + y);
+
+    match middle_snake(n, m, eq_mid) {
+        Some((x, y)) => {
+            let (from_mid, to_mid) = (from_start + x, to_start + y);
+            edit_script_range(
+                eq,
+                (from_start, from_mid),
+                (to_start, to_mid),
+                removed,
+                added,
+            );
+            edit_script_range(
+                eq,
+                (from_mid, from_end),
+                (to_mid, to_end),
+                removed,
+                added,
+            );
+        }
+        None => {
+            // the sequences have nothing in common
+            removed.extend(from_start..from_end);
+            added.extend(to_start..to_end);
+        }
+    }
+}
+
+/// Search for the shortest edit script from both ends of the
+/// sequences at once, until the two searches overlap, and return the
+/// point where they meet. Only two vectors of diagonals are kept,
+/// so this takes O(n + m) space. Returns `None` if the sequences
+/// have no elements in common.
+fn middle_snake<F>(n: usize, m: usize, eq: F) -> Option<(usize, usize)>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let (n, m) = (n as isize, m as isize);
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let len = 2 * max_d + 2;
+
+    // the furthest reaching x on each diagonal, from the start in
+    // `forward`, and from the end in `reverse`
+    let mut forward = vec![-1isize; len as usize];
+    let mut reverse = vec![-1isize; len as usize];
+    forward[(offset + 1) as usize] = 0;
+    reverse[(offset + 1) as usize] = 0;
+
+    let delta = n - m;
+    // if the total length is odd, the forward search finds the
+    // overlap, otherwise the reverse search does
+    let front = delta % 2 != 0;
+
+    let (mut f_start, mut f_end) = (0, 0);
+    let (mut r_start, mut r_end) = (0, 0);
+
+    for d in 0..max_d {
+        for k in ((-d + f_start)..=(d - f_end)).step_by(2) {
+            let ix = (offset + k) as usize;
+            let mut x =
+                if k == -d || (k != d && forward[ix - 1] < forward[ix + 1]) {
+                    forward[ix + 1]
+                } else {
+                    forward[ix - 1] + 1
+                };
+            let mut y = x - k;
+            while x < n && y < m && eq(x as usize, y as usize) {
+                x += 1;
+                y += 1;
+            }
+            forward[ix] = x;
+
+            if x > n {
+                f_end += 2;
+            } else if y > m {
+                f_start += 2;
+            } else if front {
+                let r_ix = offset + delta - k;
+                if (0..len).contains(&r_ix)
+                    && reverse[r_ix as usize] != -1
+                    && x >= n - reverse[r_ix as usize]
+                {
+                    return Some((x as usize, y as usize));
+                }
+            }
+        }
+
+        for k in ((-d + r_start)..=(d - r_end)).step_by(2) {
+            let ix = (offset + k) as usize;
+            let mut x =
+                if k == -d || (k != d && reverse[ix - 1] < reverse[ix + 1]) {
+                    reverse[ix + 1]
+                } else {
+                    reverse[ix - 1] + 1
+                };
+            let mut y = x - k;
+            while x < n
+                && y < m
+                && eq((n - x - 1) as usize, (m - y - 1) as usize)
+            {
+                x += 1;
+                y += 1;
+            }
+            reverse[ix] = x;
+
+            if x > n {
+                r_end += 2;
+            } else if y > m {
+                r_start += 2;
+            } else if !front {
+                let f_ix = offset + delta - k;
+                if (0..len).contains(&f_ix) && forward[f_ix as usize] != -1 {
+                    let f_x = forward[f_ix as usize];
+                    let f_y = f_x - (f_ix - offset);
+                    if f_x >= n - x {
+                        return Some((f_x as usize, f_y as usize));
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+type PathState = (bool, Vec<Handle>);
+
+fn path_states<G>(graph: G) -> Vec<(Vec<u8>, PathState)>
+where
+    G: GraphPathNames + IntoPathIds + GraphPathsSteps + Copy,
+{
+    let mut paths = graph
+        .path_ids()
+        .filter_map(|path| {
+            let name = graph.get_path_name_vec(path)?;
+            let circular = graph.path_circular(path)?;
+            let steps = graph.path_steps(path)?.map(|s| s.handle()).collect();
+            Some((name, (circular, steps)))
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+fn diff_paths(
+    from_paths: Vec<(Vec<u8>, PathState)>,
+    to_paths: Vec<(Vec<u8>, PathState)>,
+    node_map: &FnvHashMap<NodeId, NodeId>,
+) -> Vec<PathDiff> {
+    let translate = |steps: &[Handle]| -> Vec<Option<Handle>> {
+        steps.iter().map(|&h| map_handle(node_map, h)).collect()
+    };
+
+    let mut to_map: FnvHashMap<Vec<u8>, PathState> =
+        to_paths.iter().cloned().collect();
+
+    let mut result = Vec::new();
+    let mut removed: Vec<(Vec<u8>, PathState)> = Vec::new();
+
+    for (name, (circular, steps)) in from_paths {
+        let (to_circular, to_steps) = match to_map.remove(&name) {
+            Some(to_path) => to_path,
+            None => {
+                removed.push((name, (circular, steps)));
+                continue;
+            }
+        };
+
+        let mapped = translate(&steps);
+        let (removed_ix, added_ix) =
+            edit_script(mapped.len(), to_steps.len(), |i, j| {
+                mapped[i] == Some(to_steps[j])
+            });
+
+        if circular != to_circular
+            || !removed_ix.is_empty()
+            || !added_ix.is_empty()
+        {
+            result.push(PathDiff::Changed {
+                name,
+                circular: Some(to_circular).filter(|&c| c != circular),
+                removed_steps: removed_ix
+                    .into_iter()
+                    .map(|i| (i, steps[i]))
+                    .collect(),
+                added_steps: added_ix
+                    .into_iter()
+                    .map(|j| (j, to_steps[j]))
+                    .collect(),
+            });
+        }
+    }
+
+    // the paths that are only in the second graph, in name order
+    let mut added = to_paths
+        .into_iter()
+        .filter(|(name, _)| to_map.contains_key(name))
+        .collect::<Vec<_>>();
+
+    for (name, (circular, steps)) in removed {
+        let mapped = translate(&steps);
+        let renamed = added.iter().position(|(_, (to_circular, to_steps))| {
+            *to_circular == circular
+                && to_steps.len() == mapped.len()
+                && to_steps
+                    .iter()
+                    .zip(mapped.iter())
+                    .all(|(a, b)| Some(*a) == *b)
+        });
+
+        match renamed {
+            Some(ix) => {
+                let (to_name, _) = added.remove(ix);
+                result.push(PathDiff::Renamed {
+                    from: name,
+                    to: to_name,
+                });
+            }
+            None => result.push(PathDiff::Removed { name }),
+        }
+    }
+
+    result.extend(added.into_iter().map(|(name, _)| PathDiff::Added { name }));
+
+    result.sort();
+    result
+}
+
+/// Compare the graph `from` to the graph `to`, reporting the changes
+/// that turn `from` into `to`.
+pub fn diff_graphs<A, B>(from: A, to: B, matching: NodeMatching) -> GraphDiff
+where
+    A: HandleGraphRef + GraphPathNames + IntoPathIds + GraphPathsSteps,
+    B: HandleGraphRef + GraphPathNames + IntoPathIds + GraphPathsSteps,
+{
+    let from_nodes = sorted_nodes(from);
+    let to_nodes = sorted_nodes(to);
+
+    let node_map = match_nodes(&from_nodes, &to_nodes, matching);
+    let to_seqs: FnvHashMap<NodeId, &[u8]> = to_nodes
+        .iter()
+        .map(|(id, seq)| (*id, seq.as_slice()))
+        .collect();
+
+    let mut diff = GraphDiff::default();
+    let mut count = 0;
+
+    for (id, seq) in from_nodes.iter() {
+        match node_map.get(id) {
+            Some(to_id) => {
+                if *to_id != *id {
+                    diff.renumbered.push((*id, *to_id));
+                }
+                let new_seq = to_seqs[to_id];
+                if new_seq != seq.as_slice() {
+                    diff.sequences.push(SequenceChange {
+                        from: *id,
+                        to: *to_id,
+                        old_seq: seq.clone(),
+                        new_seq: new_seq.to_vec(),
+                    });
+                }
+            }
+            None => diff.nodes.del(*id, &mut count),
+        }
+    }
+
+    let matched: FnvHashSet<NodeId> = node_map.values().copied().collect();
+    for (id, _) in to_nodes.iter() {
+        if !matched.contains(id) {
+            diff.nodes.add(*id, &mut count);
+        }
+    }
+
+    let to_edges = canonical_edges(to);
+    let mut mapped_edges: FnvHashSet<Edge> = FnvHashSet::default();
+
+    for edge in canonical_edges(from) {
+        let Edge(l, r) = edge;
+        let mapped = map_handle(&node_map, l).and_then(|l| {
+            Some(Edge::edge_handle(l, map_handle(&node_map, r)?))
+        });
+        match mapped {
+            Some(mapped) if to_edges.binary_search(&mapped).is_ok() => {
+                mapped_edges.insert(mapped);
+            }
+            _ => diff.edges.del(edge, &mut count),
+        }
+    }
+
+    for edge in to_edges.iter() {
+        if !mapped_edges.contains(edge) {
+            diff.edges.add(*edge, &mut count);
+        }
+    }
+
+    diff.paths = diff_paths(path_states(from), path_states(to), &node_map);
+
+    debug!(
+        "graph diff: {} node changes, {} edge changes, {} path changes",
+        diff.nodes.len(),
+        diff.edges.len(),
+        diff.paths.len()
+    );
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        hashgraph::HashGraph,
+        mutablehandlegraph::*,
+        packedgraph::PackedGraph,
+        transaction::AddDel,
+        util::test::{hnd, r_hnd, test_graph},
+    };
+
+    /// A copy of `graph` with every node ID increased by 100.
+    fn shifted(graph: &PackedGraph) -> PackedGraph {
+        let shift = |h: Handle| Handle::pack(h.id().0 + 100, h.is_reverse());
+
+        let mut other = PackedGraph::default();
+        for (id, seq) in sorted_nodes(graph) {
+            other.create_handle(&seq, id.0 + 100);
+        }
+        for Edge(l, r) in graph.edges() {
+            other.create_edge(Edge(shift(l), shift(r)));
+        }
+        for (name, (circular, steps)) in path_states(graph) {
+            let path = other.create_path(&name, circular).unwrap();
+            for h in steps {
+                other.path_append_step(path, shift(h));
+            }
+        }
+        other
+    }
+
+    #[test]
+    fn edit_script_minimal() {
+        let diff = |a: &[u8], b: &[u8]| {
+            edit_script(a.len(), b.len(), |i, j| a[i] == b[j])
+        };
+
+        assert_eq!(diff(b"ABCABBA", b"ABCABBA"), (vec![], vec![]));
+        assert_eq!(diff(b"", b"AB"), (vec![], vec![0, 1]));
+        assert_eq!(diff(b"AB", b""), (vec![0, 1], vec![]));
+
+        let (removed, added) = diff(b"ABCABBA", b"CBABAC");
+        assert_eq!(removed.len() + added.len(), 5);
+
+        assert_eq!(diff(b"GATTACA", b"GATCACA"), (vec![3], vec![3]));
+        assert_eq!(diff(b"GATTACA", b"GATACA"), (vec![3], vec![]));
+    }
+
+    #[test]
+    fn edit_script_all_short_sequences() {
+        // every sequence over {A, B} of length up to 6
+        let seqs = (0..=6)
+            .flat_map(|len| {
+                (0..1usize << len).map(move |bits| {
+                    (0..len)
+                        .map(|i| if bits >> i & 1 == 0 { b'A' } else { b'B' })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let lcs_len = |a: &[u8], b: &[u8]| {
+            let mut table = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+            for i in 0..a.len() {
+                for j in 0..b.len() {
+                    table[i + 1][j + 1] = if a[i] == b[j] {
+                        table[i][j] + 1
+                    } else {
+                        table[i][j + 1].max(table[i + 1][j])
+                    };
+                }
+            }
+            table[a.len()][b.len()]
+        };
+
+        for a in seqs.iter() {
+            for b in seqs.iter() {
+                let (removed, added) =
+                    edit_script(a.len(), b.len(), |i, j| a[i] == b[j]);
+
+                assert!(removed.windows(2).all(|w| w[0] < w[1]));
+                assert!(added.windows(2).all(|w| w[0] < w[1]));
+
+                // the script is minimal, and the kept elements match
+                let lcs = lcs_len(a, b);
+                assert_eq!(removed.len(), a.len() - lcs);
+                assert_eq!(added.len(), b.len() - lcs);
+
+                let kept_a = (0..a.len())
+                    .filter(|i| removed.binary_search(i).is_err())
+                    .map(|i| a[i])
+                    .collect::<Vec<_>>();
+                let kept_b = (0..b.len())
+                    .filter(|j| added.binary_search(j).is_err())
+                    .map(|j| b[j])
+                    .collect::<Vec<_>>();
+                assert_eq!(kept_a, kept_b);
+            }
+        }
+    }
+
+    #[test]
+    fn diff_identical_graphs() {
+        let packed: PackedGraph = test_graph();
+        let hash: HashGraph = test_graph();
+
+        assert!(diff_graphs(&packed, &hash, NodeMatching::ById).is_empty());
+        assert!(
+            diff_graphs(&hash, &packed, NodeMatching::BySequence).is_empty()
+        );
+    }
+
+    #[test]
+    fn diff_by_id() {
+        let from: PackedGraph = test_graph();
+        let mut to: PackedGraph = test_graph();
+
+        to.remove_edge(Edge(hnd(1), hnd(8)));
+        to.create_handle(b"GATTACA", 10);
+        to.create_edge(Edge(hnd(9), hnd(10)));
+
+        let p1 = to.get_path_id(b"path1").unwrap();
+        to.path_append_step(p1, hnd(10));
+        to.path_set_circularity(p1, true);
+
+        let p2 = to.get_path_id(b"path2").unwrap();
+        let steps = to.path_steps(p2).unwrap().collect::<Vec<_>>();
+        to.path_remove_step(p2, steps[1].0);
+
+        let p3 = to.create_path(b"path3", false).unwrap();
+        to.path_append_step(p3, hnd(5));
+
+        let diff = diff_graphs(&from, &to, NodeMatching::ById);
+
+        assert_eq!(
+            diff.nodes.iter().copied().collect::<Vec<_>>(),
+            vec![AddDel::Add(0, NodeId::from(10))]
+        );
+        assert_eq!(
+            diff.edges.iter().copied().collect::<Vec<_>>(),
+            vec![
+                AddDel::Del(1, Edge::edge_handle(hnd(1), hnd(8))),
+                AddDel::Add(2, Edge::edge_handle(hnd(9), hnd(10))),
+            ]
+        );
+        assert!(diff.sequences.is_empty());
+        assert!(diff.renumbered.is_empty());
+
+        assert_eq!(
+            diff.paths,
+            vec![
+                PathDiff::Added {
+                    name: b"path3".to_vec()
+                },
+                PathDiff::Changed {
+                    name: b"path1".to_vec(),
+                    circular: Some(true),
+                    removed_steps: vec![],
+                    added_steps: vec![(5, hnd(10))],
+                },
+                PathDiff::Changed {
+                    name: b"path2".to_vec(),
+                    circular: None,
+                    removed_steps: vec![(1, r_hnd(4))],
+                    added_steps: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_by_sequence() {
+        let from: PackedGraph = test_graph();
+        let mut to = shifted(&from);
+
+        let by_id = diff_graphs(&from, &to, NodeMatching::ById);
+        assert_eq!(by_id.nodes.len(), 18);
+        assert_eq!(by_id.paths.len(), 2);
+
+        let by_seq = diff_graphs(&from, &to, NodeMatching::BySequence);
+        assert!(by_seq.is_empty());
+        assert_eq!(by_seq.renumbered.len(), 9);
+        assert_eq!(by_seq.renumbered[0], (NodeId::from(1), NodeId::from(101)));
+
+        // a path that was renamed is recognized as such, and a node
+        // with a new sequence is paired up by ID
+        let p2 = to.get_path_id(b"path2").unwrap();
+        let steps = to.path_steps(p2).unwrap().collect::<Vec<_>>();
+        to.destroy_path(p2);
+        let renamed = to.create_path(b"path2b", false).unwrap();
+        for step in steps {
+            to.path_append_step(renamed, step.handle());
+        }
+
+        let mut from = from;
+        from.create_handle(b"GG", 105);
+        to.remove_handle(hnd(105));
+        to.create_handle(b"CC", 105);
+
+        let diff = diff_graphs(&from, &to, NodeMatching::BySequence);
+        assert_eq!(
+            diff.sequences,
+            vec![SequenceChange {
+                from: 105.into(),
+                to: 105.into(),
+                old_seq: b"GG".to_vec(),
+                new_seq: b"CC".to_vec(),
+            }]
+        );
+        assert_eq!(
+            diff.edges.iter().map(|ad| ad.is_add()).collect::<Vec<_>>(),
+            vec![false, false]
+        );
+        assert_eq!(
+            diff.nodes.iter().copied().collect::<Vec<_>>(),
+            vec![AddDel::Del(0, NodeId::from(5))]
+        );
+        assert_eq!(
+            diff.paths,
+            vec![PathDiff::Renamed {
+                from: b"path2".to_vec(),
+                to: b"path2b".to_vec(),
+            }]
+        );
+    }
+}
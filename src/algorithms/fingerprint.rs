@@ -0,0 +1,336 @@
+/*!
+
+Stable content hashes of graphs.
+
+A [`GraphFingerprint`] covers the nodes and their sequences, the
+edges, and the paths of a graph, and only depends on what the graph
+contains, not on how it's stored, so a `HashGraph` and a
+`PackedGraph` with the same contents have the same fingerprint, as
+does a `PackedGraph` before and after defragmentation. The hashes are
+stable across runs and platforms, so they can be stored alongside
+data derived from a graph to detect when it's used with the wrong
+graph.
+
+The nodes and edges are hashed independently and combined with a
+commutative operation, so the hash doesn't depend on iteration order,
+and is computed in parallel. With [`IdMode::Ignore`], node IDs are
+replaced by labels derived from the sequences and neighborhoods of
+the nodes, making the fingerprint invariant to renumbering the nodes.
+
+*/
+
+use crate::{
+    handle::{Direction, Edge, Handle, NodeId},
+    handlegraph::*,
+    pathhandlegraph::*,
+};
+
+use fnv::{FnvHashMap, FnvHasher};
+
+use rayon::prelude::*;
+
+use std::hash::Hasher;
+
+/// The number of rounds of refining the node labels by their
+/// neighbors' when node IDs are ignored. This is fixed, as changing
+/// it changes every fingerprint.
+const LABEL_ROUNDS: usize = 3;
+
+/// Whether node IDs are part of the fingerprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdMode {
+    /// Graphs that only differ in their node IDs have different
+    /// fingerprints.
+    Include,
+    /// Node IDs are ignored, so renumbering the nodes of a graph
+    /// doesn't change its fingerprint.
+    Ignore,
+}
+
+/// The hashes of the different parts of a graph, and their sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GraphFingerprint {
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub path_count: usize,
+    pub nodes: u64,
+    pub edges: u64,
+    pub paths: u64,
+}
+
+impl GraphFingerprint {
+    /// A single hash combining all parts of the fingerprint.
+    pub fn value(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        hasher.usize(self.node_count);
+        hasher.usize(self.edge_count);
+        hasher.usize(self.path_count);
+        hasher.u64(self.nodes);
+        hasher.u64(self.edges);
+        hasher.u64(self.paths);
+        hasher.finish()
+    }
+}
+
+impl std::fmt::Display for GraphFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.value())
+    }
+}
+
+/// FNV-1a over little-endian encoded values, with the result mixed
+/// so that summing hashes is a reasonable multiset hash.
+struct StableHasher(FnvHasher);
+
+impl StableHasher {
+    fn new() -> Self {
+        StableHasher(FnvHasher::default())
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.write(&v.to_le_bytes());
+    }
+
+    fn usize(&mut self, v: usize) {
+        self.u64(v as u64);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.usize(bytes.len());
+        self.0.write(bytes);
+    }
+
+    /// The splitmix64 finalizer, applied to the FNV hash.
+    fn finish(&self) -> u64 {
+        let mut z = self.0.finish();
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Identifies each oriented node, either by its ID or by a label
+/// that doesn't depend on the ID.
+enum HandleKeys {
+    Ids,
+    Labels(FnvHashMap<NodeId, u64>),
+}
+
+impl HandleKeys {
+    fn key(&self, handle: Handle) -> u64 {
+        match self {
+            HandleKeys::Ids => handle.as_integer(),
+            HandleKeys::Labels(labels) => {
+                let mut hasher = StableHasher::new();
+                hasher.u64(labels[&handle.id()]);
+                hasher.u64(handle.is_reverse() as u64);
+                hasher.finish()
+            }
+        }
+    }
+}
+
+fn node_labels<G>(graph: G) -> FnvHashMap<NodeId, u64>
+where
+    G: HandleGraphRef + IntoHandlesPar + Send + Sync,
+{
+    let mut labels: FnvHashMap<NodeId, u64> = graph
+        .handles_par()
+        .map(|handle| {
+            let mut hasher = StableHasher::new();
+            hasher.bytes(&graph.sequence_vec(handle));
+            (handle.id(), hasher.finish())
+        })
+        .collect();
+
+    for _ in 0..LABEL_ROUNDS {
+        let keys = HandleKeys::Labels(labels);
+        let next = graph
+            .handles_par()
+            .map(|handle| {
+                let mut hasher = StableHasher::new();
+                hasher.u64(keys.key(handle));
+                for &dir in [Direction::Left, Direction::Right].iter() {
+                    let mut neighbors = graph
+                        .neighbors(handle, dir)
+                        .map(|h| keys.key(h))
+                        .collect::<Vec<_>>();
+                    neighbors.sort_unstable();
+                    hasher.usize(neighbors.len());
+                    neighbors.into_iter().for_each(|k| hasher.u64(k));
+                }
+                (handle.id(), hasher.finish())
+            })
+            .collect();
+        labels = next;
+    }
+
+    labels
+}
+
+/// Compute the fingerprint of `graph`.
+pub fn graph_fingerprint<G>(graph: G, ids: IdMode) -> GraphFingerprint
+where
+    G: HandleGraphRef
+        + IntoHandlesPar
+        + IntoEdgesPar
+        + GraphPathNames
+        + IntoPathIds
+        + GraphPathsSteps
+        + Send
+        + Sync,
+{
+    let keys = match ids {
+        IdMode::Include => HandleKeys::Ids,
+        IdMode::Ignore => HandleKeys::Labels(node_labels(graph)),
+    };
+
+    let (node_count, nodes) = graph
+        .handles_par()
+        .map(|handle| {
+            let mut hasher = StableHasher::new();
+            hasher.u64(keys.key(handle));
+            hasher.bytes(&graph.sequence_vec(handle));
+            (1, hasher.finish())
+        })
+        .reduce(
+            || (0, 0),
+            |(c0, h0), (c1, h1)| (c0 + c1, h0.wrapping_add(h1)),
+        );
+
+    let (edge_count, edges) = graph
+        .edges_par()
+        .map(|Edge(l, r)| {
+            // an edge is the same as its reverse, so both directions
+            // are hashed and combined symmetrically
+            let fwd = (keys.key(l), keys.key(r));
+            let rev = (keys.key(r.flip()), keys.key(l.flip()));
+            let (a, b) = fwd.min(rev);
+            let mut hasher = StableHasher::new();
+            hasher.u64(a);
+            hasher.u64(b);
+            (1, hasher.finish())
+        })
+        .reduce(
+            || (0, 0),
+            |(c0, h0), (c1, h1)| (c0 + c1, h0.wrapping_add(h1)),
+        );
+
+    let path_ids = graph.path_ids().collect::<Vec<_>>();
+    let path_count = path_ids.len();
+    let paths = path_ids
+        .into_par_iter()
+        .map(|path| {
+            let mut hasher = StableHasher::new();
+            if let Some(name) = graph.get_path_name_vec(path) {
+                hasher.bytes(&name);
+            }
+            hasher.u64(graph.path_circular(path).unwrap_or(false) as u64);
+            if let Some(steps) = graph.path_steps(path) {
+                steps.for_each(|step| hasher.u64(keys.key(step.handle())));
+            }
+            hasher.finish()
+        })
+        .reduce(|| 0, u64::wrapping_add);
+
+    GraphFingerprint {
+        node_count,
+        edge_count,
+        path_count,
+        nodes,
+        edges,
+        paths,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        hashgraph::HashGraph,
+        mutablehandlegraph::*,
+        packedgraph::{defragment::Defragment, PackedGraph},
+        util::test::{hnd, r_hnd, test_graph},
+    };
+
+    #[test]
+    fn fingerprint_independent_of_layout() {
+        let packed: PackedGraph = test_graph();
+        let hash: HashGraph = test_graph();
+
+        for &ids in [IdMode::Include, IdMode::Ignore].iter() {
+            let fingerprint = graph_fingerprint(&packed, ids);
+            assert_eq!(fingerprint.node_count, 9);
+            assert_eq!(fingerprint.edge_count, 13);
+            assert_eq!(fingerprint.path_count, 2);
+            assert_eq!(fingerprint, graph_fingerprint(&hash, ids));
+        }
+
+        // removing and defragmenting leaves the same graph as never
+        // having added the removed parts
+        let mut edited: PackedGraph = test_graph();
+        let extra = edited.append_handle(b"GATTACA");
+        edited.create_edge(Edge(hnd(9), extra));
+        let path = edited.create_path(b"extra", false).unwrap();
+        edited.path_append_step(path, extra);
+        edited.destroy_path(path);
+        edited.remove_handle(extra);
+        edited.defragment();
+
+        assert_eq!(
+            graph_fingerprint(&packed, IdMode::Include),
+            graph_fingerprint(&edited, IdMode::Include)
+        );
+    }
+
+    #[test]
+    fn fingerprint_node_ids() {
+        let graph: PackedGraph = test_graph();
+        let mut ordered: PackedGraph = test_graph();
+        let order = (1..=9).rev().map(hnd).collect::<Vec<_>>();
+        ordered.apply_ordering(&order);
+
+        assert_ne!(
+            graph_fingerprint(&graph, IdMode::Include),
+            graph_fingerprint(&ordered, IdMode::Include)
+        );
+        assert_eq!(
+            graph_fingerprint(&graph, IdMode::Ignore),
+            graph_fingerprint(&ordered, IdMode::Ignore)
+        );
+    }
+
+    #[test]
+    fn fingerprint_detects_changes() {
+        let graph: PackedGraph = test_graph();
+
+        let mut edited: PackedGraph = test_graph();
+        edited.remove_edge(Edge(hnd(1), hnd(8)));
+        edited.create_edge(Edge(hnd(1), r_hnd(8)));
+
+        let mut flipped_step: PackedGraph = test_graph();
+        let path = flipped_step.get_path_id(b"path2").unwrap();
+        let first = flipped_step.path_first_step(path).unwrap();
+        flipped_step.path_flip_step(path, first);
+
+        for &ids in [IdMode::Include, IdMode::Ignore].iter() {
+            let fingerprint = graph_fingerprint(&graph, ids);
+
+            // without IDs, the nodes are labeled by their neighbors,
+            // so changing the edges changes the node hashes too
+            let edges = graph_fingerprint(&edited, ids);
+            assert_eq!(
+                fingerprint.nodes == edges.nodes,
+                ids == IdMode::Include
+            );
+            assert_ne!(fingerprint.edges, edges.edges);
+            assert_ne!(fingerprint.value(), edges.value());
+
+            let paths = graph_fingerprint(&flipped_step, ids);
+            assert_eq!(fingerprint.edges, paths.edges);
+            assert_ne!(fingerprint.paths, paths.paths);
+            assert_ne!(fingerprint.to_string(), paths.to_string());
+        }
+    }
+}
@@ -0,0 +1,408 @@
+/*!
+
+Inject intervals on existing paths into the graph as new paths, e.g.
+to add genes and other annotations from a BED file.
+
+The steps covering each interval are found using a
+`PathPositionMap`, and nodes that are only partially covered by an
+interval are divided, so that each new path starts and ends exactly
+at the requested bases.
+
+*/
+
+use crate::{
+    handle::{Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+};
+
+use crate::packedgraph::PackedGraph;
+use crate::path_position::PathPositionMap;
+use crate::util::bed::read_bed;
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+use bstr::ByteSlice;
+
+use std::io::{self, BufRead};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// An interval on an existing path, to be injected as the path
+/// `name`. `start` and `end` are 0-based and end-exclusive, as in BED.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathInterval {
+    pub source: PathId,
+    pub start: usize,
+    pub end: usize,
+    pub name: Vec<u8>,
+}
+
+/// An interval resolved to the handles of the steps that cover it,
+/// along with the 0-based position of the first of those steps.
+pub(crate) struct CoveringSteps {
+    pub(crate) handles: Vec<Handle>,
+    pub(crate) first_step_pos: usize,
+}
+
+pub(crate) fn covering_steps(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    interval: &PathInterval,
+) -> Option<CoveringSteps> {
+    let PathInterval {
+        source, start, end, ..
+    } = *interval;
+
+    if end <= start {
+        return None;
+    }
+
+    let first = index.find_step_at_base(source, start + 1)?;
+    let last = index.find_step_at_base(source, end)?;
+
+    let first_step_pos = index.path_step_position(source, first)? - 1;
+
+    let mut handles = Vec::new();
+    let mut step = first;
+
+    loop {
+        handles.push(graph.path_handle_at_step(source, step)?);
+        if step == last {
+            break;
+        }
+        step = graph.path_next_step(source, step)?;
+    }
+
+    Some(CoveringSteps {
+        handles,
+        first_step_pos,
+    })
+}
+
+/// The offsets, on the forward strand, where nodes must be divided
+/// so that new paths can start and end at the given bases.
+#[derive(Debug, Default)]
+pub(crate) struct NodeCuts(FnvHashMap<NodeId, Vec<usize>>);
+
+impl NodeCuts {
+    /// Cut `handle` before the base at `offset` on the handle's
+    /// strand, unless that's at either end of the node.
+    pub(crate) fn add<G: HandleGraphRef>(
+        &mut self,
+        graph: G,
+        handle: Handle,
+        offset: usize,
+    ) {
+        let len = graph.node_len(handle);
+        if offset > 0 && offset < len {
+            let fwd_offset = if handle.is_reverse() {
+                len - offset
+            } else {
+                offset
+            };
+            self.0.entry(handle.id()).or_default().push(fwd_offset);
+        }
+    }
+
+    /// Divide each node at all of its cuts, in order of node ID.
+    pub(crate) fn divide<G>(self, graph: &mut G) -> DividedNodes
+    where
+        G: MutableHandles,
+    {
+        let mut to_divide = self.0.into_iter().collect::<Vec<_>>();
+        to_divide.sort_by_key(|(id, _)| *id);
+
+        debug!("dividing {} nodes", to_divide.len());
+
+        let mut divided = FnvHashMap::default();
+        for (id, mut offsets) in to_divide {
+            offsets.sort_unstable();
+            offsets.dedup();
+            let parts = graph.divide_handle(Handle::pack(id, false), &offsets);
+            divided.insert(id, parts);
+        }
+        DividedNodes(divided)
+    }
+}
+
+/// The forward handles of the parts of each divided node, from left
+/// to right, by the ID of the original node.
+pub(crate) struct DividedNodes(FnvHashMap<NodeId, Vec<Handle>>);
+
+impl DividedNodes {
+    /// Append to `path` the parts of the handles in `walk` that are
+    /// entirely within `start..end`, where `pos` is the position of
+    /// the start of `walk`. The handles in `walk` are those of the
+    /// graph before the nodes were divided.
+    pub(crate) fn append_range<G, I>(
+        &self,
+        graph: &mut G,
+        path: PathId,
+        walk: I,
+        mut pos: usize,
+        (start, end): (usize, usize),
+    ) where
+        G: MutableGraphPaths,
+        for<'a> &'a G: HandleGraphRef,
+        I: IntoIterator<Item = Handle>,
+    {
+        for handle in walk {
+            let parts: Vec<Handle> = match self.0.get(&handle.id()) {
+                None => vec![handle],
+                Some(parts) if handle.is_reverse() => {
+                    parts.iter().rev().map(|h| h.flip()).collect()
+                }
+                Some(parts) => parts.clone(),
+            };
+
+            for part in parts {
+                let len = graph.node_len(part);
+                if pos >= start && pos + len <= end {
+                    graph.path_append_step(path, part);
+                }
+                pos += len;
+            }
+        }
+    }
+}
+
+/// Add the cuts needed at the ends of the interval.
+fn interval_cuts(
+    graph: &PackedGraph,
+    interval: &PathInterval,
+    steps: &CoveringSteps,
+    cuts: &mut NodeCuts,
+) {
+    let first = steps.handles[0];
+    cuts.add(graph, first, interval.start - steps.first_step_pos);
+
+    let last = *steps.handles.last().unwrap();
+    let last_len = graph.node_len(last);
+    let total_len: usize =
+        steps.handles.iter().map(|&h| graph.node_len(h)).sum();
+    let last_step_pos = steps.first_step_pos + total_len - last_len;
+    cuts.add(graph, last, interval.end - last_step_pos);
+}
+
+/// Inject each of the provided intervals as a new path.
+///
+/// All intervals are resolved against the paths as they are before
+/// any nodes are divided, and each node is divided at most once, at
+/// all the interval ends that fall inside it. The existing paths are
+/// updated by `divide_handle`, and so still spell the same
+/// sequences.
+///
+/// Returns the ID of each new path, in the same order as the
+/// intervals; an entry is `None` if the interval couldn't be found on
+/// its source path, or if a path with that name already exists.
+pub fn inject_intervals(
+    graph: &mut PackedGraph,
+    intervals: &[PathInterval],
+) -> Vec<Option<PathId>> {
+    let index = PathPositionMap::index_paths(graph);
+
+    // intervals whose names are already taken, either by an existing
+    // path or an earlier interval, are skipped before any nodes are
+    // divided, so that they leave the graph unchanged
+    let mut names = FnvHashSet::default();
+
+    let covering = intervals
+        .iter()
+        .map(|interval| {
+            if graph.get_path_id(&interval.name).is_some()
+                || !names.insert(interval.name.as_slice())
+            {
+                return None;
+            }
+            covering_steps(graph, &index, interval)
+        })
+        .collect::<Vec<_>>();
+
+    let mut cuts = NodeCuts::default();
+
+    for (interval, steps) in intervals.iter().zip(covering.iter()) {
+        if let Some(steps) = steps {
+            interval_cuts(graph, interval, steps, &mut cuts);
+        }
+    }
+
+    let divided = cuts.divide(graph);
+
+    intervals
+        .iter()
+        .zip(covering)
+        .map(|(interval, steps)| {
+            let steps = steps?;
+
+            let path_id = graph.create_path(&interval.name, false)?;
+
+            divided.append_range(
+                graph,
+                path_id,
+                steps.handles,
+                steps.first_step_pos,
+                (interval.start, interval.end),
+            );
+
+            Some(path_id)
+        })
+        .collect()
+}
+
+/// Inject the intervals in a BED file as new paths, using the `chrom`
+/// column as the name of the source path. New paths are named after
+/// the `name` column, or `chrom:start-end` if it's missing.
+///
+/// Returns the new path IDs in the same order as the BED records; see
+/// `inject_intervals`.
+pub fn inject_bed<R: BufRead>(
+    graph: &mut PackedGraph,
+    bed: R,
+) -> io::Result<Vec<Option<PathId>>> {
+    let records = read_bed(bed)?;
+
+    let mut record_ixs = Vec::with_capacity(records.len());
+    let mut intervals = Vec::with_capacity(records.len());
+
+    for (ix, record) in records.iter().enumerate() {
+        if let Some(source) = graph.get_path_id(&record.chrom) {
+            let name = match record.name() {
+                Some(name) if name != b"." => name.to_vec(),
+                _ => format!(
+                    "{}:{}-{}",
+                    record.chrom.as_bstr(),
+                    record.start,
+                    record.end
+                )
+                .into_bytes(),
+            };
+
+            record_ixs.push(ix);
+            intervals.push(PathInterval {
+                source,
+                start: record.start,
+                end: record.end,
+                name,
+            });
+        }
+    }
+
+    let injected = inject_intervals(graph, &intervals);
+
+    let mut result = vec![None; records.len()];
+    for (ix, path_id) in record_ixs.into_iter().zip(injected) {
+        result[ix] = path_id;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conversion::fasta::path_sequence;
+    use crate::handle::Edge;
+    use crate::util::test::{add_path, hnd, small_graph};
+
+    fn test_graph() -> PackedGraph {
+        let mut graph: PackedGraph = small_graph();
+
+        let n4 = graph.append_handle(b"CCGTA");
+        graph.create_edge(Edge(hnd(3), n4));
+
+        let steps = [hnd(1), hnd(2), hnd(3), n4];
+        add_path(&mut graph, b"ref", &steps);
+
+        let rev = steps.iter().rev().map(|h| h.flip()).collect::<Vec<_>>();
+        add_path(&mut graph, b"rev", &rev);
+
+        graph
+    }
+
+    #[test]
+    fn inject_bed_intervals() {
+        let mut graph = test_graph();
+
+        let ref_path = graph.get_path_id(b"ref").unwrap();
+        let rev_path = graph.get_path_id(b"rev").unwrap();
+
+        let ref_seq = path_sequence(&graph, ref_path).unwrap();
+        let rev_seq = path_sequence(&graph, rev_path).unwrap();
+
+        assert_eq!(ref_seq, b"AAACGGTCACCGTA");
+
+        let bed = b"ref\t2\t7\tgene\n\
+                    ref\t4\t6\n\
+                    rev\t1\t4\tanti\n\
+                    ref\t9\t9\tempty\n\
+                    missing\t0\t2\tnope\n";
+
+        let injected = inject_bed(&mut graph, &bed[..]).unwrap();
+
+        assert!(injected[0].is_some());
+        assert!(injected[1].is_some());
+        assert!(injected[2].is_some());
+        assert_eq!(injected[3], None);
+        assert_eq!(injected[4], None);
+
+        // the original paths are unchanged
+        assert_eq!(path_sequence(&graph, ref_path).unwrap(), ref_seq);
+        assert_eq!(path_sequence(&graph, rev_path).unwrap(), rev_seq);
+
+        let gene = injected[0].unwrap();
+        assert_eq!(path_sequence(&graph, gene).unwrap(), &ref_seq[2..7]);
+
+        let unnamed = graph.get_path_id(b"ref:4-6").unwrap();
+        assert_eq!(injected[1], Some(unnamed));
+        assert_eq!(path_sequence(&graph, unnamed).unwrap(), b"GG");
+        assert_eq!(graph.path_len(unnamed), Some(1));
+
+        let anti = injected[2].unwrap();
+        assert_eq!(path_sequence(&graph, anti).unwrap(), &rev_seq[1..4]);
+        assert!(graph
+            .path_steps(anti)
+            .unwrap()
+            .all(|step| step.handle().is_reverse()));
+
+        // injecting the same name again fails, but other intervals
+        // can be injected on the already divided paths
+        let bed = b"ref\t0\t2\tgene\nref\t3\t8\tgene2\n";
+        let again = inject_bed(&mut graph, &bed[..]).unwrap();
+        assert_eq!(again[0], None);
+
+        let gene2 = again[1].unwrap();
+        assert_eq!(path_sequence(&graph, gene2).unwrap(), &ref_seq[3..8]);
+        assert_eq!(path_sequence(&graph, ref_path).unwrap(), ref_seq);
+    }
+
+    #[test]
+    fn inject_name_collisions() {
+        let mut graph = test_graph();
+
+        let node_count = graph.node_count();
+        let edge_count = graph.edge_count();
+
+        // both intervals would divide the first node, but one is
+        // named after an existing path, and the other after an
+        // earlier interval in the same batch
+        let bed = b"ref\t0\t3\tgene\n\
+                    ref\t1\t3\tref\n\
+                    ref\t2\t5\tgene\n";
+
+        let injected = inject_bed(&mut graph, &bed[..]).unwrap();
+        assert!(injected[0].is_some());
+        assert_eq!(injected[1], None);
+        assert_eq!(injected[2], None);
+
+        // only the cut at the end of the first interval was made
+        assert_eq!(graph.node_count(), node_count + 1);
+        assert_eq!(graph.edge_count(), edge_count + 1);
+
+        let gene = injected[0].unwrap();
+        assert_eq!(path_sequence(&graph, gene).unwrap(), b"AAA");
+    }
+}
@@ -0,0 +1,632 @@
+/*!
+
+Decide whether two graphs are the same graph, up to the IDs and
+orientations of their nodes.
+
+An isomorphism maps every node of the first graph to a node of the
+second, possibly flipping its orientation, so that the oriented
+sequences, the edges, and the embedded paths all correspond. Paths
+must have the same names and circularity in both graphs, and the
+steps of a path are compared in order.
+
+The search starts by labeling each oriented node by its sequence, and
+refining the labels by those of its neighbors for a few rounds. The
+steps of the paths then fix the mapping for every node on a path,
+and the remaining nodes are matched by a backtracking search that
+only considers candidates with the same label that are adjacent to
+the images of nodes that are already matched. Graphs that are mostly
+covered by paths, or whose nodes have mostly distinct sequences, are
+therefore matched in close to linear time.
+
+*/
+
+use crate::{
+    handle::{Direction, Handle, NodeId},
+    handlegraph::*,
+    pathhandlegraph::*,
+    util::dna,
+};
+
+use fnv::{FnvHashMap, FnvHasher};
+
+use std::collections::VecDeque;
+use std::hash::Hasher;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// The maximum number of rounds of label refinement. The labels are
+/// only used to prune the search, so there's no need to refine them
+/// until they're stable.
+const REFINE_ROUNDS: usize = 8;
+
+const UNMAPPED: usize = usize::MAX;
+
+/// A graph with its nodes numbered 0..N in order of ID. Oriented
+/// nodes are numbered `2 * node + is_reverse`.
+struct IndexedGraph {
+    ids: Vec<NodeId>,
+    seqs: Vec<Vec<u8>>,
+    right: Vec<Vec<usize>>,
+    left: Vec<Vec<usize>>,
+    paths: Vec<(Vec<u8>, bool, Vec<usize>)>,
+}
+
+fn oriented_ix(index: &FnvHashMap<NodeId, usize>, handle: Handle) -> usize {
+    2 * index[&handle.id()] + handle.is_reverse() as usize
+}
+
+impl IndexedGraph {
+    fn new<G>(graph: G) -> Self
+    where
+        G: HandleGraphRef + GraphPathNames + IntoPathIds + GraphPathsSteps,
+    {
+        let mut ids = graph.handles().map(|h| h.id()).collect::<Vec<_>>();
+        ids.sort();
+
+        let index: FnvHashMap<NodeId, usize> =
+            ids.iter().enumerate().map(|(ix, id)| (*id, ix)).collect();
+
+        let seqs = ids
+            .iter()
+            .map(|&id| graph.sequence_vec(Handle::pack(id, false)))
+            .collect();
+
+        let neighbors = |dir: Direction| {
+            (0..2 * ids.len())
+                .map(|o| {
+                    let handle = Handle::pack(ids[o / 2], o & 1 == 1);
+                    let mut ns = graph
+                        .neighbors(handle, dir)
+                        .map(|h| oriented_ix(&index, h))
+                        .collect::<Vec<_>>();
+                    ns.sort_unstable();
+                    ns.dedup();
+                    ns
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let right = neighbors(Direction::Right);
+        let left = neighbors(Direction::Left);
+
+        let mut paths = graph
+            .path_ids()
+            .filter_map(|path| {
+                let name = graph.get_path_name_vec(path)?;
+                let circular = graph.path_circular(path)?;
+                let steps = graph
+                    .path_steps(path)?
+                    .map(|s| oriented_ix(&index, s.handle()))
+                    .collect();
+                Some((name, circular, steps))
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        Self {
+            ids,
+            seqs,
+            right,
+            left,
+            paths,
+        }
+    }
+
+    fn node_count(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Compare the sequence of node `node` of `self` to the oriented
+    /// node `other` of `graph`.
+    fn same_seq(&self, node: usize, graph: &Self, other: usize) -> bool {
+        let seq = &self.seqs[node];
+        let other_seq = &graph.seqs[other / 2];
+        if other & 1 == 0 {
+            seq == other_seq
+        } else {
+            seq.len() == other_seq.len()
+                && seq.iter().copied().eq(dna::rev_comp_iter(other_seq))
+        }
+    }
+
+    fn initial_labels(&self) -> Vec<u64> {
+        (0..2 * self.node_count())
+            .map(|o| {
+                let seq = &self.seqs[o / 2];
+                let mut hasher = FnvHasher::default();
+                if o & 1 == 0 {
+                    hasher.write(seq);
+                } else {
+                    hasher.write(&dna::rev_comp(seq));
+                }
+                hasher.finish()
+            })
+            .collect()
+    }
+
+    fn refine_labels(&self, labels: &[u64]) -> Vec<u64> {
+        let mut buf: Vec<u64> = Vec::new();
+        (0..labels.len())
+            .map(|o| {
+                let mut hasher = FnvHasher::default();
+                hasher.write_u64(labels[o]);
+                for ns in [&self.right[o], &self.left[o]].iter() {
+                    buf.clear();
+                    buf.extend(ns.iter().map(|&n| labels[n]));
+                    buf.sort_unstable();
+                    hasher.write_usize(buf.len());
+                    buf.iter().for_each(|&l| hasher.write_u64(l));
+                }
+                hasher.finish()
+            })
+            .collect()
+    }
+}
+
+fn label_histogram(labels: &[u64]) -> Vec<u64> {
+    let mut hist = labels.to_vec();
+    hist.sort_unstable();
+    hist
+}
+
+fn distinct_labels(hist: &[u64]) -> usize {
+    let mut count = 0;
+    let mut prev = None;
+    for &l in hist {
+        if prev != Some(l) {
+            count += 1;
+            prev = Some(l);
+        }
+    }
+    count
+}
+
+struct Matcher<'a> {
+    a: &'a IndexedGraph,
+    b: &'a IndexedGraph,
+    a_labels: Vec<u64>,
+    b_labels: Vec<u64>,
+    b_classes: FnvHashMap<u64, Vec<usize>>,
+    /// The oriented node of `b` that each forward node of `a` maps
+    /// to.
+    map: Vec<usize>,
+    used: Vec<bool>,
+}
+
+impl<'a> Matcher<'a> {
+    fn image(&self, o: usize) -> Option<usize> {
+        let m = self.map[o / 2];
+        if m == UNMAPPED {
+            None
+        } else {
+            Some(m ^ (o & 1))
+        }
+    }
+
+    /// Check if the forward node `u` of `a` can be mapped to the
+    /// oriented node `target` of `b`, given the nodes that are
+    /// already mapped.
+    fn consistent(&self, u: usize, target: usize) -> bool {
+        let fwd = 2 * u;
+        if self.used[target / 2]
+            || self.a_labels[fwd] != self.b_labels[target]
+            || !self.a.same_seq(u, self.b, target)
+        {
+            return false;
+        }
+
+        let image = |o: usize| {
+            if o / 2 == u {
+                Some(target ^ (o & 1))
+            } else {
+                self.image(o)
+            }
+        };
+
+        let check = |a_ns: &[usize], b_ns: &[usize]| {
+            a_ns.len() == b_ns.len()
+                && a_ns.iter().all(|&n| match image(n) {
+                    Some(m) => b_ns.binary_search(&m).is_ok(),
+                    None => true,
+                })
+        };
+
+        check(&self.a.right[fwd], &self.b.right[target])
+            && check(&self.a.left[fwd], &self.b.left[target])
+    }
+
+    fn assign(&mut self, u: usize, target: usize) {
+        self.map[u] = target;
+        self.used[target / 2] = true;
+    }
+
+    fn unassign(&mut self, u: usize) {
+        let target = self.map[u];
+        if target != UNMAPPED {
+            self.used[target / 2] = false;
+            self.map[u] = UNMAPPED;
+        }
+    }
+
+    /// Map the nodes on the paths of `a` to those on the paths of
+    /// `b` with the same names.
+    fn map_paths(&mut self) -> bool {
+        let b_paths: FnvHashMap<&[u8], (bool, &[usize])> = self
+            .b
+            .paths
+            .iter()
+            .map(|(name, circ, steps)| (name.as_slice(), (*circ, &steps[..])))
+            .collect();
+
+        for (name, circular, steps) in self.a.paths.iter() {
+            let (b_circular, b_steps) = match b_paths.get(name.as_slice()) {
+                Some(path) => *path,
+                None => return false,
+            };
+            if *circular != b_circular || steps.len() != b_steps.len() {
+                return false;
+            }
+
+            for (&a_step, &b_step) in steps.iter().zip(b_steps.iter()) {
+                let u = a_step / 2;
+                let target = b_step ^ (a_step & 1);
+                if self.map[u] == UNMAPPED {
+                    if !self.consistent(u, target) {
+                        return false;
+                    }
+                    self.assign(u, target);
+                } else if self.map[u] != target {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// The order in which the unmapped nodes are searched, so that
+    /// each node is adjacent to an earlier one whenever possible.
+    fn search_order(&self) -> Vec<usize> {
+        let n = self.a.node_count();
+        let mut visited =
+            self.map.iter().map(|&m| m != UNMAPPED).collect::<Vec<_>>();
+
+        let mut seeds = (0..n).filter(|&u| !visited[u]).collect::<Vec<_>>();
+        seeds.sort_by_key(|&u| {
+            let class = self.b_classes.get(&self.a_labels[2 * u]);
+            (class.map(|c| c.len()).unwrap_or(0), u)
+        });
+
+        let mut order = Vec::with_capacity(seeds.len());
+        let mut queue: VecDeque<usize> =
+            (0..n).filter(|&u| visited[u]).collect();
+
+        let mut seeds = seeds.into_iter();
+        loop {
+            while let Some(u) = queue.pop_front() {
+                let fwd = 2 * u;
+                for &o in self.a.right[fwd].iter().chain(&self.a.left[fwd]) {
+                    let v = o / 2;
+                    if !visited[v] {
+                        visited[v] = true;
+                        order.push(v);
+                        queue.push_back(v);
+                    }
+                }
+            }
+            match seeds.find(|&u| !visited[u]) {
+                Some(u) => {
+                    visited[u] = true;
+                    order.push(u);
+                    queue.push_back(u);
+                }
+                None => break,
+            }
+        }
+        order
+    }
+
+    fn candidates(&self, u: usize) -> Vec<usize> {
+        let fwd = 2 * u;
+        // an edge y -> u means image(u) is to the right of image(y)
+        for &y in self.a.left[fwd].iter() {
+            if let Some(m) = self.image(y) {
+                return self.b.right[m].clone();
+            }
+        }
+        for &y in self.a.right[fwd].iter() {
+            if let Some(m) = self.image(y) {
+                return self.b.left[m].clone();
+            }
+        }
+        self.b_classes
+            .get(&self.a_labels[fwd])
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn search(&mut self) -> bool {
+        let order = self.search_order();
+        let mut frames: Vec<(Vec<usize>, usize)> =
+            Vec::with_capacity(order.len());
+
+        loop {
+            if frames.len() < order.len() {
+                let u = order[frames.len()];
+                frames.push((self.candidates(u), 0));
+            } else {
+                // every node was checked against all of its neighbors
+                // when it was mapped, and the degrees match, so the
+                // mapping is complete
+                return true;
+            }
+
+            // try the next candidate for the deepest node, backtracking
+            // as long as there are none left
+            loop {
+                let depth = match frames.len().checked_sub(1) {
+                    Some(depth) => depth,
+                    None => return false,
+                };
+                let u = order[depth];
+                self.unassign(u);
+
+                let (cands, next) = frames.last_mut().unwrap();
+                let found =
+                    cands[*next..].iter().position(|&c| self.consistent(u, c));
+
+                let (cands, next) = frames.last_mut().unwrap();
+                match found {
+                    Some(ix) => {
+                        let target = cands[*next + ix];
+                        *next += ix + 1;
+                        self.assign(u, target);
+                        break;
+                    }
+                    None => {
+                        frames.pop();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Find an isomorphism from the graph `a` to the graph `b`, if they
+/// are isomorphic. The isomorphism is given as the handle in `b` that
+/// the forward handle of each node in `a` maps to, in order of the
+/// node IDs of `a`.
+///
+/// In the worst case, e.g. for large graphs without paths and with
+/// many nodes that share sequences and neighborhoods, this takes
+/// exponential time.
+pub fn find_isomorphism<A, B>(a: A, b: B) -> Option<Vec<(NodeId, Handle)>>
+where
+    A: HandleGraphRef + GraphPathNames + IntoPathIds + GraphPathsSteps,
+    B: HandleGraphRef + GraphPathNames + IntoPathIds + GraphPathsSteps,
+{
+    let a = IndexedGraph::new(a);
+    let b = IndexedGraph::new(b);
+
+    let degrees =
+        |g: &IndexedGraph| -> usize { g.right.iter().map(|ns| ns.len()).sum() };
+
+    if a.node_count() != b.node_count()
+        || a.paths.len() != b.paths.len()
+        || degrees(&a) != degrees(&b)
+    {
+        return None;
+    }
+
+    let mut a_labels = a.initial_labels();
+    let mut b_labels = b.initial_labels();
+
+    let mut a_hist = label_histogram(&a_labels);
+    if a_hist != label_histogram(&b_labels) {
+        return None;
+    }
+
+    let mut distinct = distinct_labels(&a_hist);
+    for _ in 0..REFINE_ROUNDS {
+        let a_next = a.refine_labels(&a_labels);
+        let b_next = b.refine_labels(&b_labels);
+
+        let next_hist = label_histogram(&a_next);
+        if next_hist != label_histogram(&b_next) {
+            return None;
+        }
+
+        let next_distinct = distinct_labels(&next_hist);
+        a_labels = a_next;
+        b_labels = b_next;
+        a_hist = next_hist;
+        if next_distinct == distinct {
+            break;
+        }
+        distinct = next_distinct;
+    }
+    debug!(
+        "isomorphism: {} distinct labels over {} oriented nodes",
+        distinct,
+        a_hist.len()
+    );
+
+    let mut b_classes: FnvHashMap<u64, Vec<usize>> = FnvHashMap::default();
+    for (o, &label) in b_labels.iter().enumerate() {
+        b_classes.entry(label).or_default().push(o);
+    }
+
+    let mut matcher = Matcher {
+        a: &a,
+        b: &b,
+        a_labels,
+        b_labels,
+        b_classes,
+        map: vec![UNMAPPED; a.node_count()],
+        used: vec![false; b.node_count()],
+    };
+
+    if !matcher.map_paths() || !matcher.search() {
+        return None;
+    }
+
+    let result = a
+        .ids
+        .iter()
+        .zip(matcher.map.iter())
+        .map(|(&id, &m)| (id, Handle::pack(b.ids[m / 2], m & 1 == 1)))
+        .collect();
+    Some(result)
+}
+
+/// Returns `true` if the graphs are equal up to the IDs and
+/// orientations of their nodes, including their paths.
+pub fn is_isomorphic<A, B>(a: A, b: B) -> bool
+where
+    A: HandleGraphRef + GraphPathNames + IntoPathIds + GraphPathsSteps,
+    B: HandleGraphRef + GraphPathNames + IntoPathIds + GraphPathsSteps,
+{
+    find_isomorphism(a, b).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        handle::Edge,
+        hashgraph::HashGraph,
+        mutablehandlegraph::*,
+        packedgraph::PackedGraph,
+        util::test::{add_path, hnd, r_hnd, test_graph, test_graph_no_paths},
+    };
+
+    /// Copy `graph`, mapping the forward handle of each node to the
+    /// handle `f(id)`, and optionally leaving out the paths.
+    fn relabeled<F>(graph: &PackedGraph, f: F, with_paths: bool) -> HashGraph
+    where
+        F: Fn(NodeId) -> Handle,
+    {
+        let map = |h: Handle| {
+            let new = f(h.id());
+            if h.is_reverse() {
+                new.flip()
+            } else {
+                new
+            }
+        };
+
+        let mut other = HashGraph::default();
+        for h in graph.handles() {
+            let new = f(h.id());
+            let seq =
+                graph.sequence_vec(if new.is_reverse() { h.flip() } else { h });
+            other.create_handle(&seq, new.id());
+        }
+        for Edge(l, r) in graph.edges() {
+            other.create_edge(Edge(map(l), map(r)));
+        }
+        if with_paths {
+            for path in graph.path_ids() {
+                let name = graph.get_path_name_vec(path).unwrap();
+                let new_path = other.create_path(&name, false).unwrap();
+                for step in graph.path_steps(path).unwrap() {
+                    other.path_append_step(new_path, map(step.handle()));
+                }
+            }
+        }
+        other
+    }
+
+    fn flip_odd(id: NodeId) -> Handle {
+        Handle::pack(20 - id.0, id.0 % 2 == 1)
+    }
+
+    #[test]
+    fn isomorphic_graph_types() {
+        let packed: PackedGraph = test_graph();
+        let hash: HashGraph = test_graph();
+
+        let iso = find_isomorphism(&packed, &hash).unwrap();
+        assert!(iso.iter().all(|&(id, h)| h == Handle::pack(id, false)));
+        assert!(is_isomorphic(&hash, &packed));
+
+        let mut ordered: PackedGraph = test_graph();
+        let order = (1..=9).rev().map(hnd).collect::<Vec<_>>();
+        ordered.apply_ordering(&order);
+        assert!(is_isomorphic(&packed, &ordered));
+    }
+
+    #[test]
+    fn isomorphic_relabeled_and_flipped() {
+        let graph: PackedGraph = test_graph();
+        let other = relabeled(&graph, flip_odd, true);
+
+        let iso = find_isomorphism(&graph, &other).unwrap();
+        assert!(iso.iter().all(|&(id, h)| h == flip_odd(id)));
+
+        // without the paths, the nodes are matched by the search
+        let graph_no_paths: PackedGraph = test_graph_no_paths();
+        let other_no_paths = relabeled(&graph_no_paths, flip_odd, false);
+        let iso = find_isomorphism(&graph_no_paths, &other_no_paths).unwrap();
+        assert!(iso.iter().all(|&(id, h)| h == flip_odd(id)));
+
+        assert!(!is_isomorphic(&graph, &other_no_paths));
+    }
+
+    #[test]
+    fn not_isomorphic() {
+        let graph: PackedGraph = test_graph();
+
+        let mut changed: PackedGraph = test_graph();
+        changed.remove_edge(Edge(hnd(1), hnd(8)));
+        changed.create_edge(Edge(hnd(1), hnd(3)));
+        let other = relabeled(&changed, flip_odd, true);
+        assert!(!is_isomorphic(&graph, &other));
+
+        let mut other = relabeled(&graph, flip_odd, true);
+        let path = other.get_path_id(b"path2").unwrap();
+        other.path_set_circularity(path, true);
+        assert!(!is_isomorphic(&graph, &other));
+
+        // a path with the same nodes, but in a different orientation
+        let mut other: PackedGraph = test_graph_no_paths();
+        add_path(
+            &mut other,
+            b"path1",
+            &[hnd(1), hnd(2), hnd(7), hnd(3), hnd(9)],
+        );
+        add_path(&mut other, b"path2", &[hnd(6), hnd(4), r_hnd(8), r_hnd(1)]);
+        assert!(!is_isomorphic(&graph, &other));
+    }
+
+    #[test]
+    fn isomorphism_needs_backtracking() {
+        // a cycle of six identical nodes, and two cycles of three,
+        // can't be told apart by their labels
+        let cycles = |cycles: &[&[u64]]| {
+            let mut graph = HashGraph::default();
+            for cycle in cycles {
+                for &id in cycle.iter() {
+                    graph.create_handle(b"A", id);
+                }
+                for (ix, &id) in cycle.iter().enumerate() {
+                    let next = cycle[(ix + 1) % cycle.len()];
+                    graph.create_edge(Edge(hnd(id), hnd(next)));
+                }
+            }
+            graph
+        };
+
+        let hexagon = cycles(&[&[1, 2, 3, 4, 5, 6]]);
+        let shuffled = cycles(&[&[4, 2, 6, 1, 5, 3]]);
+        let triangles = cycles(&[&[1, 2, 3], &[4, 5, 6]]);
+
+        assert!(is_isomorphic(&hexagon, &shuffled));
+        assert!(!is_isomorphic(&hexagon, &triangles));
+        assert!(is_isomorphic(
+            &triangles,
+            &cycles(&[&[6, 2, 4], &[3, 1, 5]])
+        ));
+    }
+}
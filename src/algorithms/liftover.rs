@@ -0,0 +1,473 @@
+/*!
+
+Lift positions on one embedded path over to another path, by way of
+the graph.
+
+A position on the source path is first resolved to a node and an
+offset into that node. If the target path doesn't visit that node,
+the graph is searched outward from the position, in both directions,
+until the closest node that is on the target path is found, within a
+radius limited both in bases and in steps.
+
+Positions given to and returned from this module are 0-based, like in
+BED, while the `PathPositionMap` index uses 1-based positions.
+
+*/
+
+use crate::{
+    handle::{Direction, Handle, NodeId},
+    handlegraph::*,
+    pathhandlegraph::*,
+};
+
+use crate::packedgraph::paths::StepPtr;
+use crate::packedgraph::PackedGraph;
+use crate::path_position::PathPositionMap;
+use crate::util::bed::{read_bed, BedRecord};
+
+use fnv::FnvHashSet;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{self, BufRead, Write};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// Limits on how far from the source position the search for the
+/// target path may go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiftoverParams {
+    /// The maximum number of bases between the source position and
+    /// the lifted position.
+    pub max_bases: usize,
+    /// The maximum number of nodes the search may step across.
+    pub max_steps: usize,
+}
+
+impl Default for LiftoverParams {
+    fn default() -> Self {
+        Self {
+            max_bases: 1000,
+            max_steps: 100,
+        }
+    }
+}
+
+/// A position on the target path that a source position was lifted
+/// to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiftedPosition {
+    pub path: PathId,
+    /// 0-based position on the target path.
+    pub pos: usize,
+    /// `true` if the target path traverses the node in the opposite
+    /// orientation from the source path.
+    pub is_reverse: bool,
+    /// The distance in bases that had to be traversed in the graph
+    /// to find the target path; zero if both paths share the node.
+    pub distance: usize,
+}
+
+/// Resolve the 0-based position `pos` on `path` to the step that
+/// covers it, and the offset into that step's handle.
+pub fn path_pos_to_step(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    path: PathId,
+    pos: usize,
+) -> Option<(StepPtr, Handle, usize)> {
+    let step = index.find_step_at_base(path, pos + 1)?;
+    let step_pos = index.path_step_position(path, step)?;
+    let handle = graph.path_handle_at_step(path, step)?;
+    Some((step, handle, pos + 1 - step_pos))
+}
+
+/// Find the position on `target` that's on the node `handle`, at
+/// `offset` bases into `handle`. If `target` visits the node more
+/// than once, the first visit on the path is used.
+fn project_onto_path(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    target: PathId,
+    handle: Handle,
+    offset: usize,
+) -> Option<(usize, bool)> {
+    let node_len = graph.node_len(handle);
+
+    graph
+        .steps_on_handle(handle)?
+        .filter(|(path, _)| *path == target)
+        .filter_map(|(_, step)| {
+            let step_handle = graph.path_handle_at_step(target, step)?;
+            let step_pos = index.path_step_position(target, step)?;
+
+            let is_reverse = step_handle != handle;
+            let step_offset = if is_reverse {
+                node_len - 1 - offset
+            } else {
+                offset
+            };
+
+            Some((step_pos - 1 + step_offset, is_reverse))
+        })
+        .min()
+}
+
+/// Lift the 0-based position `pos` on the path `source` over to the
+/// path `target`.
+///
+/// The search from `pos` is a shortest-path search in bases, over the
+/// neighbors in both directions; the first node on `target` that is
+/// reached is used. Moving to a neighbor on the right lands on its
+/// first base, and moving to the left lands on its last base.
+///
+/// Returns `None` if either path doesn't exist, `pos` is outside the
+/// source path, or no node on `target` could be found within the
+/// limits in `params`.
+pub fn liftover(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    source: PathId,
+    pos: usize,
+    target: PathId,
+    params: &LiftoverParams,
+) -> Option<LiftedPosition> {
+    let (_step, handle, offset) = path_pos_to_step(graph, index, source, pos)?;
+
+    let mut visited: FnvHashSet<NodeId> = FnvHashSet::default();
+
+    // (distance, steps, handle, offset)
+    let mut queue: BinaryHeap<Reverse<(usize, usize, Handle, usize)>> =
+        BinaryHeap::new();
+
+    queue.push(Reverse((0, 0, handle, offset)));
+
+    while let Some(Reverse((dist, steps, handle, offset))) = queue.pop() {
+        if !visited.insert(handle.id()) {
+            continue;
+        }
+
+        if let Some((pos, is_reverse)) =
+            project_onto_path(graph, index, target, handle, offset)
+        {
+            return Some(LiftedPosition {
+                path: target,
+                pos,
+                is_reverse,
+                distance: dist,
+            });
+        }
+
+        if steps >= params.max_steps {
+            continue;
+        }
+
+        let node_len = graph.node_len(handle);
+
+        let right_dist = dist + node_len - offset;
+        if right_dist <= params.max_bases {
+            for next in graph.neighbors(handle, Direction::Right) {
+                if !visited.contains(&next.id()) {
+                    queue.push(Reverse((right_dist, steps + 1, next, 0)));
+                }
+            }
+        }
+
+        let left_dist = dist + offset + 1;
+        if left_dist <= params.max_bases {
+            for prev in graph.neighbors(handle, Direction::Left) {
+                if !visited.contains(&prev.id()) {
+                    let prev_offset = graph.node_len(prev) - 1;
+                    queue.push(Reverse((
+                        left_dist,
+                        steps + 1,
+                        prev,
+                        prev_offset,
+                    )));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Lift a BED interval on `source` over to `target`, by lifting its
+/// first and last bases. The interval is only lifted if both ends
+/// land on the target path in the same orientation; if they land in
+/// the reverse orientation, the resulting interval is flipped so that
+/// `start <= end`.
+///
+/// Returns the target interval as `(start, end, is_reverse)`.
+pub fn liftover_interval(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    source: PathId,
+    start: usize,
+    end: usize,
+    target: PathId,
+    params: &LiftoverParams,
+) -> Option<(usize, usize, bool)> {
+    if end <= start {
+        return None;
+    }
+
+    let first = liftover(graph, index, source, start, target, params)?;
+    let last = liftover(graph, index, source, end - 1, target, params)?;
+
+    if first.is_reverse != last.is_reverse {
+        return None;
+    }
+
+    let (start, end) = if first.is_reverse {
+        (last.pos, first.pos + 1)
+    } else {
+        (first.pos, last.pos + 1)
+    };
+
+    if end <= start {
+        return None;
+    }
+
+    Some((start, end, first.is_reverse))
+}
+
+/// Lift a single BED record over to `target`, using the record's
+/// `chrom` column as the name of the source path.
+///
+/// The resulting record has `chrom` set to the target path name, and
+/// the strand column set to the strand of the lifted interval,
+/// relative to the strand of the original record, if any.
+pub fn liftover_bed_record(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    record: &BedRecord,
+    target: PathId,
+    params: &LiftoverParams,
+) -> Option<BedRecord> {
+    let source = graph.get_path_id(&record.chrom)?;
+    let target_name = graph.get_path_name_vec(target)?;
+
+    let (start, end, is_reverse) = liftover_interval(
+        graph,
+        index,
+        source,
+        record.start,
+        record.end,
+        target,
+        params,
+    )?;
+
+    let mut lifted = record.clone();
+    lifted.chrom = target_name;
+    lifted.start = start;
+    lifted.end = end;
+
+    if is_reverse {
+        let strand = record.is_reverse().unwrap_or(false);
+        lifted.set_strand(!strand);
+    }
+
+    Some(lifted)
+}
+
+/// Lift all the BED records in `bed` over to `target`, writing the
+/// lifted records to `out` as BED.
+///
+/// Returns the records that couldn't be lifted.
+pub fn liftover_bed<R: BufRead, W: Write>(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    bed: R,
+    target: PathId,
+    params: &LiftoverParams,
+    out: &mut W,
+) -> io::Result<Vec<BedRecord>> {
+    let records = read_bed(bed)?;
+
+    let mut unlifted = Vec::new();
+
+    for record in records {
+        match liftover_bed_record(graph, index, &record, target, params) {
+            Some(lifted) => lifted.write(out)?,
+            None => unlifted.push(record),
+        }
+    }
+
+    debug!("liftover: {} records could not be lifted", unlifted.len());
+
+    Ok(unlifted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::handle::Edge;
+    use crate::mutablehandlegraph::*;
+    use crate::util::test::add_path;
+
+    fn hnd(x: u64) -> Handle {
+        Handle::pack(x, false)
+    }
+
+    /// A bubble where `ref` takes the upper branch, and `alt` the
+    /// lower, with a third path traversing the graph in reverse.
+    ///
+    ///         GG
+    ///  AAAA <    > TTT - CCCCC
+    ///         CAC
+    fn test_graph() -> PackedGraph {
+        let mut graph = PackedGraph::default();
+
+        let n1 = graph.append_handle(b"AAAA");
+        let n2 = graph.append_handle(b"GG");
+        let n3 = graph.append_handle(b"CAC");
+        let n4 = graph.append_handle(b"TTT");
+        let n5 = graph.append_handle(b"CCCCC");
+
+        graph.create_edge(Edge(n1, n2));
+        graph.create_edge(Edge(n1, n3));
+        graph.create_edge(Edge(n2, n4));
+        graph.create_edge(Edge(n3, n4));
+        graph.create_edge(Edge(n4, n5));
+
+        add_path(&mut graph, b"ref", &[n1, n2, n4, n5]);
+        add_path(&mut graph, b"alt", &[n1, n3, n4]);
+        add_path(
+            &mut graph,
+            b"rev",
+            &[n5.flip(), n4.flip(), n3.flip(), n1.flip()],
+        );
+
+        graph
+    }
+
+    #[test]
+    fn find_step_at_base() {
+        let graph = test_graph();
+        let index = PathPositionMap::index_paths(&graph);
+
+        let ref_path = PathId(0);
+
+        let step_handles = (1..=15)
+            .map(|pos| {
+                let step = index.find_step_at_base(ref_path, pos)?;
+                graph.path_handle_at_step(ref_path, step)
+            })
+            .collect::<Vec<_>>();
+
+        let expected = [1, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 5, 5]
+            .iter()
+            .map(|&n| Some(hnd(n)))
+            .chain(std::iter::once(None))
+            .collect::<Vec<_>>();
+
+        assert_eq!(step_handles, expected);
+        assert_eq!(index.find_step_at_base(ref_path, 0), None);
+    }
+
+    #[test]
+    fn liftover_shared_node() {
+        let graph = test_graph();
+        let index = PathPositionMap::index_paths(&graph);
+        let params = LiftoverParams::default();
+
+        let ref_path = graph.get_path_id(b"ref").unwrap();
+        let alt_path = graph.get_path_id(b"alt").unwrap();
+        let rev_path = graph.get_path_id(b"rev").unwrap();
+
+        // base 7 on ref is the second T in node 4, which is base 8 on alt
+        let lifted =
+            liftover(&graph, &index, ref_path, 7, alt_path, &params).unwrap();
+        assert_eq!(lifted.pos, 8);
+        assert!(!lifted.is_reverse);
+        assert_eq!(lifted.distance, 0);
+
+        // ...and base 6 on rev, on the reverse strand
+        let lifted =
+            liftover(&graph, &index, ref_path, 7, rev_path, &params).unwrap();
+        assert_eq!(lifted.pos, 6);
+        assert!(lifted.is_reverse);
+        assert_eq!(lifted.distance, 0);
+
+        // lifting back gives the original position
+        let back =
+            liftover(&graph, &index, rev_path, 6, ref_path, &params).unwrap();
+        assert_eq!(back.pos, 7);
+        assert!(back.is_reverse);
+    }
+
+    #[test]
+    fn liftover_across_bubble() {
+        let graph = test_graph();
+        let index = PathPositionMap::index_paths(&graph);
+
+        let ref_path = graph.get_path_id(b"ref").unwrap();
+        let alt_path = graph.get_path_id(b"alt").unwrap();
+
+        // the last G in node 2 is one base from node 4
+        let params = LiftoverParams::default();
+        let lifted =
+            liftover(&graph, &index, ref_path, 5, alt_path, &params).unwrap();
+        assert_eq!(lifted.pos, 7);
+        assert_eq!(lifted.distance, 1);
+
+        // the first G in node 2 is one base from the end of node 1
+        let lifted =
+            liftover(&graph, &index, ref_path, 4, alt_path, &params).unwrap();
+        assert_eq!(lifted.pos, 3);
+        assert_eq!(lifted.distance, 1);
+
+        // node 5 isn't on alt, and node 4 is 1 step or 1 base away
+        let lifted =
+            liftover(&graph, &index, ref_path, 9, alt_path, &params).unwrap();
+        assert_eq!(lifted.pos, 9);
+        assert_eq!(lifted.distance, 1);
+
+        let lifted =
+            liftover(&graph, &index, ref_path, 13, alt_path, &params).unwrap();
+        assert_eq!(lifted.distance, 5);
+
+        let params = LiftoverParams {
+            max_bases: 4,
+            max_steps: 10,
+        };
+        assert!(
+            liftover(&graph, &index, ref_path, 13, alt_path, &params).is_none()
+        );
+
+        let params = LiftoverParams {
+            max_bases: 100,
+            max_steps: 0,
+        };
+        assert!(
+            liftover(&graph, &index, ref_path, 5, alt_path, &params).is_none()
+        );
+    }
+
+    #[test]
+    fn liftover_bed_batch() {
+        let graph = test_graph();
+        let index = PathPositionMap::index_paths(&graph);
+        let params = LiftoverParams::default();
+
+        let rev_path = graph.get_path_id(b"rev").unwrap();
+
+        let bed = b"ref\t0\t4\tfirst\nref\t6\t9\tsecond\t0\t-\nnope\t0\t1\n";
+
+        let mut out: Vec<u8> = Vec::new();
+        let unlifted =
+            liftover_bed(&graph, &index, &bed[..], rev_path, &params, &mut out)
+                .unwrap();
+
+        assert_eq!(unlifted, vec![BedRecord::new(b"nope", 0, 1)]);
+
+        // rev is 5 + 3 + 3 + 4 bases long
+        assert_eq!(
+            out,
+            &b"rev\t11\t15\tfirst\t0\t-\nrev\t5\t8\tsecond\t0\t+\n"[..]
+        );
+    }
+}
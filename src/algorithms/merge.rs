@@ -0,0 +1,523 @@
+/*!
+
+Combining several graphs, e.g. one per chromosome, into one graph.
+
+The node IDs of the inputs can either be offset, so that each graph's
+nodes get IDs above those of the graphs before it, or be kept as they
+are, if the graphs are known to use disjoint IDs. A path whose name is
+already taken in the merged graph is renamed by appending `.1`, `.2`,
+etc., whichever is the first that's free.
+
+[`merge_graphs`] builds a new graph from graphs that are all in
+memory, while [`merge_gfa_lines`] adds the lines of a GFA to a graph
+as they are parsed, so that the graph being added never has to be
+held in memory.
+
+*/
+
+use crate::{
+    handle::{Edge, Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+};
+
+use fnv::FnvHashSet;
+
+use gfa::{
+    gfa::Line,
+    optfields::OptFields,
+    parser::{GFAResult, ParseError},
+};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// How to assign the node IDs of the graphs being merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdPolicy {
+    /// Add the largest node ID in the merged graph so far to each ID
+    /// of the next graph.
+    Offset,
+    /// Keep the IDs, and fail if the same ID is used twice.
+    Keep,
+}
+
+#[derive(Debug)]
+pub enum MergeError {
+    /// A node ID is used by more than one of the graphs.
+    IdConflict(NodeId),
+    /// A link or path refers to a segment that isn't in the GFA.
+    MissingSegment(usize),
+    Gfa(ParseError),
+}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::IdConflict(id) => {
+                write!(f, "node ID {} is used by more than one graph", id.0)
+            }
+            MergeError::MissingSegment(name) => {
+                write!(f, "segment {} is not in the GFA", name)
+            }
+            MergeError::Gfa(err) => write!(f, "{:?}", err),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+impl From<ParseError> for MergeError {
+    fn from(err: ParseError) -> Self {
+        MergeError::Gfa(err)
+    }
+}
+
+/// How one of the inputs was added to the merged graph.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MergedGraph {
+    /// The value added to each node ID of the input.
+    pub id_offset: u64,
+    /// The original and the new names of the renamed paths.
+    pub renamed_paths: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Create a path named `name`, or, if that's taken, the first free
+/// name of the form `name.N`, and record the renaming in `merged`.
+fn create_unique_path<T>(
+    graph: &mut T,
+    name: &[u8],
+    circular: bool,
+    merged: &mut MergedGraph,
+) -> PathId
+where
+    T: MutableGraphPaths,
+    for<'a> &'a T: GraphPathNames,
+{
+    let mut new_name = name.to_vec();
+    let mut suffix = 0;
+
+    while graph.get_path_id(&new_name).is_some() {
+        suffix += 1;
+        new_name = name.to_vec();
+        new_name.extend(format!(".{}", suffix).bytes());
+    }
+
+    if suffix > 0 {
+        merged.renamed_paths.push((name.to_vec(), new_name.clone()));
+    }
+
+    graph.create_path(&new_name, circular).unwrap()
+}
+
+fn shift(handle: Handle, offset: u64) -> Handle {
+    Handle::pack(handle.id().0 + offset, handle.is_reverse())
+}
+
+fn add_graph<G, T>(graph: &mut T, source: G, offset: u64) -> MergedGraph
+where
+    G: HandleGraphRef + GraphPathNames + IntoPathIds + GraphPathsSteps,
+    T: AdditiveHandleGraph + MutableGraphPaths,
+    for<'a> &'a T: GraphPathNames,
+{
+    let mut merged = MergedGraph {
+        id_offset: offset,
+        renamed_paths: Vec::new(),
+    };
+
+    let mut handles = source.handles().collect::<Vec<_>>();
+    handles.sort();
+
+    for handle in handles {
+        let seq = source.sequence_vec(handle);
+        graph.create_handle(&seq, handle.id().0 + offset);
+    }
+
+    for Edge(left, right) in source.edges() {
+        graph.create_edge(Edge(shift(left, offset), shift(right, offset)));
+    }
+
+    let mut paths = source.path_ids().collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+        let name = source.get_path_name_vec(path).unwrap_or_default();
+        let circular = source.path_circular(path).unwrap_or(false);
+        let new_path = create_unique_path(graph, &name, circular, &mut merged);
+
+        if let Some(steps) = source.path_steps(path) {
+            for step in steps {
+                graph.path_append_step(new_path, shift(step.handle(), offset));
+            }
+        }
+    }
+
+    merged
+}
+
+/// Merge `graphs` into a new graph, copying their nodes, edges, and
+/// paths in order, and assigning node IDs according to `ids`.
+/// Returns the merged graph, and how each input was added to it.
+pub fn merge_graphs<G, T>(
+    graphs: &[G],
+    ids: IdPolicy,
+) -> Result<(T, Vec<MergedGraph>), MergeError>
+where
+    G: HandleGraphRef + GraphPathNames + IntoPathIds + GraphPathsSteps,
+    T: HandleGraph + AdditiveHandleGraph + MutableGraphPaths + Default,
+    for<'a> &'a T: GraphPathNames,
+{
+    if ids == IdPolicy::Keep {
+        let mut seen: FnvHashSet<NodeId> = FnvHashSet::default();
+        for &source in graphs.iter() {
+            let mut handles = source.handles().collect::<Vec<_>>();
+            handles.sort();
+            if let Some(h) = handles.into_iter().find(|h| !seen.insert(h.id()))
+            {
+                return Err(MergeError::IdConflict(h.id()));
+            }
+        }
+    }
+
+    let mut graph = T::default();
+
+    let merged = graphs
+        .iter()
+        .map(|&source| {
+            let offset = match ids {
+                IdPolicy::Offset => graph.max_node_id().0,
+                IdPolicy::Keep => 0,
+            };
+            add_graph(&mut graph, source, offset)
+        })
+        .collect();
+
+    debug!(
+        "merged {} graphs into {} nodes",
+        graphs.len(),
+        graph.node_count()
+    );
+
+    Ok((graph, merged))
+}
+
+/// The segments referred to by a link or path line.
+fn line_segments<T: OptFields>(line: &Line<usize, T>) -> Vec<usize> {
+    match line {
+        Line::Link(v) => vec![v.from_segment, v.to_segment],
+        Line::Path(v) => v.iter().map(|(seg, _)| seg).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn add_gfa_line<G, T>(
+    graph: &mut G,
+    line: Line<usize, T>,
+    offset: u64,
+    merged: &mut MergedGraph,
+) where
+    G: AdditiveHandleGraph + MutableGraphPaths,
+    for<'a> &'a G: GraphPathNames,
+    T: OptFields,
+{
+    match line {
+        Line::Segment(v) => {
+            graph.create_handle(&v.sequence, v.name as u64 + offset);
+        }
+        Line::Link(v) => {
+            let left = Handle::new(v.from_segment, v.from_orient);
+            let right = Handle::new(v.to_segment, v.to_orient);
+            graph.create_edge(Edge(shift(left, offset), shift(right, offset)));
+        }
+        Line::Path(v) => {
+            let path = create_unique_path(graph, &v.path_name, false, merged);
+            for (seg, orient) in v.iter() {
+                let handle = Handle::new(seg, orient);
+                graph.path_append_step(path, shift(handle, offset));
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Add the segments, links, and paths of a GFA to `graph` as they're
+/// parsed, assigning node IDs according to `ids`, and return how the
+/// GFA was added.
+///
+/// Unlike [`merge_graphs`], the graph described by the GFA is never
+/// built on its own, which makes this the way to combine graphs that
+/// are too big to have in memory twice, e.g. by merging the GFA of
+/// each chromosome into a single `PackedGraph` in turn.
+///
+/// `gfa_lines` is called twice, and must produce the same lines both
+/// times, e.g. by reopening the file. The first pass checks that the
+/// lines can be parsed, that every link and path only uses segments
+/// of the GFA, and, with [`IdPolicy::Keep`], that no segment ID is
+/// already in `graph`, so that `graph` is left unchanged if an error
+/// is returned. The second pass adds the lines. Links and paths that
+/// come before the segments they use are held back until the end.
+pub fn merge_gfa_lines<G, F, I, T>(
+    graph: &mut G,
+    mut gfa_lines: F,
+    ids: IdPolicy,
+) -> Result<MergedGraph, MergeError>
+where
+    G: HandleGraph + AdditiveHandleGraph + MutableGraphPaths,
+    for<'a> &'a G: IntoHandles + GraphPathNames,
+    F: FnMut() -> I,
+    I: IntoIterator<Item = GFAResult<Line<usize, T>>>,
+    T: OptFields,
+{
+    let offset = match ids {
+        IdPolicy::Offset => graph.max_node_id().0,
+        IdPolicy::Keep => 0,
+    };
+
+    let mut segments: FnvHashSet<usize> = FnvHashSet::default();
+    // segments that were used before they were defined
+    let mut pending: FnvHashSet<usize> = FnvHashSet::default();
+
+    for line in gfa_lines() {
+        let line = line?;
+        if let Line::Segment(v) = &line {
+            let id = NodeId::from(v.name as u64 + offset);
+            if graph.has_node(id) || !segments.insert(v.name) {
+                return Err(MergeError::IdConflict(id));
+            }
+        }
+        for seg in line_segments(&line) {
+            if !segments.contains(&seg) {
+                pending.insert(seg);
+            }
+        }
+    }
+
+    let mut missing =
+        pending.difference(&segments).copied().collect::<Vec<_>>();
+    missing.sort();
+    if let Some(&seg) = missing.first() {
+        return Err(MergeError::MissingSegment(seg));
+    }
+
+    let mut merged = MergedGraph {
+        id_offset: offset,
+        renamed_paths: Vec::new(),
+    };
+
+    let mut held_back = Vec::new();
+
+    for line in gfa_lines() {
+        let line = line?;
+        let ready = line_segments(&line)
+            .into_iter()
+            .all(|seg| graph.has_node(seg as u64 + offset));
+        if ready {
+            add_gfa_line(graph, line, offset, &mut merged);
+        } else {
+            held_back.push(line);
+        }
+    }
+
+    for line in held_back {
+        add_gfa_line(graph, line, offset, &mut merged);
+    }
+
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        algorithms::fingerprint::{graph_fingerprint, IdMode},
+        conversion::write_as_gfa,
+        hashgraph::HashGraph,
+        packedgraph::PackedGraph,
+    };
+
+    use gfa::parser::GFAParser;
+
+    fn hnd(x: u64) -> Handle {
+        Handle::pack(x, false)
+    }
+
+    fn r_hnd(x: u64) -> Handle {
+        Handle::pack(x, true)
+    }
+
+    fn chromosome<G>(first_id: u64, seqs: &[&[u8]], paths: &[&[u8]]) -> G
+    where
+        G: AdditiveHandleGraph + MutableGraphPaths + Default,
+    {
+        let mut graph = G::default();
+
+        let handles = seqs
+            .iter()
+            .enumerate()
+            .map(|(ix, seq)| graph.create_handle(seq, first_id + ix as u64))
+            .collect::<Vec<_>>();
+        for w in handles.windows(2) {
+            graph.create_edge(Edge(w[0], w[1]));
+        }
+
+        for name in paths {
+            let path = graph.create_path(name, false).unwrap();
+            for &h in handles.iter() {
+                graph.path_append_step(path, h);
+            }
+        }
+
+        graph
+    }
+
+    fn path_names(graph: &PackedGraph) -> Vec<Vec<u8>> {
+        let mut names = graph
+            .path_ids()
+            .filter_map(|path| graph.get_path_name_vec(path))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn merge_offset_ids() {
+        let chr1: HashGraph = chromosome(1, &[b"GAT", b"TACA"], &[b"ref"]);
+        let chr2: HashGraph =
+            chromosome(1, &[b"CC", b"GA", b"T"], &[b"ref", b"ref.1"]);
+
+        let (graph, merged): (PackedGraph, _) =
+            merge_graphs(&[&chr1, &chr2], IdPolicy::Offset).unwrap();
+
+        assert_eq!(merged[0], MergedGraph::default());
+        assert_eq!(merged[1].id_offset, 2);
+        assert_eq!(
+            merged[1].renamed_paths,
+            vec![
+                (b"ref".to_vec(), b"ref.1".to_vec()),
+                (b"ref.1".to_vec(), b"ref.1.1".to_vec()),
+            ]
+        );
+
+        assert_eq!(graph.node_count(), 5);
+        assert_eq!(graph.edge_count(), 3);
+        assert_eq!(graph.sequence_vec(hnd(3)), b"CC");
+        assert!(graph.has_edge(hnd(4), hnd(5)));
+        assert!(!graph.has_edge(hnd(2), hnd(3)));
+        assert_eq!(path_names(&graph), vec![&b"ref"[..], b"ref.1", b"ref.1.1"]);
+
+        let path = graph.get_path_id(b"ref.1").unwrap();
+        let steps = graph
+            .path_steps(path)
+            .unwrap()
+            .map(|s| s.handle())
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![hnd(3), hnd(4), hnd(5)]);
+    }
+
+    #[test]
+    fn merge_keep_ids() {
+        let chr1: PackedGraph = chromosome(1, &[b"GAT", b"TACA"], &[b"a"]);
+        let chr2: PackedGraph = chromosome(10, &[b"CC", b"GA"], &[b"b"]);
+
+        let (graph, merged): (HashGraph, _) =
+            merge_graphs(&[&chr1, &chr2], IdPolicy::Keep).unwrap();
+        assert!(merged.iter().all(|m| *m == MergedGraph::default()));
+        assert_eq!(graph.node_count(), 4);
+        assert!(graph.has_edge(hnd(10), hnd(11)));
+
+        let chr3: PackedGraph = chromosome(2, &[b"T"], &[]);
+        let result: Result<(HashGraph, _), _> =
+            merge_graphs(&[&chr1, &chr2, &chr3], IdPolicy::Keep);
+        match result {
+            Err(MergeError::IdConflict(id)) => assert_eq!(id, NodeId::from(2)),
+            _ => panic!("expected an ID conflict"),
+        }
+    }
+
+    fn gfa_lines(
+        gfa: &[u8],
+    ) -> impl Iterator<Item = GFAResult<Line<usize, ()>>> + '_ {
+        let parser: GFAParser<usize, ()> = GFAParser::new();
+        gfa.split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(move |line| parser.parse_gfa_line(line))
+    }
+
+    #[test]
+    fn merge_gfa_streaming() {
+        let chr1: PackedGraph = chromosome(1, &[b"GAT", b"TACA"], &[b"ref"]);
+        let mut chr2: PackedGraph = chromosome(1, &[b"CC", b"GA"], &[b"ref"]);
+        chr2.create_edge(Edge(hnd(2), r_hnd(1)));
+
+        let (expected, _): (PackedGraph, _) =
+            merge_graphs(&[&chr1, &chr2], IdPolicy::Offset).unwrap();
+
+        let mut gfa: Vec<u8> = Vec::new();
+        write_as_gfa(&chr2, &mut gfa).unwrap();
+
+        let mut graph = chr1.clone();
+        let merged =
+            merge_gfa_lines(&mut graph, || gfa_lines(&gfa), IdPolicy::Offset)
+                .unwrap();
+        assert_eq!(merged.id_offset, 2);
+        assert_eq!(merged.renamed_paths.len(), 1);
+
+        assert_eq!(
+            graph_fingerprint(&graph, IdMode::Include),
+            graph_fingerprint(&expected, IdMode::Include)
+        );
+
+        // the same GFA again can't keep its IDs, and nothing is added
+        let before = graph_fingerprint(&graph, IdMode::Include);
+        let result =
+            merge_gfa_lines(&mut graph, || gfa_lines(&gfa), IdPolicy::Keep);
+        assert!(matches!(result, Err(MergeError::IdConflict(_))));
+        assert_eq!(graph_fingerprint(&graph, IdMode::Include), before);
+    }
+
+    #[test]
+    fn merge_gfa_lines_out_of_order() {
+        let chr1: PackedGraph = chromosome(1, &[b"GAT", b"TACA"], &[b"ref"]);
+
+        // the path and a link come before the segments they use
+        let gfa: &[u8] = b"H\tVN:Z:1.0\n\
+                           P\tref\t1+,2+,3-\t*\n\
+                           S\t1\tCC\n\
+                           L\t1\t+\t2\t+\t0M\n\
+                           S\t2\tGA\n\
+                           L\t2\t+\t3\t-\t0M\n\
+                           S\t3\tT\n";
+
+        let mut graph = chr1.clone();
+        let merged =
+            merge_gfa_lines(&mut graph, || gfa_lines(gfa), IdPolicy::Offset)
+                .unwrap();
+        assert_eq!(merged.id_offset, 2);
+
+        assert_eq!(graph.node_count(), 5);
+        assert!(graph.has_edge(hnd(3), hnd(4)));
+        assert!(graph.has_edge(hnd(4), r_hnd(5)));
+
+        let path = graph.get_path_id(b"ref.1").unwrap();
+        let steps = graph
+            .path_steps(path)
+            .unwrap()
+            .map(|s| s.handle())
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![hnd(3), hnd(4), r_hnd(5)]);
+
+        // a link to a segment that isn't in the GFA
+        let gfa: &[u8] = b"S\t1\tCC\n\
+                           L\t1\t+\t4\t+\t0M\n\
+                           S\t2\tGA\n";
+
+        let mut graph = chr1.clone();
+        let result =
+            merge_gfa_lines(&mut graph, || gfa_lines(gfa), IdPolicy::Offset);
+        assert!(matches!(result, Err(MergeError::MissingSegment(4))));
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(path_names(&graph), vec![b"ref".to_vec()]);
+    }
+}
@@ -0,0 +1,547 @@
+/*!
+
+A minimizer index over the sequences of a graph, for finding seed
+positions of reads.
+
+The graph k-mers are enumerated by walking from every position on
+every handle, in both orientations, and following edges whenever a
+walk reaches the end of a node. Every walk of `k + w - 1` bases is a
+window of `w` k-mers, and the k-mer in the window with the smallest
+hash is its minimizer. With `w == 1`, every k-mer in the graph is
+indexed.
+
+The distinct minimizers are mapped to their graph positions with a
+[`boomphf`] minimal perfect hash function. Since both orientations of
+the graph are indexed, only the forward strand of a read needs to be
+queried; a read from the reverse strand will hit reverse handles.
+
+K-mers containing bases other than `A`, `C`, `G`, and `T` are never
+indexed.
+
+*/
+
+use crate::{
+    handle::{Direction, Handle},
+    handlegraph::*,
+};
+
+use fnv::FnvHashMap;
+
+use boomphf::Mphf;
+
+use rayon::prelude::*;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinimizerParams {
+    /// The k-mer length; must be in `1..=32`.
+    pub k: usize,
+    /// The number of consecutive k-mers in each window.
+    pub w: usize,
+    /// The maximum number of edges a single window may cross, which
+    /// limits the number of walks enumerated in complex regions.
+    pub max_edges: usize,
+}
+
+impl Default for MinimizerParams {
+    fn default() -> Self {
+        Self {
+            k: 15,
+            w: 10,
+            max_edges: 8,
+        }
+    }
+}
+
+impl MinimizerParams {
+    #[inline]
+    fn window_len(&self) -> usize {
+        self.k + self.w - 1
+    }
+}
+
+/// A minimizer of a sequence, with the offset of its first base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Minimizer {
+    /// The 2-bit encoded k-mer.
+    pub kmer: u64,
+    pub offset: usize,
+}
+
+/// A graph position for one of the minimizers of a read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MinimizerHit {
+    /// The offset of the minimizer in the read.
+    pub read_offset: usize,
+    /// The handle and offset of the first base of the minimizer in
+    /// the graph.
+    pub handle: Handle,
+    pub offset: usize,
+}
+
+#[inline]
+fn encode_base(base: u8) -> Option<u64> {
+    match base {
+        b'A' | b'a' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'G' | b'g' => Some(2),
+        b'T' | b't' => Some(3),
+        _ => None,
+    }
+}
+
+/// Encode `seq` as a 2-bit packed k-mer. Returns `None` if the
+/// sequence is longer than 32 bases, or contains bases other than
+/// `ACGT`.
+pub fn encode_kmer(seq: &[u8]) -> Option<u64> {
+    if seq.len() > 32 {
+        return None;
+    }
+    seq.iter()
+        .try_fold(0u64, |code, &b| Some((code << 2) | encode_base(b)?))
+}
+
+/// Decode a 2-bit packed k-mer of length `k`.
+pub fn decode_kmer(kmer: u64, k: usize) -> Vec<u8> {
+    (0..k)
+        .rev()
+        .map(|i| b"ACGT"[((kmer >> (2 * i)) & 3) as usize])
+        .collect()
+}
+
+/// The order used to pick minimizers; a bijective mix of the 2-bit
+/// k-mer, so that minimizers aren't biased towards poly-A.
+#[inline]
+fn kmer_order(kmer: u64) -> u64 {
+    let mut x = kmer;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// The 2-bit encoded k-mers starting at each position of `bases`, or
+/// `None` for the k-mers that contain other bases.
+fn kmer_codes<I>(bases: I, k: usize) -> Vec<Option<u64>>
+where
+    I: IntoIterator<Item = u8>,
+{
+    let mask = if k == 32 { !0 } else { (1u64 << (2 * k)) - 1 };
+
+    let mut codes = Vec::new();
+    let mut code = 0u64;
+    let mut valid_len = 0;
+
+    for (ix, base) in bases.into_iter().enumerate() {
+        match encode_base(base) {
+            Some(b) => {
+                code = ((code << 2) | b) & mask;
+                valid_len += 1;
+            }
+            None => valid_len = 0,
+        }
+        if ix + 1 >= k {
+            codes.push(if valid_len >= k { Some(code) } else { None });
+        }
+    }
+
+    codes
+}
+
+/// Find the minimizer among the k-mers in `window`, returning its
+/// index in the window and the k-mer. Ties are broken by taking the
+/// leftmost k-mer.
+#[inline]
+fn window_minimizer(window: &[Option<u64>]) -> Option<(usize, u64)> {
+    window
+        .iter()
+        .enumerate()
+        .filter_map(|(ix, code)| code.map(|c| (ix, c)))
+        .min_by_key(|&(ix, code)| (kmer_order(code), ix))
+}
+
+/// Return the minimizers of `seq`, in order, each reported once even
+/// if it's the minimizer of several consecutive windows.
+pub fn sequence_minimizers(
+    seq: &[u8],
+    params: &MinimizerParams,
+) -> Vec<Minimizer> {
+    let codes = kmer_codes(seq.iter().copied(), params.k);
+
+    let mut minimizers: Vec<Minimizer> = Vec::new();
+
+    for (start, window) in codes.windows(params.w).enumerate() {
+        if let Some((ix, kmer)) = window_minimizer(window) {
+            let minimizer = Minimizer {
+                kmer,
+                offset: start + ix,
+            };
+            if minimizers.last() != Some(&minimizer) {
+                minimizers.push(minimizer);
+            }
+        }
+    }
+
+    minimizers
+}
+
+/// The graph state while enumerating the windows starting at a
+/// single position.
+struct WindowWalker<'a, G> {
+    graph: G,
+    params: &'a MinimizerParams,
+    sequences: &'a FnvHashMap<Handle, Vec<u8>>,
+    bases: Vec<u8>,
+    positions: Vec<(Handle, usize)>,
+}
+
+impl<'a, G> WindowWalker<'a, G>
+where
+    G: IntoNeighbors + Copy,
+{
+    fn walk(
+        &mut self,
+        handle: Handle,
+        offset: usize,
+        edges: usize,
+        out: &mut Vec<(u64, Handle, usize)>,
+    ) {
+        let window_len = self.params.window_len();
+        let seq = &self.sequences[&handle];
+
+        let depth = self.bases.len();
+
+        let mut offset = offset;
+        while offset < seq.len() && self.bases.len() < window_len {
+            self.bases.push(seq[offset]);
+            self.positions.push((handle, offset));
+            offset += 1;
+        }
+
+        if self.bases.len() == window_len {
+            let codes = kmer_codes(self.bases.iter().copied(), self.params.k);
+            if let Some((ix, kmer)) = window_minimizer(&codes) {
+                let (h, o) = self.positions[ix];
+                out.push((kmer, h, o));
+            }
+        } else if edges < self.params.max_edges {
+            for next in self.graph.neighbors(handle, Direction::Right) {
+                self.walk(next, 0, edges + 1, out);
+            }
+        }
+
+        self.bases.truncate(depth);
+        self.positions.truncate(depth);
+    }
+}
+
+/// A minimizer index mapping each distinct graph minimizer to all the
+/// graph positions it occurs at.
+#[derive(Debug, Clone)]
+pub struct MinimizerIndex {
+    params: MinimizerParams,
+    mphf: Option<Mphf<u64>>,
+    /// The k-mer stored at each hash slot, to reject k-mers that
+    /// aren't in the index.
+    kmers: Vec<u64>,
+    /// The range of `hits` for each hash slot.
+    offsets: Vec<usize>,
+    hits: Vec<(Handle, usize)>,
+}
+
+impl MinimizerIndex {
+    /// Build the index over all minimizers of `graph`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `params.k` is not in `1..=32`, or `params.w` is 0.
+    pub fn new<G>(graph: G, params: MinimizerParams) -> Self
+    where
+        G: HandleGraphRef + Send + Sync,
+    {
+        assert!(params.k > 0 && params.k <= 32, "k must be in 1..=32");
+        assert!(params.w > 0, "w must be at least 1");
+
+        let handles = graph
+            .handles()
+            .flat_map(|h| std::iter::once(h).chain(std::iter::once(h.flip())))
+            .collect::<Vec<_>>();
+
+        let sequences: FnvHashMap<Handle, Vec<u8>> = handles
+            .iter()
+            .map(|&h| (h, graph.sequence_vec(h)))
+            .collect();
+
+        let mut minimizers = handles
+            .par_iter()
+            .fold(Vec::new, |mut out, &handle| {
+                let mut walker = WindowWalker {
+                    graph,
+                    params: &params,
+                    sequences: &sequences,
+                    bases: Vec::with_capacity(params.window_len()),
+                    positions: Vec::with_capacity(params.window_len()),
+                };
+                for offset in 0..sequences[&handle].len() {
+                    walker.walk(handle, offset, 0, &mut out);
+                }
+                out
+            })
+            .reduce(Vec::new, |mut a, mut b| {
+                a.append(&mut b);
+                a
+            });
+
+        minimizers.par_sort_unstable();
+        minimizers.dedup();
+
+        let mut keys = minimizers.iter().map(|m| m.0).collect::<Vec<_>>();
+        keys.dedup();
+
+        debug!(
+            "indexing {} distinct minimizers at {} graph positions",
+            keys.len(),
+            minimizers.len()
+        );
+
+        if keys.is_empty() {
+            return Self {
+                params,
+                mphf: None,
+                kmers: Vec::new(),
+                offsets: vec![0],
+                hits: Vec::new(),
+            };
+        }
+
+        let mphf = Mphf::new_parallel(1.7, &keys, None);
+
+        let mut kmers = vec![0; keys.len()];
+        let mut counts = vec![0; keys.len()];
+
+        for &(kmer, _, _) in minimizers.iter() {
+            let slot = mphf.hash(&kmer) as usize;
+            kmers[slot] = kmer;
+            counts[slot] += 1;
+        }
+
+        let mut offsets = Vec::with_capacity(keys.len() + 1);
+        offsets.push(0);
+        for count in counts {
+            offsets.push(offsets.last().unwrap() + count);
+        }
+
+        let mut hits = vec![(Handle::from_integer(0), 0); minimizers.len()];
+        let mut next = offsets.clone();
+
+        for (kmer, handle, offset) in minimizers {
+            let slot = mphf.hash(&kmer) as usize;
+            hits[next[slot]] = (handle, offset);
+            next[slot] += 1;
+        }
+
+        Self {
+            params,
+            mphf: Some(mphf),
+            kmers,
+            offsets,
+            hits,
+        }
+    }
+
+    #[inline]
+    pub fn params(&self) -> &MinimizerParams {
+        &self.params
+    }
+
+    /// The number of distinct minimizers in the index.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.kmers.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.kmers.is_empty()
+    }
+
+    /// The total number of graph positions in the index.
+    #[inline]
+    pub fn hit_count(&self) -> usize {
+        self.hits.len()
+    }
+
+    /// Return the graph positions of the 2-bit encoded `kmer`, or an
+    /// empty slice if it's not a minimizer in the graph. The
+    /// positions are sorted.
+    pub fn find(&self, kmer: u64) -> &[(Handle, usize)] {
+        let slot = self
+            .mphf
+            .as_ref()
+            .and_then(|mphf| mphf.try_hash(&kmer))
+            .map(|slot| slot as usize)
+            .filter(|&slot| slot < self.kmers.len());
+
+        match slot {
+            Some(slot) if self.kmers[slot] == kmer => {
+                &self.hits[self.offsets[slot]..self.offsets[slot + 1]]
+            }
+            _ => &[],
+        }
+    }
+
+    /// Return the graph positions of the minimizers of `read`, using
+    /// the same `k` and `w` as the index.
+    pub fn query(&self, read: &[u8]) -> Vec<MinimizerHit> {
+        sequence_minimizers(read, &self.params)
+            .into_iter()
+            .flat_map(|minimizer| {
+                self.find(minimizer.kmer).iter().map(
+                    move |&(handle, offset)| MinimizerHit {
+                        read_offset: minimizer.offset,
+                        handle,
+                        offset,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hashgraph::HashGraph;
+    use crate::mutablehandlegraph::*;
+    use crate::packedgraph::PackedGraph;
+    use crate::util::test::{edge, seq_graph};
+
+    fn build_graph<G>() -> G
+    where
+        G: Default + AdditiveHandleGraph,
+    {
+        let seqs: [&[u8]; 4] = [b"ACGTTGCA", b"GGA", b"TTNC", b"CATTAGCG"];
+        let edges = [edge(1, 2), edge(1, 3), edge(2, 4), edge(3, 4)];
+        seq_graph(&seqs, &edges)
+    }
+
+    fn graph_seq<G: HandleGraphRef>(graph: G, path: &[Handle]) -> Vec<u8> {
+        path.iter().flat_map(|&h| graph.sequence(h)).collect()
+    }
+
+    fn check_index<G>(graph: G)
+    where
+        G: HandleGraphRef + Send + Sync,
+    {
+        let h = |id: u64| Handle::pack(id, false);
+
+        let params = MinimizerParams {
+            k: 5,
+            w: 1,
+            max_edges: 8,
+        };
+        let index = MinimizerIndex::new(graph, params);
+
+        // a k-mer crossing the n1 -> n2 edge
+        let kmer = encode_kmer(b"GCAGG").unwrap();
+        assert_eq!(index.find(kmer), &[(h(1), 5)]);
+
+        // and its reverse complement
+        let kmer = encode_kmer(b"CCTGC").unwrap();
+        assert_eq!(index.find(kmer), &[(h(2).flip(), 1)]);
+
+        // a k-mer crossing the n1 -> n3 edge, next to an N
+        let kmer = encode_kmer(b"GCATT").unwrap();
+        assert_eq!(index.find(kmer), &[(h(1), 5)]);
+        assert!(index
+            .find(encode_kmer(b"CATTA").unwrap())
+            .contains(&(h(4), 0)));
+        assert!(index.find(encode_kmer(b"AAAAA").unwrap()).is_empty());
+
+        // every graph k-mer of the read is found, at consecutive
+        // graph positions
+        let read = graph_seq(graph, &[h(1), h(2), h(4)]);
+        let hits = index.query(&read[3..]);
+        let read_offsets =
+            hits.iter().map(|h| h.read_offset).collect::<Vec<_>>();
+        let expected = (0..read.len() - 3 - 4).collect::<Vec<_>>();
+        let mut dedup = read_offsets.clone();
+        dedup.dedup();
+        assert_eq!(dedup, expected);
+
+        let hit = hits.iter().find(|h| h.read_offset == 4).unwrap();
+        assert_eq!((hit.handle, hit.offset), (h(1), 7));
+
+        // with windows, the reverse complement of a read hits the
+        // reverse strand
+        let params = MinimizerParams {
+            k: 4,
+            w: 3,
+            max_edges: 8,
+        };
+        let index = MinimizerIndex::new(graph, params);
+        assert!(!index.is_empty());
+
+        let path = [h(4).flip(), h(2).flip(), h(1).flip()];
+        let read = graph_seq(graph, &path);
+        let hits = index.query(&read);
+        assert!(hits.iter().any(|hit| hit.handle.is_reverse()));
+
+        // the hits on the read's own path spell the read's k-mers
+        let path_seq = |handle: Handle, offset: usize| {
+            let ix = path.iter().position(|&p| p == handle)?;
+            let seq = graph_seq(graph, &path[ix..]);
+            Some(seq[offset..offset + params.k].to_vec())
+        };
+
+        for hit in hits {
+            if let Some(kmer) = path_seq(hit.handle, hit.offset) {
+                let read_kmer = &read[hit.read_offset..][..params.k];
+                assert_eq!(kmer, read_kmer);
+            }
+        }
+    }
+
+    #[test]
+    fn minimizer_index_packedgraph() {
+        let graph: PackedGraph = build_graph();
+        check_index(&graph);
+    }
+
+    #[test]
+    fn minimizer_index_hashgraph() {
+        let graph: HashGraph = build_graph();
+        check_index(&graph);
+    }
+
+    #[test]
+    fn read_minimizers() {
+        let params = MinimizerParams {
+            k: 3,
+            w: 4,
+            max_edges: 0,
+        };
+
+        let seq = b"ACGTTGCANNACGTAC";
+        let minimizers = sequence_minimizers(seq, &params);
+
+        for m in minimizers.iter() {
+            let kmer = &seq[m.offset..m.offset + params.k];
+            assert_eq!(encode_kmer(kmer), Some(m.kmer));
+            assert_eq!(decode_kmer(m.kmer, params.k), kmer);
+        }
+
+        // every window has its minimizer among the reported ones
+        let codes = kmer_codes(seq.iter().copied(), params.k);
+        for (start, window) in codes.windows(params.w).enumerate() {
+            if let Some((ix, _)) = window_minimizer(window) {
+                assert!(minimizers.iter().any(|m| m.offset == start + ix));
+            }
+        }
+
+        assert!(sequence_minimizers(b"AC", &params).is_empty());
+        assert_eq!(encode_kmer(b"ACGN"), None);
+    }
+}
@@ -0,0 +1,330 @@
+/*!
+
+Normalize a graph by merging redundant nodes and edges, in the style
+of `vg mod -n`.
+
+Sibling nodes, i.e. nodes that are reached from exactly the same set
+of neighbors, and that start with the same sequence, spell the same
+prefix no matter which of them a walk through the graph takes. The
+shared prefix is split off each sibling with `divide_handle`, and the
+prefixes are merged into a single node. As this is done for both
+orientations of every node, shared suffixes are merged as well. The
+resulting chains of nodes are then merged by [`unchop`], and the
+whole process is repeated until the graph no longer changes.
+
+Every walk through the normalized graph spells a sequence that a walk
+through the original graph spells, and vice versa, and the paths are
+updated so that they spell the same sequences as before.
+
+*/
+
+use crate::{
+    handle::{Direction, Edge, Handle},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+};
+
+use crate::packedgraph::*;
+
+use fnv::FnvHashMap;
+
+use super::unchop::unchop;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// What a normalization changed in the graph.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NormalizeStats {
+    /// The number of rounds of merging and unchopping that were run.
+    pub iterations: usize,
+    /// The number of nodes that had a shared prefix or suffix split
+    /// off before being merged.
+    pub divided_nodes: usize,
+    /// The number of sibling nodes that were merged into another.
+    pub merged_nodes: usize,
+    /// The number of duplicate edge records that were removed.
+    pub removed_edges: usize,
+}
+
+/// Remove duplicate records of the same edge, so that every edge is
+/// stored once. Returns the number of duplicates removed.
+pub fn remove_redundant_edges(graph: &mut PackedGraph) -> usize {
+    let mut counts: FnvHashMap<Edge, usize> = FnvHashMap::default();
+    for Edge(l, r) in graph.edges() {
+        *counts.entry(Edge::edge_handle(l, r)).or_default() += 1;
+    }
+
+    let mut removed = 0;
+    for (edge, count) in counts {
+        if count > 1 {
+            let Edge(l, r) = edge;
+            while graph.has_edge(l, r) {
+                graph.remove_edge(edge);
+            }
+            graph.create_edge(edge);
+            removed += count - 1;
+        }
+    }
+    removed
+}
+
+/// The siblings to the right of `handle` that can be merged: they
+/// have the same left neighbors and first base, and they aren't
+/// adjacent to each other or to themselves.
+fn sibling_group(graph: &PackedGraph, handle: Handle) -> Option<Vec<Handle>> {
+    let mut siblings = graph
+        .neighbors(handle, Direction::Right)
+        .filter(|s| s.id() != handle.id())
+        .collect::<Vec<_>>();
+    siblings.sort();
+    siblings.dedup();
+
+    // a node can't be merged with its own reverse
+    let mut node_count: FnvHashMap<u64, usize> = FnvHashMap::default();
+    for s in siblings.iter() {
+        *node_count.entry(s.id().0).or_default() += 1;
+    }
+    siblings.retain(|s| node_count[&s.id().0] == 1);
+
+    let mut groups: FnvHashMap<(u8, Vec<Handle>), Vec<Handle>> =
+        FnvHashMap::default();
+
+    for &s in siblings.iter() {
+        let first = graph.sequence(s).next()?;
+        let mut lefts = graph.neighbors(s, Direction::Left).collect::<Vec<_>>();
+        lefts.sort();
+        lefts.dedup();
+        groups.entry((first, lefts)).or_default().push(s);
+    }
+
+    let mut groups = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect::<Vec<_>>();
+    groups.sort();
+
+    groups.into_iter().find(|group| {
+        group.iter().all(|&s| {
+            [Direction::Left, Direction::Right].iter().all(|&dir| {
+                graph
+                    .neighbors(s, dir)
+                    .all(|n| group.iter().all(|g| g.id() != n.id()))
+            })
+        })
+    })
+}
+
+/// Replace every step on the node of `from` with `to`, in the
+/// corresponding orientation.
+fn replace_steps(graph: &mut PackedGraph, from: Handle, to: Handle) {
+    let steps = graph
+        .steps_on_handle(from)
+        .map(|steps| steps.collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    for (path, step) in steps {
+        let step_handle = graph.path_handle_at_step(path, step).unwrap();
+        let new = if step_handle == from { to } else { to.flip() };
+        graph.path_insert_step_after(path, step, new);
+        graph.path_remove_step(path, step);
+    }
+}
+
+/// Split the shared prefix off each sibling in `group`, and merge the
+/// prefixes into one node.
+fn merge_group(
+    graph: &mut PackedGraph,
+    group: &[Handle],
+    stats: &mut NormalizeStats,
+) {
+    let seqs = group
+        .iter()
+        .map(|&s| graph.sequence_vec(s))
+        .collect::<Vec<_>>();
+
+    let prefix_len = (0..)
+        .take_while(|&ix| {
+            seqs.iter()
+                .all(|seq| ix < seq.len() && seq[ix] == seqs[0][ix])
+        })
+        .count();
+
+    let prefixes = group
+        .iter()
+        .zip(seqs.iter())
+        .map(|(&s, seq)| {
+            if seq.len() > prefix_len {
+                stats.divided_nodes += 1;
+                let parts = graph.divide_handle(s, &[prefix_len]);
+                // the parts are forward and left to right, so the
+                // prefix of a reverse handle is the last part
+                if s.is_reverse() {
+                    parts.last().unwrap().flip()
+                } else {
+                    parts[0]
+                }
+            } else {
+                s
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let keep = prefixes[0];
+
+    for &other in prefixes[1..].iter() {
+        let rights =
+            graph.neighbors(other, Direction::Right).collect::<Vec<_>>();
+        for right in rights {
+            graph.create_edge(Edge(keep, right));
+        }
+        replace_steps(graph, other, keep);
+        graph.remove_handle(other);
+        stats.merged_nodes += 1;
+    }
+}
+
+/// Merge all sibling groups that can be found in one pass over the
+/// graph. Returns `true` if anything was merged.
+fn merge_siblings(graph: &mut PackedGraph, stats: &mut NormalizeStats) -> bool {
+    let handles = graph.handles().collect::<Vec<_>>();
+    let mut changed = false;
+
+    for handle in handles {
+        for &h in [handle, handle.flip()].iter() {
+            if !graph.has_node(h.id()) {
+                continue;
+            }
+            if let Some(group) = sibling_group(graph, h) {
+                merge_group(graph, &group, stats);
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+/// Normalize the graph, merging redundant edges, sibling nodes, and
+/// chains of nodes, until nothing changes or `max_iterations` rounds
+/// have been run. Note that [`unchop`] renumbers the nodes.
+pub fn normalize(
+    graph: &mut PackedGraph,
+    max_iterations: usize,
+) -> NormalizeStats {
+    let mut stats = NormalizeStats::default();
+
+    while stats.iterations < max_iterations {
+        stats.iterations += 1;
+
+        stats.removed_edges += remove_redundant_edges(graph);
+        let merged = merge_siblings(graph, &mut stats);
+
+        let node_count = graph.node_count();
+        unchop(graph);
+        let unchopped = graph.node_count() < node_count;
+
+        debug!(
+            "normalize iteration {}: {} nodes, {} merged in total",
+            stats.iterations,
+            graph.node_count(),
+            stats.merged_nodes
+        );
+
+        if !merged && !unchopped {
+            break;
+        }
+    }
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conversion::fasta::path_sequence;
+    use crate::util::test::{add_path, edge, hnd, r_hnd, seq_graph};
+
+    #[test]
+    fn normalize_shared_prefix() {
+        let mut graph: PackedGraph = seq_graph(
+            &[b"A", b"GAT", b"GAC", b"T"],
+            &[edge(1, 2), edge(1, 3), edge(2, 4), edge(3, 4)],
+        );
+        let p1 = add_path(&mut graph, b"p1", &[hnd(1), hnd(2), hnd(4)]);
+        let p2 = add_path(&mut graph, b"p2", &[hnd(1), hnd(3), hnd(4)]);
+        let p3 = add_path(&mut graph, b"p3", &[r_hnd(4), r_hnd(3), r_hnd(1)]);
+
+        let stats = normalize(&mut graph, 10);
+        assert_eq!(stats.divided_nodes, 2);
+        assert_eq!(stats.merged_nodes, 1);
+        assert_eq!(stats.removed_edges, 0);
+
+        assert_eq!(path_sequence(&graph, p1).unwrap(), b"AGATT");
+        assert_eq!(path_sequence(&graph, p2).unwrap(), b"AGACT");
+        assert_eq!(path_sequence(&graph, p3).unwrap(), b"AGTCT");
+
+        // AGA, T, C, and T
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(graph.edge_count(), 4);
+        let mut seqs = graph
+            .handles()
+            .map(|h| graph.sequence_vec(h))
+            .collect::<Vec<_>>();
+        seqs.sort();
+        assert_eq!(
+            seqs,
+            vec![b"AGA".to_vec(), b"C".to_vec(), b"T".to_vec(), b"T".to_vec()]
+        );
+
+        // normalizing again changes nothing
+        let stats = normalize(&mut graph, 10);
+        assert_eq!(stats.iterations, 1);
+        assert_eq!(stats.merged_nodes, 0);
+    }
+
+    #[test]
+    fn normalize_shared_suffix() {
+        let mut graph: PackedGraph =
+            seq_graph(&[b"ACG", b"TCG", b"T"], &[edge(1, 3), edge(2, 3)]);
+        let p1 = add_path(&mut graph, b"p1", &[hnd(1), hnd(3)]);
+        let p2 = add_path(&mut graph, b"p2", &[r_hnd(3), r_hnd(2)]);
+
+        let stats = normalize(&mut graph, 10);
+        assert_eq!(stats.divided_nodes, 2);
+        assert_eq!(stats.merged_nodes, 1);
+        assert_eq!(path_sequence(&graph, p1).unwrap(), b"ACGT");
+        assert_eq!(path_sequence(&graph, p2).unwrap(), b"ACGA");
+
+        // A and T, both followed by CGT
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.edge_count(), 2);
+        assert_eq!(graph.edges().count(), 2);
+    }
+
+    #[test]
+    fn normalize_identical_siblings() {
+        // 2 and 3 are identical, but 3 also follows 4, so only the
+        // siblings 5 and 6 can be merged
+        let mut graph: PackedGraph = seq_graph(
+            &[b"A", b"GG", b"GG", b"C", b"TT", b"TT"],
+            &[edge(1, 2), edge(1, 3), edge(4, 3), edge(2, 5), edge(2, 6)],
+        );
+        let p1 = add_path(&mut graph, b"p1", &[hnd(1), hnd(2), hnd(5)]);
+        let p2 = add_path(&mut graph, b"p2", &[hnd(1), hnd(2), hnd(6)]);
+        let p3 = add_path(&mut graph, b"p3", &[hnd(4), hnd(3)]);
+
+        let stats = normalize(&mut graph, 10);
+        assert_eq!(stats.divided_nodes, 0);
+        assert_eq!(stats.merged_nodes, 1);
+        assert_eq!(path_sequence(&graph, p1).unwrap(), b"AGGTT");
+        assert_eq!(path_sequence(&graph, p2).unwrap(), b"AGGTT");
+        assert_eq!(path_sequence(&graph, p3).unwrap(), b"CGG");
+
+        // A, GGTT, GG, and C
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(graph.edge_count(), 3);
+    }
+}
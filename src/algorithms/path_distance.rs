@@ -0,0 +1,435 @@
+/*!
+
+Pairwise similarity and distance between paths, or groups of paths
+such as the haplotypes of a sample, based on the nodes they cover.
+
+All statistics are gathered in a single parallel pass over the nodes
+of the graph, using the path occurrences on each node, so the path
+sequences never need to be built. Note that coverage counts are only
+as precise as the graph's occurrence records; `HashGraph` only keeps
+one occurrence per path and node.
+
+*/
+
+use crate::{handle::Handle, handlegraph::*, pathhandlegraph::*};
+
+use fnv::FnvHashMap;
+
+use bstr::ByteSlice;
+
+use rayon::prelude::*;
+
+use std::io::{self, Write};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// The metrics that can be computed from a `PathDistances`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceMetric {
+    /// One minus the Jaccard index of the sets of nodes covered by
+    /// each group.
+    NodeJaccard,
+    /// Like `NodeJaccard`, but with each node weighted by its length.
+    BaseJaccard,
+    /// The Euclidean distance between the per-base coverage vectors
+    /// of the groups, i.e. each node contributes its length times the
+    /// squared difference in the number of times it's visited.
+    Euclidean,
+}
+
+/// A partition of (some of) the paths in a graph into named groups.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathGroups {
+    names: Vec<Vec<u8>>,
+    path_group: FnvHashMap<PathId, usize>,
+}
+
+impl PathGroups {
+    /// Build groups from a list of group names and the paths in each
+    /// group. If a path is in more than one group, the last group
+    /// wins.
+    pub fn new(groups: Vec<(Vec<u8>, Vec<PathId>)>) -> Self {
+        let mut names = Vec::with_capacity(groups.len());
+        let mut path_group = FnvHashMap::default();
+
+        for (ix, (name, paths)) in groups.into_iter().enumerate() {
+            names.push(name);
+            for path in paths {
+                path_group.insert(path, ix);
+            }
+        }
+
+        Self { names, path_group }
+    }
+
+    /// Put each path in its own group, named after the path, ordered
+    /// by path ID.
+    pub fn by_path<G>(graph: G) -> Self
+    where
+        G: IntoPathIds + GraphPathNames + Copy,
+    {
+        let mut path_ids = graph.path_ids().collect::<Vec<_>>();
+        path_ids.sort();
+
+        let groups = path_ids
+            .into_iter()
+            .map(|id| {
+                let name = graph.get_path_name_vec(id).unwrap_or_default();
+                (name, vec![id])
+            })
+            .collect();
+
+        Self::new(groups)
+    }
+
+    /// Group the paths by the part of their name before the first
+    /// `separator`, e.g. by sample with PanSN names like
+    /// `sample#1#chr1` and `#` as the separator. Paths without the
+    /// separator are grouped by their full name. Groups are sorted by
+    /// name.
+    pub fn by_name_prefix<G>(graph: G, separator: u8) -> Self
+    where
+        G: IntoPathIds + GraphPathNames + Copy,
+    {
+        let mut groups: FnvHashMap<Vec<u8>, Vec<PathId>> =
+            FnvHashMap::default();
+
+        for id in graph.path_ids() {
+            let name = graph.get_path_name_vec(id).unwrap_or_default();
+            let prefix = match name.find_byte(separator) {
+                Some(ix) => name[..ix].to_vec(),
+                None => name,
+            };
+            groups.entry(prefix).or_default().push(id);
+        }
+
+        let mut groups = groups.into_iter().collect::<Vec<_>>();
+        groups.sort();
+
+        Self::new(groups)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    #[inline]
+    pub fn names(&self) -> &[Vec<u8>] {
+        &self.names
+    }
+
+    #[inline]
+    pub fn group_of(&self, path: PathId) -> Option<usize> {
+        self.path_group.get(&path).copied()
+    }
+}
+
+/// Accumulated statistics for a pair of groups; on the diagonal,
+/// they're the statistics for a single group.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct PairStats {
+    nodes: usize,
+    bases: usize,
+    coverage_dot: f64,
+}
+
+impl PairStats {
+    fn add(&mut self, other: &Self) {
+        self.nodes += other.nodes;
+        self.bases += other.bases;
+        self.coverage_dot += other.coverage_dot;
+    }
+}
+
+/// The node coverage statistics for each pair of groups, from which
+/// the distances in `DistanceMetric` can be computed.
+#[derive(Debug, Clone)]
+pub struct PathDistances {
+    names: Vec<Vec<u8>>,
+    // upper triangular `n * n` matrix, including the diagonal
+    stats: Vec<PairStats>,
+}
+
+/// Sum the statistics of the nodes in `handles` into an `n * n`
+/// matrix.
+fn node_stats<G>(
+    graph: G,
+    groups: &PathGroups,
+    handles: &[Handle],
+    stats: &mut [PairStats],
+    counts: &mut Vec<(usize, usize)>,
+) where
+    G: IntoSequences + IntoNodeOccurrences + Copy,
+{
+    let n = groups.len();
+
+    for &handle in handles {
+        counts.clear();
+
+        let occurrences = match graph.steps_on_handle(handle) {
+            Some(occurrences) => occurrences,
+            None => continue,
+        };
+
+        let mut group_ixs = occurrences
+            .filter_map(|(path, _)| groups.group_of(path))
+            .collect::<Vec<_>>();
+
+        if group_ixs.is_empty() {
+            continue;
+        }
+
+        group_ixs.sort_unstable();
+
+        for ix in group_ixs {
+            match counts.last_mut() {
+                Some((last, count)) if *last == ix => *count += 1,
+                _ => counts.push((ix, 1)),
+            }
+        }
+
+        let len = graph.node_len(handle);
+
+        for (a_ix, &(a, a_count)) in counts.iter().enumerate() {
+            for &(b, b_count) in counts[a_ix..].iter() {
+                let pair = &mut stats[a * n + b];
+                pair.nodes += 1;
+                pair.bases += len;
+                pair.coverage_dot += (len * a_count * b_count) as f64;
+            }
+        }
+    }
+}
+
+impl PathDistances {
+    /// Gather the coverage statistics for each pair of groups in
+    /// `groups`, in parallel over the nodes in the graph.
+    pub fn new<G>(graph: G, groups: &PathGroups) -> Self
+    where
+        G: HandleGraphRef + IntoNodeOccurrences + Send + Sync,
+    {
+        let n = groups.len();
+
+        let handles = graph.handles().collect::<Vec<_>>();
+
+        let chunk_size = (handles.len() / rayon::current_num_threads()).max(1);
+
+        let stats = handles
+            .par_chunks(chunk_size)
+            .fold(
+                || vec![PairStats::default(); n * n],
+                |mut stats, chunk| {
+                    let mut counts = Vec::new();
+                    node_stats(graph, groups, chunk, &mut stats, &mut counts);
+                    stats
+                },
+            )
+            .reduce(
+                || vec![PairStats::default(); n * n],
+                |mut lhs, rhs| {
+                    lhs.iter_mut().zip(rhs.iter()).for_each(|(l, r)| l.add(r));
+                    lhs
+                },
+            );
+
+        Self {
+            names: groups.names().to_vec(),
+            stats,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    #[inline]
+    pub fn names(&self) -> &[Vec<u8>] {
+        &self.names
+    }
+
+    #[inline]
+    fn pair(&self, a: usize, b: usize) -> &PairStats {
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        &self.stats[a * self.len() + b]
+    }
+
+    /// The distance between groups `a` and `b`, by their index in
+    /// the `PathGroups` used to construct this. Panics if either
+    /// index is out of bounds.
+    pub fn distance(&self, metric: DistanceMetric, a: usize, b: usize) -> f64 {
+        let (aa, bb, ab) = (self.pair(a, a), self.pair(b, b), self.pair(a, b));
+
+        let jaccard_distance = |a: usize, b: usize, shared: usize| {
+            let union = a + b - shared;
+            if union == 0 {
+                0.0
+            } else {
+                1.0 - (shared as f64 / union as f64)
+            }
+        };
+
+        match metric {
+            DistanceMetric::NodeJaccard => {
+                jaccard_distance(aa.nodes, bb.nodes, ab.nodes)
+            }
+            DistanceMetric::BaseJaccard => {
+                jaccard_distance(aa.bases, bb.bases, ab.bases)
+            }
+            DistanceMetric::Euclidean => {
+                let sq =
+                    aa.coverage_dot + bb.coverage_dot - 2.0 * ab.coverage_dot;
+                sq.max(0.0).sqrt()
+            }
+        }
+    }
+
+    /// The full, symmetric, distance matrix in row-major order.
+    pub fn matrix(&self, metric: DistanceMetric) -> Vec<f64> {
+        let n = self.len();
+        (0..n * n)
+            .map(|ix| self.distance(metric, ix / n, ix % n))
+            .collect()
+    }
+
+    /// Write the distance matrix as TSV, with the group names as the
+    /// column headers and as the first column of each row.
+    pub fn write_tsv<W: Write>(
+        &self,
+        metric: DistanceMetric,
+        out: &mut W,
+    ) -> io::Result<()> {
+        write!(out, "group")?;
+        for name in self.names.iter() {
+            write!(out, "\t{}", name.as_bstr())?;
+        }
+        writeln!(out)?;
+
+        for (a, name) in self.names.iter().enumerate() {
+            write!(out, "{}", name.as_bstr())?;
+            for b in 0..self.len() {
+                write!(out, "\t{:.6}", self.distance(metric, a, b))?;
+            }
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hashgraph::HashGraph;
+    use crate::mutablehandlegraph::*;
+    use crate::packedgraph::PackedGraph;
+    use crate::util::test::{add_path, edge, hnd, seq_graph};
+
+    fn build_graph<G>() -> G
+    where
+        G: Default + AdditiveHandleGraph + MutableGraphPaths,
+    {
+        let seqs: [&[u8]; 4] = [b"AAAA", b"CC", b"GGGGGG", b"T"];
+        let edges =
+            [edge(1, 2), edge(1, 3), edge(2, 4), edge(3, 4), edge(4, 1)];
+        let mut graph: G = seq_graph(&seqs, &edges);
+
+        let (n1, n2, n3, n4) = (hnd(1), hnd(2), hnd(3), hnd(4));
+
+        add_path(&mut graph, b"a#1", &[n1, n2, n4]);
+        add_path(&mut graph, b"a#2", &[n1, n3, n4]);
+        add_path(&mut graph, b"b#1", &[n1, n2, n4, n1, n2, n4]);
+
+        graph
+    }
+
+    fn check_distances<G>(graph: G)
+    where
+        G: HandleGraphRef
+            + IntoNodeOccurrences
+            + IntoPathIds
+            + GraphPathNames
+            + Send
+            + Sync,
+    {
+        let groups = PathGroups::by_path(graph);
+        let dists = PathDistances::new(graph, &groups);
+
+        assert_eq!(
+            dists.names(),
+            &[b"a#1".to_vec(), b"a#2".to_vec(), b"b#1".to_vec()]
+        );
+
+        let near = |x: f64, y: f64| (x - y).abs() < 1e-9;
+
+        use DistanceMetric::*;
+
+        // a#1 and a#2 share nodes 1 and 4, out of all four
+        assert!(near(dists.distance(NodeJaccard, 0, 1), 0.5));
+        // ... which are 5 of the 13 bases
+        assert!(near(dists.distance(BaseJaccard, 0, 1), 8.0 / 13.0));
+        // a#1 and b#1 cover the same nodes, b#1 twice
+        assert!(near(dists.distance(NodeJaccard, 0, 2), 0.0));
+        assert!(near(dists.distance(BaseJaccard, 2, 0), 0.0));
+        assert!(near(dists.distance(Euclidean, 0, 1), 8.0f64.sqrt()));
+
+        for metric in [NodeJaccard, BaseJaccard, Euclidean].iter() {
+            let matrix = dists.matrix(*metric);
+            for a in 0..3 {
+                assert!(near(matrix[a * 3 + a], 0.0));
+                for b in 0..3 {
+                    assert!(near(matrix[a * 3 + b], matrix[b * 3 + a]));
+                }
+            }
+        }
+
+        let samples = PathGroups::by_name_prefix(graph, b'#');
+        let dists = PathDistances::new(graph, &samples);
+        assert_eq!(dists.names(), &[b"a".to_vec(), b"b".to_vec()]);
+
+        // sample a covers all nodes, b only 1, 2 and 4
+        assert!(near(dists.distance(NodeJaccard, 0, 1), 0.25));
+
+        let mut tsv: Vec<u8> = Vec::new();
+        dists.write_tsv(NodeJaccard, &mut tsv).unwrap();
+        assert_eq!(
+            tsv.as_bstr(),
+            b"group\ta\tb\n\
+              a\t0.000000\t0.250000\n\
+              b\t0.250000\t0.000000\n"
+                .as_bstr()
+        );
+    }
+
+    #[test]
+    fn packedgraph_path_distances() {
+        let graph: PackedGraph = build_graph();
+        check_distances(&graph);
+
+        // b#1 visits each of its nodes twice, for a coverage
+        // difference of one on 7 bases
+        let groups = PathGroups::by_path(&graph);
+        let dists = PathDistances::new(&graph, &groups);
+        let euclidean = dists.distance(DistanceMetric::Euclidean, 0, 2);
+        assert!((euclidean - 7.0f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn hashgraph_path_distances() {
+        let graph: HashGraph = build_graph();
+        check_distances(&graph);
+    }
+}
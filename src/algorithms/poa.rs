@@ -0,0 +1,299 @@
+/*!
+
+Build variation graphs from sets of sequences by partial order
+alignment, similar to spoa.
+
+Each sequence is aligned to the graph built so far using
+[`align_to_graph`], and the alignment is then merged into the graph:
+nodes are divided at the boundaries of the alignment edits, matching
+bases reuse the existing nodes, and mismatching and inserted bases
+become new nodes, with edges added around them. Every sequence is
+embedded as a path, so the sequences can be recovered from the
+graph.
+
+Since the whole graph is realigned to for every sequence, this is
+meant for small graphs, such as a single locus.
+
+*/
+
+use crate::{
+    handle::{Edge, Handle},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+};
+
+use super::align::{align_to_graph, AlignmentMode, AlignmentScoring, EditOp};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// Divide the node of `handle` at the given offsets, which are
+/// relative to the orientation of `handle`, and return the resulting
+/// handles in the same orientation as `handle`, along with the
+/// offset each of them starts at.
+fn divide_oriented<G>(
+    graph: &mut G,
+    handle: Handle,
+    offsets: &[usize],
+) -> Vec<(Handle, usize)>
+where
+    G: MutableHandles,
+    for<'a> &'a G: HandleGraphRef,
+{
+    let len = graph.node_len(handle);
+
+    let mut fwd_offsets = offsets
+        .iter()
+        .filter(|&&o| o > 0 && o < len)
+        .map(|&o| if handle.is_reverse() { len - o } else { o })
+        .collect::<Vec<_>>();
+    fwd_offsets.sort_unstable();
+    fwd_offsets.dedup();
+
+    let parts = if fwd_offsets.is_empty() {
+        vec![handle.forward()]
+    } else {
+        graph.divide_handle(handle.forward(), &fwd_offsets)
+    };
+
+    let parts: Vec<Handle> = if handle.is_reverse() {
+        parts.into_iter().rev().map(|h| h.flip()).collect()
+    } else {
+        parts
+    };
+
+    let mut start = 0;
+    parts
+        .into_iter()
+        .map(|h| {
+            let part = (h, start);
+            start += graph.node_len(h);
+            part
+        })
+        .collect()
+}
+
+/// Builds the new path through the graph, adding the nodes for
+/// novel sequence and the edges between consecutive steps.
+struct PathBuilder<'a, G> {
+    graph: &'a mut G,
+    steps: Vec<Handle>,
+    novel: Vec<u8>,
+}
+
+impl<'a, G> PathBuilder<'a, G>
+where
+    G: AdditiveHandleGraph,
+{
+    fn push(&mut self, handle: Handle) {
+        if let Some(&prev) = self.steps.last() {
+            self.graph.create_edge(Edge(prev, handle));
+        }
+        self.steps.push(handle);
+    }
+
+    fn flush_novel(&mut self) {
+        if !self.novel.is_empty() {
+            let novel = std::mem::take(&mut self.novel);
+            let handle = self.graph.append_handle(&novel);
+            self.push(handle);
+        }
+    }
+}
+
+/// Align `sequence` to `graph` and merge it into the graph, embedding
+/// it as the path `name`. An empty graph simply gets a single node
+/// with the sequence.
+///
+/// Returns `None` if the path already exists, or if `sequence` is
+/// empty.
+pub fn poa_add_sequence<G>(
+    graph: &mut G,
+    name: &[u8],
+    sequence: &[u8],
+    scoring: &AlignmentScoring,
+) -> Option<PathId>
+where
+    G: MutableHandles + MutableGraphPaths,
+    for<'a> &'a G: HandleGraphRef + GraphPathNames,
+{
+    if sequence.is_empty() || graph.has_path(name) {
+        return None;
+    }
+
+    let alignment =
+        align_to_graph(&*graph, sequence, scoring, AlignmentMode::Glocal);
+
+    let mut builder = PathBuilder {
+        graph,
+        steps: Vec::new(),
+        novel: Vec::new(),
+    };
+
+    match alignment {
+        None => builder.novel.extend_from_slice(sequence),
+        Some(alignment) => {
+            let mut query_pos = alignment.query_start;
+
+            // leading query bases are only possible in local mode,
+            // but they're still part of the sequence
+            builder
+                .novel
+                .extend_from_slice(&sequence[..alignment.query_start]);
+
+            for mapping in alignment.path.iter() {
+                let mut cuts = vec![mapping.offset];
+                let mut offset = mapping.offset;
+                for edit in mapping.edits.iter() {
+                    if edit.op != EditOp::Insertion {
+                        offset += edit.len;
+                        cuts.push(offset);
+                    }
+                }
+
+                let parts =
+                    divide_oriented(builder.graph, mapping.handle, &cuts);
+
+                let mut offset = mapping.offset;
+                for edit in mapping.edits.iter() {
+                    let query = &sequence[query_pos..];
+                    match edit.op {
+                        EditOp::Match => {
+                            builder.flush_novel();
+                            let end = offset + edit.len;
+                            for &(part, start) in parts.iter() {
+                                if start >= offset && start < end {
+                                    builder.push(part);
+                                }
+                            }
+                            offset = end;
+                            query_pos += edit.len;
+                        }
+                        EditOp::Mismatch | EditOp::Insertion => {
+                            builder.novel.extend_from_slice(&query[..edit.len]);
+                            query_pos += edit.len;
+                            if edit.op == EditOp::Mismatch {
+                                offset += edit.len;
+                            }
+                        }
+                        EditOp::Deletion => {
+                            offset += edit.len;
+                        }
+                    }
+                }
+            }
+
+            builder
+                .novel
+                .extend_from_slice(&sequence[alignment.query_end..]);
+        }
+    }
+
+    builder.flush_novel();
+
+    let steps = builder.steps;
+
+    let path = graph.create_path(name, false)?;
+    for handle in steps {
+        graph.path_append_step(path, handle);
+    }
+
+    Some(path)
+}
+
+/// Build a graph from the provided `(name, sequence)` pairs by
+/// partial order alignment, with each sequence embedded as a path
+/// named `name`. Sequences are added in order; empty sequences, and
+/// sequences with duplicate names, are skipped.
+pub fn poa_graph<G, I, N, S>(sequences: I, scoring: &AlignmentScoring) -> G
+where
+    G: Default + MutableHandles + MutableGraphPaths,
+    for<'a> &'a G: HandleGraphRef + GraphPathNames,
+    I: IntoIterator<Item = (N, S)>,
+    N: AsRef<[u8]>,
+    S: AsRef<[u8]>,
+{
+    let mut graph = G::default();
+
+    for (name, seq) in sequences {
+        let path =
+            poa_add_sequence(&mut graph, name.as_ref(), seq.as_ref(), scoring);
+        if path.is_none() {
+            debug!("poa: skipped sequence {:?}", name.as_ref());
+        }
+    }
+
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conversion::fasta::path_sequence;
+    use crate::hashgraph::HashGraph;
+    use crate::packedgraph::PackedGraph;
+
+    const SEQS: [(&[u8], &[u8]); 5] = [
+        (b"s1", b"ACGTACGTTTGACCA"),
+        (b"s2", b"ACGTAGGTTTGACCA"),
+        (b"s3", b"ACGTACGTGACCA"),
+        (b"s4", b"ACGTACGTTTTTGACCA"),
+        (b"s5", b"TTACGTACGTTTGACCAGG"),
+    ];
+
+    fn check_graph<G>(graph: &G)
+    where
+        G: HandleGraph,
+        for<'a> &'a G:
+            HandleGraphRef + GraphPathsSteps + GraphPathNames + IntoSequences,
+    {
+        for &(name, seq) in SEQS.iter() {
+            let path = graph.get_path_id(name).unwrap();
+            assert_eq!(path_sequence(graph, path).unwrap(), seq);
+
+            // consecutive steps are connected
+            let steps = graph
+                .path_steps(path)
+                .unwrap()
+                .map(|s| s.handle())
+                .collect::<Vec<_>>();
+            for w in steps.windows(2) {
+                assert!(graph.has_edge(w[0], w[1]));
+            }
+        }
+
+        // the shared sequence is only stored once, so the graph is
+        // much smaller than the input
+        let input_len: usize = SEQS.iter().map(|(_, s)| s.len()).sum();
+        assert!(graph.total_length() < input_len / 2);
+        assert!(graph.total_length() >= SEQS[0].1.len());
+    }
+
+    #[test]
+    fn poa_hashgraph() {
+        let scoring = AlignmentScoring::default();
+        let graph: HashGraph = poa_graph(SEQS.iter().copied(), &scoring);
+        check_graph(&graph);
+    }
+
+    #[test]
+    fn poa_packedgraph() {
+        let scoring = AlignmentScoring::default();
+        let mut graph: PackedGraph = poa_graph(SEQS.iter().copied(), &scoring);
+        check_graph(&graph);
+
+        assert_eq!(
+            poa_add_sequence(&mut graph, b"s1", b"ACGT", &scoring),
+            None
+        );
+        assert_eq!(poa_add_sequence(&mut graph, b"s6", b"", &scoring), None);
+
+        // a completely novel sequence is still added
+        let path = poa_add_sequence(&mut graph, b"s6", b"GGGGGG", &scoring);
+        let path = path.unwrap();
+        assert_eq!(path_sequence(&graph, path).unwrap(), b"GGGGGG");
+        check_graph(&graph);
+    }
+}
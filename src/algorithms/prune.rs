@@ -0,0 +1,457 @@
+/*!
+
+Simplify a graph before indexing, by removing rarely used nodes and
+edges, and by cutting apart regions where the number of walks
+explodes, in the style of `vg prune`.
+
+All the criteria are evaluated on the graph as it is before anything
+is removed, and then everything is removed at once. Removing a node or
+an edge breaks the paths that step through it, so those paths are
+destroyed. The nodes and edges of the paths listed as protected are
+never removed, so the protected paths are restored in the pruned
+graph exactly as they were.
+
+*/
+
+use crate::{
+    handle::{Direction, Edge, Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+};
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// A limit on the number of distinct walks starting from each
+/// oriented node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WalkLimit {
+    /// The number of edges to follow from each node. Walks that reach
+    /// a dead end before taking this many steps are counted as well.
+    pub walk_length: usize,
+    /// The maximum number of distinct walks from a node. The edges on
+    /// the side of an oriented node with more walks are removed.
+    pub max_walks: usize,
+}
+
+/// What to remove when pruning a graph. The default removes nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneOptions {
+    /// Remove the nodes that are stepped on by fewer than this many
+    /// path steps.
+    pub min_coverage: Option<usize>,
+    /// Remove the edges that aren't traversed by any path.
+    pub remove_untraversed_edges: bool,
+    /// Remove the edges out of complex regions, and the nodes that
+    /// are left without any edges.
+    pub walk_limit: Option<WalkLimit>,
+    /// The names of the paths whose nodes and edges are kept.
+    pub protected_paths: Vec<Vec<u8>>,
+}
+
+/// What was removed from a graph when pruning it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// The removed nodes, sorted by ID.
+    pub removed_nodes: Vec<NodeId>,
+    /// The removed edges, including those of the removed nodes, sorted.
+    pub removed_edges: Vec<Edge>,
+    /// The names of the paths that were destroyed, sorted.
+    pub destroyed_paths: Vec<Vec<u8>>,
+    /// The number of oriented nodes with too many walks.
+    pub complex_handles: usize,
+    /// The number of nodes that would have been removed, but were
+    /// kept as they're on a protected path.
+    pub restored_nodes: usize,
+    /// The number of edges that would have been removed, but were
+    /// kept as they're on a protected path.
+    pub restored_edges: usize,
+}
+
+/// The edges between consecutive steps on `path`, including the edge
+/// from the last to the first step of a circular path.
+fn path_edges<G>(graph: G, path: PathId) -> Vec<Edge>
+where
+    G: GraphPathsSteps + Copy,
+{
+    let handles = graph
+        .path_steps(path)
+        .map(|steps| steps.map(|s| s.handle()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut edges = handles
+        .windows(2)
+        .map(|w| Edge::edge_handle(w[0], w[1]))
+        .collect::<Vec<_>>();
+
+    if graph.path_circular(path).unwrap_or(false) {
+        if let (Some(&first), Some(&last)) = (handles.first(), handles.last()) {
+            edges.push(Edge::edge_handle(last, first));
+        }
+    }
+
+    edges
+}
+
+fn incident_edges<G: HandleGraphRef>(graph: G, id: NodeId) -> Vec<Edge> {
+    let handle = Handle::pack(id, false);
+    let rights = graph
+        .neighbors(handle, Direction::Right)
+        .map(|n| Edge::edge_handle(handle, n));
+    let lefts = graph
+        .neighbors(handle, Direction::Left)
+        .map(|n| Edge::edge_handle(n, handle));
+    rights.chain(lefts).collect()
+}
+
+/// Find the oriented nodes with more than `limit.max_walks` distinct
+/// walks of `limit.walk_length` steps to their right.
+fn complex_handles<G: HandleGraphRef>(
+    graph: G,
+    limit: WalkLimit,
+) -> Vec<Handle> {
+    // the counts are capped, as they grow exponentially
+    let cap = limit.max_walks.saturating_add(1);
+
+    let handles = graph
+        .handles()
+        .flat_map(|h| vec![h, h.flip()])
+        .collect::<Vec<_>>();
+
+    let mut walks: FnvHashMap<Handle, usize> =
+        handles.iter().map(|&h| (h, 1)).collect();
+
+    for _ in 0..limit.walk_length {
+        walks = handles
+            .iter()
+            .map(|&h| {
+                let count = graph
+                    .neighbors(h, Direction::Right)
+                    .map(|n| walks[&n])
+                    .fold(None, |sum: Option<usize>, c| {
+                        Some(sum.unwrap_or(0).saturating_add(c))
+                    })
+                    .unwrap_or(1);
+                (h, count.min(cap))
+            })
+            .collect();
+    }
+
+    handles
+        .into_iter()
+        .filter(|h| walks[h] > limit.max_walks)
+        .collect()
+}
+
+/// Prune `graph` according to `options`, and report what was removed.
+pub fn prune<G>(graph: &mut G, options: &PruneOptions) -> PruneReport
+where
+    G: MutableHandleGraph + MutableGraphPaths,
+    for<'a> &'a G: HandleGraphRef
+        + GraphPathNames
+        + IntoPathIds
+        + GraphPathsSteps
+        + IntoNodeOccurrences,
+{
+    let mut report = PruneReport::default();
+
+    let mut protected_nodes: FnvHashSet<NodeId> = FnvHashSet::default();
+    let mut protected_edges: FnvHashSet<Edge> = FnvHashSet::default();
+
+    for name in options.protected_paths.iter() {
+        if let Some(path) = graph.get_path_id(name) {
+            if let Some(steps) = graph.path_steps(path) {
+                protected_nodes.extend(steps.map(|s| s.handle().id()));
+            }
+            protected_edges.extend(path_edges(&*graph, path));
+        }
+    }
+
+    let mut remove_nodes: FnvHashSet<NodeId> = FnvHashSet::default();
+    let mut remove_edges: FnvHashSet<Edge> = FnvHashSet::default();
+
+    let mut restored_nodes: FnvHashSet<NodeId> = FnvHashSet::default();
+    let mut restored_edges: FnvHashSet<Edge> = FnvHashSet::default();
+
+    if let Some(min_coverage) = options.min_coverage {
+        for handle in graph.handles() {
+            let coverage = graph
+                .steps_on_handle(handle)
+                .map(|steps| steps.count())
+                .unwrap_or(0);
+            if coverage < min_coverage {
+                if protected_nodes.contains(&handle.id()) {
+                    restored_nodes.insert(handle.id());
+                } else {
+                    remove_nodes.insert(handle.id());
+                }
+            }
+        }
+    }
+
+    if options.remove_untraversed_edges {
+        let traversed: FnvHashSet<Edge> = graph
+            .path_ids()
+            .flat_map(|path| path_edges(&*graph, path))
+            .collect();
+
+        // the protected paths are traversed, so their edges are kept
+        remove_edges.extend(
+            graph
+                .edges()
+                .map(|Edge(l, r)| Edge::edge_handle(l, r))
+                .filter(|edge| !traversed.contains(edge)),
+        );
+    }
+
+    if let Some(limit) = options.walk_limit {
+        let complex = complex_handles(&*graph, limit);
+        report.complex_handles = complex.len();
+
+        let mut cut: FnvHashSet<Edge> = FnvHashSet::default();
+        for handle in complex {
+            for next in graph.neighbors(handle, Direction::Right) {
+                let edge = Edge::edge_handle(handle, next);
+                if protected_edges.contains(&edge) {
+                    restored_edges.insert(edge);
+                } else {
+                    cut.insert(edge);
+                }
+            }
+        }
+
+        // nodes that the cut leaves without any edges are removed too
+        let endpoints = cut
+            .iter()
+            .flat_map(|&Edge(l, r)| vec![l.id(), r.id()])
+            .collect::<FnvHashSet<_>>();
+
+        remove_edges.extend(cut);
+
+        for id in endpoints {
+            let isolated = incident_edges(&*graph, id)
+                .iter()
+                .all(|edge| remove_edges.contains(edge));
+            if isolated {
+                if protected_nodes.contains(&id) {
+                    restored_nodes.insert(id);
+                } else {
+                    remove_nodes.insert(id);
+                }
+            }
+        }
+    }
+
+    // the paths on the removed nodes and edges are destroyed first,
+    // as not all graphs do that when removing a node
+    let broken_paths = graph
+        .path_ids()
+        .filter(|&path| {
+            let on_node = graph
+                .path_steps(path)
+                .into_iter()
+                .flatten()
+                .any(|s| remove_nodes.contains(&s.handle().id()));
+            on_node
+                || path_edges(&*graph, path)
+                    .iter()
+                    .any(|edge| remove_edges.contains(edge))
+        })
+        .collect::<Vec<_>>();
+
+    for path in broken_paths {
+        if let Some(name) = graph.get_path_name_vec(path) {
+            report.destroyed_paths.push(name);
+        }
+        graph.destroy_path(path);
+    }
+
+    let mut removed_edges: FnvHashSet<Edge> = FnvHashSet::default();
+
+    for &edge in remove_edges.iter() {
+        if graph.remove_edge(edge) {
+            removed_edges.insert(edge);
+        }
+    }
+
+    for &id in remove_nodes.iter() {
+        removed_edges.extend(incident_edges(&*graph, id));
+        graph.remove_handle(Handle::pack(id, false));
+    }
+
+    report.restored_nodes = restored_nodes.len();
+    report.restored_edges = restored_edges.len();
+    report.removed_nodes = remove_nodes.into_iter().collect();
+    report.removed_nodes.sort();
+    report.removed_edges = removed_edges.into_iter().collect();
+    report.removed_edges.sort();
+    report.destroyed_paths.sort();
+
+    debug!(
+        "pruned {} nodes, {} edges, and {} paths",
+        report.removed_nodes.len(),
+        report.removed_edges.len(),
+        report.destroyed_paths.len()
+    );
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{packedgraph::PackedGraph, util::test::test_graph_no_paths};
+
+    fn hnd(x: u64) -> Handle {
+        Handle::pack(x, false)
+    }
+
+    fn r_hnd(x: u64) -> Handle {
+        Handle::pack(x, true)
+    }
+
+    fn vec_hnd(ids: &[u64]) -> Vec<Handle> {
+        ids.iter().copied().map(hnd).collect()
+    }
+
+    fn edge(l: u64, r: u64) -> Edge {
+        Edge(hnd(l), hnd(r))
+    }
+
+    fn add_path(graph: &mut PackedGraph, name: &[u8], steps: &[Handle]) {
+        let path = graph.create_path(name, false).unwrap();
+        for &h in steps {
+            graph.path_append_step(path, h);
+        }
+    }
+
+    fn test_graph() -> PackedGraph {
+        let mut graph: PackedGraph = test_graph_no_paths();
+        add_path(&mut graph, b"path1", &[hnd(1), hnd(2), hnd(7), hnd(3)]);
+        add_path(&mut graph, b"path2", &[r_hnd(6), r_hnd(4), r_hnd(8)]);
+        add_path(&mut graph, b"path3", &[hnd(1), hnd(8)]);
+        graph
+    }
+
+    fn ids(ids: &[u64]) -> Vec<NodeId> {
+        ids.iter().map(|&i| NodeId::from(i)).collect()
+    }
+
+    #[test]
+    fn prune_coverage_and_edges() {
+        let mut graph = test_graph();
+        let options = PruneOptions {
+            min_coverage: Some(1),
+            remove_untraversed_edges: true,
+            ..PruneOptions::default()
+        };
+
+        let report = prune(&mut graph, &options);
+        assert_eq!(report.removed_nodes, ids(&[5, 9]));
+        assert_eq!(report.removed_edges.len(), 7);
+        assert!(report.destroyed_paths.is_empty());
+
+        assert_eq!(graph.node_count(), 7);
+        assert_eq!(graph.edge_count(), 6);
+        assert_eq!(graph.path_count(), 3);
+        for Edge(l, r) in graph.edges() {
+            let edge = Edge::edge_handle(l, r);
+            assert!(!report.removed_edges.contains(&edge));
+        }
+    }
+
+    #[test]
+    fn prune_protected_paths() {
+        let options = PruneOptions {
+            min_coverage: Some(2),
+            ..PruneOptions::default()
+        };
+
+        // only node 1 and 8 are on two paths
+        let mut graph = test_graph();
+        let report = prune(&mut graph, &options);
+        assert_eq!(report.removed_nodes, ids(&[2, 3, 4, 5, 6, 7, 9]));
+        assert_eq!(report.destroyed_paths, vec![b"path1", b"path2"]);
+        assert_eq!(graph.path_count(), 1);
+
+        let mut graph = test_graph();
+        let options = PruneOptions {
+            protected_paths: vec![b"path1".to_vec()],
+            ..options
+        };
+        let report = prune(&mut graph, &options);
+        assert_eq!(report.removed_nodes, ids(&[4, 5, 6, 9]));
+        assert_eq!(report.restored_nodes, 3);
+        assert_eq!(report.destroyed_paths, vec![b"path2"]);
+
+        let path = graph.get_path_id(b"path1").unwrap();
+        let steps = graph
+            .path_steps(path)
+            .unwrap()
+            .map(|s| s.handle())
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![hnd(1), hnd(2), hnd(7), hnd(3)]);
+    }
+
+    #[test]
+    fn prune_complex_regions() {
+        // a chain of three bubbles, with eight walks from end to end
+        let mut graph = PackedGraph::default();
+        for _ in 0..10 {
+            graph.append_handle(b"A");
+        }
+        let edges = [
+            (1, 2),
+            (1, 3),
+            (2, 4),
+            (3, 4),
+            (4, 5),
+            (4, 6),
+            (5, 7),
+            (6, 7),
+            (7, 8),
+            (7, 9),
+            (8, 10),
+            (9, 10),
+        ];
+        for &(l, r) in edges.iter() {
+            graph.create_edge(Edge(hnd(l), hnd(r)));
+        }
+        add_path(&mut graph, b"ref", &vec_hnd(&[1, 2, 4, 5, 7, 8, 10]));
+        add_path(&mut graph, b"alt", &vec_hnd(&[1, 3, 4, 6, 7, 9, 10]));
+
+        let options = PruneOptions {
+            walk_limit: Some(WalkLimit {
+                walk_length: 6,
+                max_walks: 4,
+            }),
+            ..PruneOptions::default()
+        };
+
+        // only the walks from either end of the chain are too many
+        let mut pruned = graph.clone();
+        let report = prune(&mut pruned, &options);
+        assert_eq!(report.complex_handles, 2);
+        assert_eq!(report.removed_nodes, ids(&[1, 10]));
+        assert_eq!(report.removed_edges.len(), 4);
+        assert_eq!(report.destroyed_paths, vec![b"alt", b"ref"]);
+        assert_eq!(pruned.node_count(), 8);
+        assert_eq!(pruned.edge_count(), 8);
+
+        let options = PruneOptions {
+            protected_paths: vec![b"ref".to_vec()],
+            ..options
+        };
+        let mut pruned = graph.clone();
+        let report = prune(&mut pruned, &options);
+        assert!(report.removed_nodes.is_empty());
+        assert_eq!(report.restored_edges, 2);
+        assert_eq!(report.removed_edges, vec![edge(1, 3), edge(9, 10)]);
+        assert_eq!(report.destroyed_paths, vec![b"alt"]);
+        assert_eq!(pruned.edge_count(), 10);
+    }
+}
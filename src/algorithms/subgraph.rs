@@ -0,0 +1,214 @@
+/*!
+
+Inducing the subgraph of a set of paths, e.g. to extract the haplotypes
+of a subset of the samples in a graph.
+
+The induced subgraph contains exactly the nodes and edges traversed by
+the chosen paths, with their original IDs, and only those paths. The
+nodes and edges are found by walking the steps of the paths, so the
+rest of the graph is never scanned.
+
+*/
+
+use crate::{
+    handle::{Edge, Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+};
+
+use fnv::FnvHashSet;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// Build the subgraph of `graph` made of the nodes and edges traversed
+/// by the paths in `path_names`, and copy those paths into it, in the
+/// given order. Names that aren't paths in `graph` are skipped.
+pub fn induce_from_paths<G, T>(graph: G, path_names: &[&[u8]]) -> T
+where
+    G: HandleGraphRef + GraphPathNames + GraphPathsSteps,
+    T: AdditiveHandleGraph + MutableGraphPaths + Default,
+{
+    let mut seen: FnvHashSet<PathId> = FnvHashSet::default();
+    let paths: Vec<PathId> = path_names
+        .iter()
+        .filter_map(|name| graph.get_path_id(name))
+        .filter(|&path| seen.insert(path))
+        .collect();
+
+    let mut nodes: FnvHashSet<NodeId> = FnvHashSet::default();
+    let mut edges: FnvHashSet<Edge> = FnvHashSet::default();
+
+    for &path in paths.iter() {
+        let mut handles = graph.path_steps(path).into_iter().flatten();
+
+        let first = match handles.next() {
+            Some(step) => step.handle(),
+            None => continue,
+        };
+        nodes.insert(first.id());
+
+        let mut prev = first;
+        for step in handles {
+            let handle = step.handle();
+            nodes.insert(handle.id());
+            edges.insert(Edge::edge_handle(prev, handle));
+            prev = handle;
+        }
+
+        if graph.path_circular(path).unwrap_or(false) {
+            edges.insert(Edge::edge_handle(prev, first));
+        }
+    }
+
+    let mut nodes = nodes.into_iter().collect::<Vec<_>>();
+    nodes.sort();
+
+    let mut subgraph = T::default();
+
+    for &id in nodes.iter() {
+        let seq = graph.sequence_vec(Handle::pack(id, false));
+        subgraph.create_handle(&seq, id);
+    }
+
+    for &edge in edges.iter() {
+        subgraph.create_edge(edge);
+    }
+
+    for &path in paths.iter() {
+        let name = graph.get_path_name_vec(path).unwrap_or_default();
+        let circular = graph.path_circular(path).unwrap_or(false);
+        let new_path = subgraph.create_path(&name, circular).unwrap();
+
+        for step in graph.path_steps(path).into_iter().flatten() {
+            subgraph.path_append_step(new_path, step.handle());
+        }
+    }
+
+    debug!(
+        "induced subgraph of {} paths with {} nodes and {} edges",
+        paths.len(),
+        nodes.len(),
+        edges.len()
+    );
+
+    subgraph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        hashgraph::HashGraph, packedgraph::PackedGraph, util::test::add_path,
+    };
+
+    fn hnd(x: u64) -> Handle {
+        Handle::pack(x, false)
+    }
+
+    fn r_hnd(x: u64) -> Handle {
+        Handle::pack(x, true)
+    }
+
+    fn test_graph<G>() -> G
+    where
+        G: AdditiveHandleGraph + MutableGraphPaths + Default,
+    {
+        let mut graph = G::default();
+
+        let seqs: [&[u8]; 6] = [b"GAT", b"T", b"A", b"CA", b"GG", b"TC"];
+        for seq in seqs.iter() {
+            graph.append_handle(seq);
+        }
+
+        // a bubble at 2/3, and an inversion of 4, which only sample
+        // b traverses, and node 6 that no path is on
+        let edges = [
+            (hnd(1), hnd(2)),
+            (hnd(1), hnd(3)),
+            (hnd(2), hnd(4)),
+            (hnd(3), hnd(4)),
+            (hnd(3), r_hnd(4)),
+            (hnd(4), hnd(5)),
+            (r_hnd(4), hnd(5)),
+            (hnd(5), hnd(6)),
+        ];
+        for &(l, r) in edges.iter() {
+            graph.create_edge(Edge(l, r));
+        }
+
+        add_path(&mut graph, b"a#1", &[hnd(1), hnd(2), hnd(4), hnd(5)]);
+        add_path(&mut graph, b"a#2", &[hnd(1), hnd(2), hnd(4)]);
+        add_path(&mut graph, b"b#1", &[hnd(1), hnd(3), r_hnd(4), hnd(5)]);
+
+        graph
+    }
+
+    fn check_induce<G>()
+    where
+        G: HandleGraph + AdditiveHandleGraph + MutableGraphPaths + Default,
+        for<'a> &'a G:
+            HandleGraphRef + GraphPathNames + GraphPathsSteps + IntoPathIds,
+    {
+        let graph: G = test_graph();
+
+        let sub: G = induce_from_paths(&graph, &[b"a#1", b"a#2", b"c#1"]);
+        assert_eq!(sub.node_count(), 4);
+        assert_eq!(sub.edge_count(), 3);
+        assert!(!sub.has_node(3u64));
+        assert!(sub.has_edge(hnd(2), hnd(4)));
+        assert_eq!(sub.sequence_vec(hnd(4)), b"CA");
+
+        let mut names = (&sub)
+            .path_ids()
+            .filter_map(|path| sub.get_path_name_vec(path))
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec![b"a#1".to_vec(), b"a#2".to_vec()]);
+
+        // only the edges b#1 traverses, not 3 -> 4
+        let sub: G = induce_from_paths(&graph, &[b"b#1"]);
+        assert_eq!(sub.node_count(), 4);
+        assert_eq!(sub.edge_count(), 3);
+        assert!(sub.has_edge(hnd(3), r_hnd(4)));
+        assert!(!sub.has_edge(hnd(3), hnd(4)));
+        assert!(sub.has_edge(r_hnd(4), hnd(5)));
+
+        let path = sub.get_path_id(b"b#1").unwrap();
+        let steps = sub
+            .path_steps(path)
+            .unwrap()
+            .map(|s| s.handle())
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![hnd(1), hnd(3), r_hnd(4), hnd(5)]);
+
+        let sub: G = induce_from_paths(&graph, &[]);
+        assert_eq!(sub.node_count(), 0);
+
+        // a path that visits node 1 twice has the edges from both
+        // visits, even where a graph only records one occurrence of
+        // the path on the node
+        let mut graph: G = test_graph();
+        graph.create_edge(Edge(hnd(2), hnd(1)));
+        add_path(&mut graph, b"d#1", &[hnd(1), hnd(2), hnd(1), hnd(3)]);
+
+        let sub: G = induce_from_paths(&graph, &[b"d#1"]);
+        assert_eq!(sub.node_count(), 3);
+        assert_eq!(sub.edge_count(), 3);
+        assert!(sub.has_edge(hnd(1), hnd(2)));
+        assert!(sub.has_edge(hnd(2), hnd(1)));
+        assert!(sub.has_edge(hnd(1), hnd(3)));
+    }
+
+    #[test]
+    fn induce_packedgraph() {
+        check_induce::<PackedGraph>();
+    }
+
+    #[test]
+    fn induce_hashgraph() {
+        check_induce::<HashGraph>();
+    }
+}
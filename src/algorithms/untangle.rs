@@ -0,0 +1,489 @@
+/*!
+
+Map the segments of a query path to segments of a set of target
+paths, to find how haplotypes relate to each other, including
+structural rearrangements like inversions and translocations.
+
+For each target path, the query steps on nodes that are visited
+exactly once by both paths are used as anchors. Consecutive anchors
+that are collinear on the target, with the same relative strand and
+gaps within a limit, are chained into a single mapping. Each mapping
+is scored with the base-weighted Jaccard index of the nodes in the
+query and target segments.
+
+*/
+
+use crate::{
+    handle::{Handle, NodeId},
+    handlegraph::*,
+    pathhandlegraph::*,
+};
+
+use crate::packedgraph::PackedGraph;
+use crate::path_position::PathPositionMap;
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+use bstr::ByteSlice;
+
+use std::io::{self, Write};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UntangleParams {
+    /// The maximum gap, in bases on either path, between two anchors
+    /// that are chained into the same mapping.
+    pub max_gap: usize,
+    /// Mappings shorter than this on the query are discarded.
+    pub min_segment_len: usize,
+}
+
+impl Default for UntangleParams {
+    fn default() -> Self {
+        Self {
+            max_gap: 1000,
+            min_segment_len: 0,
+        }
+    }
+}
+
+/// A mapping from a segment of a query path to a segment of a target
+/// path. Coordinates are 0-based and end-exclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegmentMapping {
+    pub query: PathId,
+    pub query_start: usize,
+    pub query_end: usize,
+    pub target: PathId,
+    pub target_start: usize,
+    pub target_end: usize,
+    /// `true` if the query segment maps to the reverse strand of the
+    /// target.
+    pub is_reverse: bool,
+    /// The total length of the nodes shared by the two segments.
+    pub shared_bases: usize,
+    /// The base-weighted Jaccard index of the nodes in the two
+    /// segments.
+    pub jaccard: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    query_pos: usize,
+    target_pos: usize,
+    len: usize,
+    is_reverse: bool,
+}
+
+/// Count the number of times each node is visited by `path`.
+fn node_visits(graph: &PackedGraph, path: PathId) -> FnvHashMap<NodeId, usize> {
+    let mut visits: FnvHashMap<NodeId, usize> = FnvHashMap::default();
+    if let Some(steps) = graph.path_steps(path) {
+        for (_, step) in steps {
+            *visits.entry(step.handle.id()).or_default() += 1;
+        }
+    }
+    visits
+}
+
+/// The handle and 0-based position of each step on `path`.
+fn linear_steps(graph: &PackedGraph, path: PathId) -> Vec<(Handle, usize)> {
+    let mut pos = 0;
+    graph
+        .path_steps(path)
+        .into_iter()
+        .flatten()
+        .map(|(_, step)| {
+            let step_pos = pos;
+            pos += graph.node_len(step.handle);
+            (step.handle, step_pos)
+        })
+        .collect()
+}
+
+/// The nodes on the steps of `path` that overlap the 0-based range
+/// `start..end`.
+fn target_segment_nodes(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    path: PathId,
+    start: usize,
+    end: usize,
+) -> FnvHashSet<NodeId> {
+    let mut nodes = FnvHashSet::default();
+
+    let mut step = index.find_step_at_base(path, start + 1);
+
+    while let Some(step_ix) = step {
+        let step_pos = index.path_step_position(path, step_ix);
+        if !matches!(step_pos, Some(pos) if pos - 1 < end) {
+            break;
+        }
+        if let Some(handle) = graph.path_handle_at_step(path, step_ix) {
+            nodes.insert(handle.id());
+        }
+        step = graph.path_next_step(path, step_ix);
+    }
+
+    nodes
+}
+
+fn anchors_on_target(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    query_steps: &[(Handle, usize)],
+    query_visits: &FnvHashMap<NodeId, usize>,
+    target: PathId,
+) -> Vec<Anchor> {
+    let target_visits = node_visits(graph, target);
+
+    query_steps
+        .iter()
+        .filter_map(|&(handle, query_pos)| {
+            let id = handle.id();
+            if query_visits.get(&id) != Some(&1)
+                || target_visits.get(&id) != Some(&1)
+            {
+                return None;
+            }
+
+            let (_, step) = graph
+                .steps_on_handle(handle)?
+                .find(|(path, _)| *path == target)?;
+
+            let target_handle = graph.path_handle_at_step(target, step)?;
+            let target_pos = index.path_step_position(target, step)? - 1;
+
+            Some(Anchor {
+                query_pos,
+                target_pos,
+                len: graph.node_len(handle),
+                is_reverse: target_handle != handle,
+            })
+        })
+        .collect()
+}
+
+/// Whether `next` continues the chain ending with `prev`.
+fn is_collinear(prev: &Anchor, next: &Anchor, max_gap: usize) -> bool {
+    if prev.is_reverse != next.is_reverse {
+        return false;
+    }
+
+    let query_gap = next.query_pos - (prev.query_pos + prev.len);
+
+    let target_gap = if prev.is_reverse {
+        if next.target_pos + next.len > prev.target_pos {
+            return false;
+        }
+        prev.target_pos - (next.target_pos + next.len)
+    } else {
+        if next.target_pos < prev.target_pos + prev.len {
+            return false;
+        }
+        next.target_pos - (prev.target_pos + prev.len)
+    };
+
+    query_gap <= max_gap && target_gap <= max_gap
+}
+
+/// Find the mappings of the segments of `query` onto each of the
+/// `targets`, sorted by query start position and then by target.
+///
+/// Coordinates on the paths are taken from `index`, which must be
+/// up to date with the graph.
+pub fn untangle(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    query: PathId,
+    targets: &[PathId],
+    params: &UntangleParams,
+) -> Vec<SegmentMapping> {
+    let query_steps = linear_steps(graph, query);
+    let query_visits = node_visits(graph, query);
+
+    let mut mappings = Vec::new();
+
+    for &target in targets {
+        if target == query {
+            continue;
+        }
+
+        let anchors = anchors_on_target(
+            graph,
+            index,
+            &query_steps,
+            &query_visits,
+            target,
+        );
+
+        let mut chains: Vec<(usize, usize)> = Vec::new();
+        let mut chain_start = 0;
+
+        for ix in 1..=anchors.len() {
+            let chain_ends = ix == anchors.len()
+                || !is_collinear(
+                    &anchors[ix - 1],
+                    &anchors[ix],
+                    params.max_gap,
+                );
+            if chain_ends {
+                chains.push((chain_start, ix - 1));
+                chain_start = ix;
+            }
+        }
+
+        for (first, last) in chains {
+            let first = anchors[first];
+            let last = anchors[last];
+
+            let query_start = first.query_pos;
+            let query_end = last.query_pos + last.len;
+
+            if query_end - query_start < params.min_segment_len {
+                continue;
+            }
+
+            let (target_start, target_end) = if first.is_reverse {
+                (last.target_pos, first.target_pos + first.len)
+            } else {
+                (first.target_pos, last.target_pos + last.len)
+            };
+
+            let (shared_bases, jaccard) = segment_jaccard(
+                graph,
+                index,
+                &query_steps,
+                (query_start, query_end),
+                target,
+                (target_start, target_end),
+            );
+
+            mappings.push(SegmentMapping {
+                query,
+                query_start,
+                query_end,
+                target,
+                target_start,
+                target_end,
+                is_reverse: first.is_reverse,
+                shared_bases,
+                jaccard,
+            });
+        }
+    }
+
+    mappings.sort_by_key(|m| (m.query_start, m.target, m.target_start));
+
+    mappings
+}
+
+/// The number of bases in nodes shared by the two segments, and the
+/// base-weighted Jaccard index of their node sets.
+fn segment_jaccard(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    query_steps: &[(Handle, usize)],
+    (query_start, query_end): (usize, usize),
+    target: PathId,
+    (target_start, target_end): (usize, usize),
+) -> (usize, f64) {
+    let query_nodes: FnvHashSet<NodeId> = query_steps
+        .iter()
+        .filter(|(_, pos)| *pos >= query_start && *pos < query_end)
+        .map(|(handle, _)| handle.id())
+        .collect();
+
+    let target_nodes =
+        target_segment_nodes(graph, index, target, target_start, target_end);
+
+    let node_len = |id: &NodeId| graph.node_len(Handle::pack(*id, false));
+
+    let shared: usize =
+        query_nodes.intersection(&target_nodes).map(node_len).sum();
+    let union: usize = query_nodes.union(&target_nodes).map(node_len).sum();
+
+    let jaccard = if union == 0 {
+        0.0
+    } else {
+        shared as f64 / union as f64
+    };
+
+    (shared, jaccard)
+}
+
+/// Write `mappings` in PAF format, with the Jaccard index as the
+/// `jc:f` tag. The number of matching bases is the number of bases in
+/// shared nodes, and the mapping quality is always 255.
+pub fn write_paf<W: Write>(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    mappings: &[SegmentMapping],
+    out: &mut W,
+) -> io::Result<()> {
+    let mut names: FnvHashMap<PathId, Vec<u8>> = FnvHashMap::default();
+
+    for mapping in mappings.iter() {
+        for &path in [mapping.query, mapping.target].iter() {
+            names.entry(path).or_insert_with(|| {
+                graph.get_path_name_vec(path).unwrap_or_default()
+            });
+        }
+
+        let path_len =
+            |path: PathId| index.path_base_len(path).unwrap_or(1) - 1;
+
+        let block_len = (mapping.query_end - mapping.query_start)
+            .max(mapping.target_end - mapping.target_start);
+
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t255\tjc:f:{:.4}",
+            names[&mapping.query].as_bstr(),
+            path_len(mapping.query),
+            mapping.query_start,
+            mapping.query_end,
+            if mapping.is_reverse { '-' } else { '+' },
+            names[&mapping.target].as_bstr(),
+            path_len(mapping.target),
+            mapping.target_start,
+            mapping.target_end,
+            mapping.shared_bases,
+            block_len,
+            mapping.jaccard,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::handle::Edge;
+    use crate::mutablehandlegraph::*;
+    use crate::util::test::add_path;
+
+    /// `ref` traverses 1-2-3-4-5, while `inv` has node 3 inverted,
+    /// and `swap` visits 4 before 2.
+    fn test_graph() -> PackedGraph {
+        let mut graph = PackedGraph::default();
+
+        let n1 = graph.append_handle(b"AAAA");
+        let n2 = graph.append_handle(b"CCC");
+        let n3 = graph.append_handle(b"GGGGG");
+        let n4 = graph.append_handle(b"TT");
+        let n5 = graph.append_handle(b"ACGT");
+
+        graph.create_edge(Edge(n1, n2));
+        graph.create_edge(Edge(n2, n3));
+        graph.create_edge(Edge(n3, n4));
+        graph.create_edge(Edge(n4, n5));
+        graph.create_edge(Edge(n2, n3.flip()));
+        graph.create_edge(Edge(n3.flip(), n4));
+        graph.create_edge(Edge(n1, n4));
+        graph.create_edge(Edge(n4, n2));
+        graph.create_edge(Edge(n2, n5));
+
+        add_path(&mut graph, b"ref", &[n1, n2, n3, n4, n5]);
+        add_path(&mut graph, b"inv", &[n1, n2, n3.flip(), n4, n5]);
+        add_path(&mut graph, b"swap", &[n1, n4, n2, n5]);
+
+        graph
+    }
+
+    fn coords(
+        mappings: &[SegmentMapping],
+    ) -> Vec<(usize, usize, usize, usize, bool)> {
+        mappings
+            .iter()
+            .map(|m| {
+                (
+                    m.query_start,
+                    m.query_end,
+                    m.target_start,
+                    m.target_end,
+                    m.is_reverse,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn untangle_inversion() {
+        let graph = test_graph();
+        let index = PathPositionMap::index_paths(&graph);
+
+        let ref_path = graph.get_path_id(b"ref").unwrap();
+        let inv_path = graph.get_path_id(b"inv").unwrap();
+
+        let params = UntangleParams::default();
+        let mappings = untangle(&graph, &index, inv_path, &[ref_path], &params);
+
+        assert_eq!(
+            coords(&mappings),
+            vec![
+                (0, 7, 0, 7, false),
+                (7, 12, 7, 12, true),
+                (12, 18, 12, 18, false)
+            ]
+        );
+
+        assert!(mappings.iter().all(|m| (m.jaccard - 1.0).abs() < 1e-9));
+
+        let mut paf: Vec<u8> = Vec::new();
+        write_paf(&graph, &index, &mappings, &mut paf).unwrap();
+        let paf = paf.lines().map(|l| l.to_vec()).collect::<Vec<_>>();
+        assert_eq!(paf.len(), 3);
+        assert_eq!(
+            paf[1],
+            b"inv\t18\t7\t12\t-\tref\t18\t7\t12\t5\t5\t255\tjc:f:1.0000"
+        );
+    }
+
+    #[test]
+    fn untangle_rearrangement() {
+        let graph = test_graph();
+        let index = PathPositionMap::index_paths(&graph);
+
+        let ref_path = graph.get_path_id(b"ref").unwrap();
+        let swap_path = graph.get_path_id(b"swap").unwrap();
+
+        let params = UntangleParams::default();
+        let mappings =
+            untangle(&graph, &index, swap_path, &[ref_path], &params);
+
+        // AAAA TT CCC ACGT on swap, AAAA CCC GGGGG TT ACGT on ref
+        assert_eq!(
+            coords(&mappings),
+            vec![(0, 6, 0, 14, false), (6, 13, 4, 18, false),]
+        );
+
+        // the first mapping covers 1-2-3-4 on ref, but only 1 and 4
+        // on the query
+        assert_eq!(mappings[0].shared_bases, 6);
+        assert!((mappings[0].jaccard - 6.0 / 14.0).abs() < 1e-9);
+
+        // with a smaller gap, the gaps around the deleted node 3 are
+        // too long
+        let params = UntangleParams {
+            max_gap: 4,
+            min_segment_len: 0,
+        };
+        let mappings =
+            untangle(&graph, &index, swap_path, &[ref_path], &params);
+
+        assert_eq!(
+            coords(&mappings),
+            vec![
+                (0, 4, 0, 4, false),
+                (4, 6, 12, 14, false),
+                (6, 9, 4, 7, false),
+                (9, 13, 14, 18, false),
+            ]
+        );
+    }
+}
@@ -13,6 +13,10 @@ use gfa::{
     parser::GFAResult,
 };
 
+pub mod fasta;
+pub mod flatten;
+pub mod gaf;
+
 pub fn from_gfa<G, T>(gfa: &GFA<usize, T>) -> G
 where
     G: Default + AdditiveHandleGraph + MutableGraphPaths,
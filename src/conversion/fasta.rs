@@ -0,0 +1,243 @@
+/*!
+
+Extracting the sequences of embedded paths, and writing them as FASTA.
+
+*/
+
+use crate::{
+    handlegraph::*,
+    pathhandlegraph::{path::PathStep, *},
+};
+
+use bstr::ByteSlice;
+
+use rayon::prelude::*;
+
+use std::io::{self, Write};
+
+/// Returns the sequence spelled by the path `id`, if the path exists.
+/// Steps that traverse a node in reverse contribute the reverse
+/// complement of the node sequence.
+pub fn path_sequence<G>(graph: G, id: PathId) -> Option<Vec<u8>>
+where
+    G: GraphPathsSteps + IntoSequences + Copy,
+{
+    let steps = graph.path_steps(id)?;
+    let mut seq = Vec::new();
+    for step in steps {
+        seq.extend(graph.sequence(step.handle()));
+    }
+    Some(seq)
+}
+
+/// Writes the bases from `seq` to `out`, starting a new line every
+/// `line_width` bases; a `line_width` of zero disables wrapping.
+/// Non-empty sequences always end with a newline.
+pub(super) fn write_wrapped<W, I>(
+    out: &mut W,
+    seq: I,
+    line_width: usize,
+) -> io::Result<()>
+where
+    W: Write,
+    I: Iterator<Item = u8>,
+{
+    const BUF_LEN: usize = 8192;
+
+    let mut buf: Vec<u8> = Vec::with_capacity(BUF_LEN + 1);
+    let mut line_len = 0;
+
+    for base in seq {
+        buf.push(base);
+        line_len += 1;
+
+        if line_len == line_width {
+            buf.push(b'\n');
+            line_len = 0;
+        }
+
+        if buf.len() >= BUF_LEN {
+            out.write_all(&buf)?;
+            buf.clear();
+        }
+    }
+
+    if line_len > 0 {
+        buf.push(b'\n');
+    }
+
+    out.write_all(&buf)
+}
+
+/// Writes a single FASTA record, with the sequence wrapped at
+/// `line_width` bases per line, or on a single line if `line_width`
+/// is zero.
+pub fn write_fasta_record<W: Write>(
+    out: &mut W,
+    name: &[u8],
+    seq: &[u8],
+    line_width: usize,
+) -> io::Result<()> {
+    writeln!(out, ">{}", name.as_bstr())?;
+    write_wrapped(out, seq.iter().copied(), line_width)
+}
+
+/// Writes the path `id` as a FASTA record, streaming the sequence
+/// from the graph. Returns `false` if the path doesn't exist.
+pub fn write_path_fasta<G, W>(
+    graph: G,
+    id: PathId,
+    line_width: usize,
+    out: &mut W,
+) -> io::Result<bool>
+where
+    G: GraphPathsSteps + IntoSequences + GraphPathNames + Copy,
+    W: Write,
+{
+    let (name, steps) = match (graph.get_path_name(id), graph.path_steps(id)) {
+        (Some(name), Some(steps)) => (name.collect::<Vec<_>>(), steps),
+        _ => return Ok(false),
+    };
+
+    writeln!(out, ">{}", name.as_bstr())?;
+
+    let seq = steps.flat_map(|step| graph.sequence(step.handle()));
+    write_wrapped(out, seq, line_width)?;
+
+    Ok(true)
+}
+
+/// Writes each path in `paths` as a FASTA record, in order, with the
+/// sequences wrapped at `line_width` bases per line. Paths that don't
+/// exist in the graph are skipped.
+///
+/// To write all paths, use e.g. `graph.path_ids()` as `paths`.
+pub fn write_paths_fasta<G, W, I>(
+    graph: G,
+    paths: I,
+    line_width: usize,
+    out: &mut W,
+) -> io::Result<()>
+where
+    G: GraphPathsSteps + IntoSequences + GraphPathNames + Copy,
+    W: Write,
+    I: IntoIterator<Item = PathId>,
+{
+    for id in paths {
+        write_path_fasta(graph, id, line_width, out)?;
+    }
+    Ok(())
+}
+
+/// Like `write_paths_fasta`, but builds the FASTA records of multiple
+/// paths in parallel. The records are still written in the order of
+/// `paths`, and at most `chunk_size` records are kept in memory at
+/// once.
+pub fn write_paths_fasta_par<G, W>(
+    graph: G,
+    paths: &[PathId],
+    line_width: usize,
+    chunk_size: usize,
+    out: &mut W,
+) -> io::Result<()>
+where
+    G: GraphPathsSteps + IntoSequences + GraphPathNames + Copy + Send + Sync,
+    W: Write,
+{
+    for chunk in paths.chunks(chunk_size.max(1)) {
+        let records = chunk
+            .par_iter()
+            .map(|&id| {
+                let mut record: Vec<u8> = Vec::new();
+                write_path_fasta(graph, id, line_width, &mut record)?;
+                Ok(record)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        for record in records {
+            out.write_all(&record)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hashgraph::HashGraph;
+    use crate::mutablehandlegraph::*;
+    use crate::packedgraph::PackedGraph;
+    use crate::util::test::{add_path, hnd, r_hnd, small_graph};
+
+    fn build_graph<G>() -> G
+    where
+        G: Default + AdditiveHandleGraph + MutableGraphPaths,
+    {
+        let mut graph: G = small_graph();
+        add_path(&mut graph, b"fwd", &[hnd(1), hnd(2), hnd(3)]);
+        add_path(&mut graph, b"rev", &[r_hnd(3), r_hnd(2), r_hnd(1)]);
+        add_path(&mut graph, b"empty", &[]);
+        graph
+    }
+
+    fn check_graph<G>(graph: G)
+    where
+        G: GraphPathsSteps
+            + IntoSequences
+            + GraphPathNames
+            + Copy
+            + Send
+            + Sync,
+    {
+        let fwd = graph.get_path_id(b"fwd").unwrap();
+        let rev = graph.get_path_id(b"rev").unwrap();
+        let empty = graph.get_path_id(b"empty").unwrap();
+
+        assert_eq!(path_sequence(graph, fwd).unwrap(), b"AAACGGTCA");
+        assert_eq!(path_sequence(graph, rev).unwrap(), b"TGACCGTTT");
+        assert_eq!(path_sequence(graph, empty).unwrap(), b"");
+        assert_eq!(path_sequence(graph, PathId(10)), None);
+
+        let paths = [fwd, rev, empty];
+
+        let mut out: Vec<u8> = Vec::new();
+        write_paths_fasta(graph, paths.iter().copied(), 4, &mut out).unwrap();
+
+        let expected: &[u8] = b">fwd\nAAAC\nGGTC\nA\n\
+                                >rev\nTGAC\nCGTT\nT\n\
+                                >empty\n";
+        assert_eq!(out.as_bstr(), expected.as_bstr());
+
+        let mut par_out: Vec<u8> = Vec::new();
+        write_paths_fasta_par(graph, &paths, 4, 2, &mut par_out).unwrap();
+        assert_eq!(par_out.as_bstr(), expected.as_bstr());
+
+        let mut out: Vec<u8> = Vec::new();
+        write_paths_fasta(graph, vec![fwd], 0, &mut out).unwrap();
+        assert_eq!(out.as_bstr(), b">fwd\nAAACGGTCA\n".as_bstr());
+
+        let mut out: Vec<u8> = Vec::new();
+        write_paths_fasta(graph, vec![rev], 9, &mut out).unwrap();
+        assert_eq!(out.as_bstr(), b">rev\nTGACCGTTT\n".as_bstr());
+    }
+
+    #[test]
+    fn packedgraph_path_fasta() {
+        let graph: PackedGraph = build_graph();
+        check_graph(&graph);
+    }
+
+    #[test]
+    fn hashgraph_path_fasta() {
+        let graph: HashGraph = build_graph();
+        check_graph(&graph);
+    }
+
+    #[test]
+    fn fasta_record() {
+        let mut out: Vec<u8> = Vec::new();
+        write_fasta_record(&mut out, b"seq", b"ACGTACGT", 3).unwrap();
+        assert_eq!(out.as_bstr(), b">seq\nACG\nTAC\nGT\n".as_bstr());
+    }
+}
@@ -0,0 +1,245 @@
+/*!
+
+Flattening a graph into a single linear sequence, like `odgi flatten`.
+
+The pseudo-reference is the concatenation of the forward sequences of
+all nodes, in order of node ID, so sort the graph first (e.g. with
+`apply_ordering`) to control the layout. Each step of an embedded path
+is then a range on the pseudo-reference, which is written as a BED
+record with the path name as its name, the strand of the step, and
+the rank of the step on its path as an extra column.
+
+*/
+
+use crate::{
+    handle::{Handle, NodeId},
+    handlegraph::*,
+    pathhandlegraph::{path::PathStep, *},
+    util::bed::BedRecord,
+};
+
+use bstr::ByteSlice;
+
+use fnv::FnvHashMap;
+
+use std::io::{self, Write};
+
+use super::fasta::write_wrapped;
+
+/// The layout of the nodes of a graph on its flattened sequence.
+#[derive(Debug, Clone, Default)]
+pub struct Flattened {
+    nodes: Vec<Handle>,
+    offsets: FnvHashMap<NodeId, usize>,
+    len: usize,
+}
+
+impl Flattened {
+    /// Lay out the nodes of `graph` in order of node ID.
+    pub fn new<G: HandleGraphRef>(graph: G) -> Self {
+        let mut nodes = graph.handles().collect::<Vec<_>>();
+        nodes.sort();
+
+        let mut offsets = FnvHashMap::default();
+        let mut len = 0;
+        for &handle in nodes.iter() {
+            offsets.insert(handle.id(), len);
+            len += graph.node_len(handle);
+        }
+
+        Flattened {
+            nodes,
+            offsets,
+            len,
+        }
+    }
+
+    /// The length of the flattened sequence.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The offset of the first base of node `id` in the flattened
+    /// sequence.
+    pub fn node_offset(&self, id: NodeId) -> Option<usize> {
+        self.offsets.get(&id).copied()
+    }
+
+    /// The flattened sequence.
+    pub fn sequence<G: HandleGraphRef>(&self, graph: G) -> Vec<u8> {
+        let mut seq = Vec::with_capacity(self.len);
+        for &handle in self.nodes.iter() {
+            seq.extend(graph.sequence(handle));
+        }
+        seq
+    }
+
+    /// The BED records of the steps of the path `id`, in order, on
+    /// the flattened sequence named `name`, if the path exists. The
+    /// fields of each record are the path name, a score of 0, the
+    /// strand, and the zero-based rank of the step.
+    pub fn path_records<G>(
+        &self,
+        graph: G,
+        name: &[u8],
+        id: PathId,
+    ) -> Option<Vec<BedRecord>>
+    where
+        G: HandleGraphRef + GraphPathsSteps + GraphPathNames,
+    {
+        let path_name = graph.get_path_name_vec(id)?;
+        let steps = graph.path_steps(id)?;
+
+        let records = steps
+            .enumerate()
+            .map(|(rank, step)| {
+                let handle = step.handle();
+                let start = self.offsets[&handle.id()];
+                let end = start + graph.node_len(handle);
+
+                let mut record = BedRecord::new(name, start, end);
+                record.fields.push(path_name.clone());
+                record.set_strand(handle.is_reverse());
+                record.fields.push(rank.to_string().into_bytes());
+                record
+            })
+            .collect();
+
+        Some(records)
+    }
+
+    /// Writes the flattened sequence as a FASTA record named `name`,
+    /// wrapped at `line_width` bases per line, or on a single line if
+    /// `line_width` is zero.
+    pub fn write_fasta<G, W>(
+        &self,
+        graph: G,
+        name: &[u8],
+        line_width: usize,
+        out: &mut W,
+    ) -> io::Result<()>
+    where
+        G: HandleGraphRef,
+        W: Write,
+    {
+        writeln!(out, ">{}", name.as_bstr())?;
+        let seq = self.nodes.iter().flat_map(|&handle| graph.sequence(handle));
+        write_wrapped(out, seq, line_width)
+    }
+
+    /// Writes the BED records of all steps of the paths in `paths`,
+    /// on the flattened sequence named `name`, with a header line.
+    /// Paths that don't exist in the graph are skipped.
+    ///
+    /// To write all paths, use e.g. `graph.path_ids()` as `paths`.
+    pub fn write_bed<G, W, I>(
+        &self,
+        graph: G,
+        name: &[u8],
+        paths: I,
+        out: &mut W,
+    ) -> io::Result<()>
+    where
+        G: HandleGraphRef + GraphPathsSteps + GraphPathNames,
+        W: Write,
+        I: IntoIterator<Item = PathId>,
+    {
+        writeln!(
+            out,
+            "#name\tstart\tend\tpath.name\tscore\tstrand\tstep.rank"
+        )?;
+
+        for id in paths {
+            let records = self.path_records(graph, name, id);
+            for record in records.into_iter().flatten() {
+                record.write(out)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::handle::Edge;
+    use crate::hashgraph::HashGraph;
+    use crate::mutablehandlegraph::*;
+    use crate::packedgraph::PackedGraph;
+    use crate::util::test::{add_path, hnd, small_graph};
+
+    fn build_graph<G>() -> G
+    where
+        G: Default + AdditiveHandleGraph + MutableGraphPaths,
+    {
+        let mut graph: G = small_graph();
+        let (n1, n2, n3) = (hnd(1), hnd(2), hnd(3));
+
+        graph.create_edge(Edge(n1, n3.flip()));
+
+        // nodes are flattened in order of ID, not of creation
+        graph.create_handle(b"T", 5u64);
+        graph.create_handle(b"G", 4u64);
+
+        add_path(&mut graph, b"fwd", &[n1, n2, n3]);
+        add_path(&mut graph, b"alt", &[n3, n1.flip()]);
+
+        graph
+    }
+
+    fn check_flatten<G>(graph: G)
+    where
+        G: HandleGraphRef + GraphPathsSteps + GraphPathNames + IntoPathIds,
+    {
+        let flat = Flattened::new(graph);
+        assert_eq!(flat.len(), 11);
+        assert_eq!(flat.sequence(graph), b"AAACGGTCAGT");
+        assert_eq!(flat.node_offset(NodeId::from(3)), Some(6));
+        assert_eq!(flat.node_offset(NodeId::from(5)), Some(10));
+        assert_eq!(flat.node_offset(NodeId::from(6)), None);
+
+        let mut fasta: Vec<u8> = Vec::new();
+        flat.write_fasta(graph, b"flat", 4, &mut fasta).unwrap();
+        assert_eq!(fasta.as_bstr(), b">flat\nAAAC\nGGTC\nAGT\n".as_bstr());
+
+        let fwd = graph.get_path_id(b"fwd").unwrap();
+        let alt = graph.get_path_id(b"alt").unwrap();
+
+        let records = flat.path_records(graph, b"flat", alt).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[1].start, records[1].end), (0, 4));
+        assert_eq!(records[1].name(), Some(b"alt".as_ref()));
+        assert_eq!(records[1].is_reverse(), Some(true));
+
+        let mut bed: Vec<u8> = Vec::new();
+        flat.write_bed(graph, b"flat", vec![fwd, alt], &mut bed)
+            .unwrap();
+
+        let expected: &[u8] =
+            b"#name\tstart\tend\tpath.name\tscore\tstrand\tstep.rank\n\
+                                flat\t0\t4\tfwd\t0\t+\t0\n\
+                                flat\t4\t6\tfwd\t0\t+\t1\n\
+                                flat\t6\t9\tfwd\t0\t+\t2\n\
+                                flat\t6\t9\talt\t0\t+\t0\n\
+                                flat\t0\t4\talt\t0\t-\t1\n";
+        assert_eq!(bed.as_bstr(), expected.as_bstr());
+    }
+
+    #[test]
+    fn packedgraph_flatten() {
+        let graph: PackedGraph = build_graph();
+        check_flatten(&graph);
+    }
+
+    #[test]
+    fn hashgraph_flatten() {
+        let graph: HashGraph = build_graph();
+        check_flatten(&graph);
+    }
+}
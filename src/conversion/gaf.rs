@@ -0,0 +1,681 @@
+/*!
+
+Reading and writing alignments to the graph in the GAF format, as
+produced by e.g. GraphAligner and minigraph.
+
+GAF records are parsed with the `gfa` crate, after which the oriented
+node walk (`>12<13>14`) is resolved to handles and validated against
+the graph, and the start and end of the alignment on the walk are
+projected to positions on the handles. Only walks over node IDs are
+supported, not rGFA stable coordinates.
+
+Resolved alignments can be injected into the graph as paths, or
+summarized as node coverage. In the other direction, paths and
+intervals on paths in a `PackedGraph` can be written as GAF records,
+using a `PathPositionMap` to find the steps that cover them.
+
+*/
+
+use crate::{
+    handle::{Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+};
+
+use crate::algorithms::inject::{covering_steps, NodeCuts, PathInterval};
+use crate::packedgraph::PackedGraph;
+use crate::path_position::PathPositionMap;
+
+use gfa::{
+    gafpaf::{parse_gaf, GAFPath, GAFStep, GAF},
+    gfa::Orientation,
+    optfields::{OptField, OptFieldVal, OptionalFields},
+};
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+use bstr::ByteSlice;
+
+use std::io::{self, BufRead, Write};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+/// The reasons a GAF record can fail to be read or resolved against
+/// a graph.
+#[derive(Debug)]
+pub enum GafError {
+    Io(io::Error),
+    /// The record on the given line, counting from 1, is not valid
+    /// GAF.
+    Parse(usize),
+    /// The path is given in rGFA stable coordinates.
+    StableCoordinates,
+    /// A segment name in the walk is not a valid node ID.
+    InvalidSegment(Vec<u8>),
+    MissingNode(NodeId),
+    MissingEdge(Handle, Handle),
+    /// The path length in the record doesn't match the total length
+    /// of the nodes in the walk.
+    PathLength {
+        expected: usize,
+        found: usize,
+    },
+    /// The alignment range is empty, or outside the walk.
+    InvalidRange(usize, usize),
+}
+
+impl std::fmt::Display for GafError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GafError::Io(err) => write!(f, "{}", err),
+            GafError::Parse(line) => {
+                write!(f, "invalid GAF record on line {}", line)
+            }
+            GafError::StableCoordinates => {
+                write!(f, "rGFA stable coordinates are not supported")
+            }
+            GafError::InvalidSegment(name) => {
+                write!(f, "invalid segment name {}", name.as_bstr())
+            }
+            GafError::MissingNode(id) => {
+                write!(f, "node {} is not in the graph", id.0)
+            }
+            GafError::MissingEdge(from, to) => write!(
+                f,
+                "missing edge {}{} -> {}{}",
+                from.id().0,
+                if from.is_reverse() { "-" } else { "+" },
+                to.id().0,
+                if to.is_reverse() { "-" } else { "+" },
+            ),
+            GafError::PathLength { expected, found } => write!(
+                f,
+                "path length {} doesn't match walk length {}",
+                expected, found
+            ),
+            GafError::InvalidRange(start, end) => {
+                write!(f, "invalid path range {}-{}", start, end)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GafError {}
+
+impl From<io::Error> for GafError {
+    fn from(err: io::Error) -> Self {
+        GafError::Io(err)
+    }
+}
+
+/// A GAF record resolved against a graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GafAlignment {
+    pub record: GAF<OptionalFields>,
+    /// The handles of the walk, in order.
+    pub walk: Vec<Handle>,
+    /// The handle containing the first aligned base, and the offset
+    /// of that base on the handle.
+    pub start: (Handle, usize),
+    /// The handle containing the last aligned base, and the offset
+    /// one past that base on the handle.
+    pub end: (Handle, usize),
+}
+
+impl GafAlignment {
+    /// Resolve a parsed GAF record against `graph`, checking that the
+    /// nodes and edges in the walk exist, and that the path length
+    /// and range are consistent with the node lengths.
+    pub fn from_record<G: HandleGraphRef>(
+        graph: G,
+        record: GAF<OptionalFields>,
+    ) -> Result<Self, GafError> {
+        let walk = resolve_walk(graph, &record.path)?;
+
+        let walk_len: usize = walk.iter().map(|&h| graph.node_len(h)).sum();
+        if walk_len != record.path_len {
+            return Err(GafError::PathLength {
+                expected: record.path_len,
+                found: walk_len,
+            });
+        }
+
+        let (start, end) = record.path_range;
+        if start >= end || end > walk_len {
+            return Err(GafError::InvalidRange(start, end));
+        }
+
+        let invalid = || GafError::InvalidRange(start, end);
+        let start_pos =
+            project_position(graph, &walk, start, false).ok_or_else(invalid)?;
+        let end_pos =
+            project_position(graph, &walk, end, true).ok_or_else(invalid)?;
+
+        Ok(Self {
+            record,
+            walk,
+            start: start_pos,
+            end: end_pos,
+        })
+    }
+
+    /// Parse a single GAF line and resolve it against `graph`.
+    /// `line_num` is only used in the error if the line can't be
+    /// parsed.
+    pub fn parse_line<G: HandleGraphRef>(
+        graph: G,
+        line: &[u8],
+        line_num: usize,
+    ) -> Result<Self, GafError> {
+        let record: GAF<OptionalFields> =
+            parse_gaf(line.split_str("\t")).ok_or(GafError::Parse(line_num))?;
+        Self::from_record(graph, record)
+    }
+
+    /// The name of the aligned sequence.
+    #[inline]
+    pub fn name(&self) -> &[u8] {
+        &self.record.seq_name
+    }
+
+    /// The 0-based, end-exclusive range of the alignment on the walk.
+    #[inline]
+    pub fn walk_range(&self) -> (usize, usize) {
+        self.record.path_range
+    }
+}
+
+/// Resolve the oriented node walk of a GAF path to handles, and check
+/// that consecutive handles are connected by edges in `graph`.
+pub fn resolve_walk<G: HandleGraphRef>(
+    graph: G,
+    path: &GAFPath,
+) -> Result<Vec<Handle>, GafError> {
+    let steps = match path {
+        GAFPath::StableId(_) => return Err(GafError::StableCoordinates),
+        GAFPath::OrientIntv(steps) => steps,
+    };
+
+    let mut walk: Vec<Handle> = Vec::with_capacity(steps.len());
+
+    for step in steps {
+        let (orient, name) = match step {
+            GAFStep::SegId(orient, name) => (*orient, name),
+            GAFStep::StableIntv(..) => return Err(GafError::StableCoordinates),
+        };
+
+        let id = name
+            .to_str()
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|&id| id > 0)
+            .ok_or_else(|| GafError::InvalidSegment(name.clone()))?;
+        let id = NodeId::from(id);
+
+        if !graph.has_node(id) {
+            return Err(GafError::MissingNode(id));
+        }
+
+        let handle = Handle::new(id, orient);
+
+        if let Some(&prev) = walk.last() {
+            if !graph.has_edge(prev, handle) {
+                return Err(GafError::MissingEdge(prev, handle));
+            }
+        }
+
+        walk.push(handle);
+    }
+
+    Ok(walk)
+}
+
+/// Find the handle in `walk` containing the base at `pos`, and the
+/// offset of the base on the handle. If `is_end` is true, `pos` is
+/// treated as an exclusive end, and is projected to the handle
+/// containing the base before it.
+fn project_position<G: HandleGraphRef>(
+    graph: G,
+    walk: &[Handle],
+    pos: usize,
+    is_end: bool,
+) -> Option<(Handle, usize)> {
+    let mut node_start = 0;
+    for &handle in walk {
+        let node_end = node_start + graph.node_len(handle);
+        let contains = if is_end {
+            pos > node_start && pos <= node_end
+        } else {
+            pos >= node_start && pos < node_end
+        };
+        if contains {
+            return Some((handle, pos - node_start));
+        }
+        node_start = node_end;
+    }
+    None
+}
+
+/// Read and resolve the GAF records from `reader`, skipping empty
+/// lines and comments. The records are resolved lazily, so invalid
+/// records can be either skipped or treated as fatal by the caller.
+pub fn read_gaf<G, R>(
+    graph: G,
+    reader: R,
+) -> impl Iterator<Item = Result<GafAlignment, GafError>>
+where
+    G: HandleGraphRef,
+    R: BufRead,
+{
+    reader
+        .split(b'\n')
+        .enumerate()
+        .filter_map(move |(ix, line)| {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => return Some(Err(GafError::Io(err))),
+            };
+            let line = line.trim_end_with(|c| c == '\r');
+            if line.is_empty() || line.starts_with(b"#") {
+                return None;
+            }
+            Some(GafAlignment::parse_line(graph, line, ix + 1))
+        })
+}
+
+/// Inject each alignment as a new path, named after the aligned
+/// sequence, covering exactly the aligned part of its walk.
+///
+/// The nodes containing the start and end of each alignment are
+/// divided as needed, and each node is divided at most once, so the
+/// alignments must all have been resolved against `graph` as it is
+/// before the call. The paths already in the graph are updated by
+/// `divide_handle`, and so still spell the same sequences.
+///
+/// Returns the ID of each new path, in the same order as the
+/// alignments; an entry is `None` if a path with that name already
+/// exists, or if an earlier alignment has the same name.
+pub fn inject_alignments<G>(
+    graph: &mut G,
+    alignments: &[GafAlignment],
+) -> Vec<Option<PathId>>
+where
+    G: MutableHandles + MutableGraphPaths,
+    for<'a> &'a G: HandleGraphRef + GraphPathNames,
+{
+    // alignments whose names are already taken, either by an existing
+    // path or an earlier alignment, are skipped before any nodes are
+    // divided, so that they leave the graph unchanged
+    let mut names = FnvHashSet::default();
+    let accepted = alignments
+        .iter()
+        .map(|aln| {
+            (&*graph).get_path_id(aln.name()).is_none()
+                && names.insert(aln.name())
+        })
+        .collect::<Vec<_>>();
+
+    let mut cuts = NodeCuts::default();
+    for (aln, _) in alignments.iter().zip(&accepted).filter(|(_, a)| **a) {
+        for &(handle, offset) in [aln.start, aln.end].iter() {
+            cuts.add(&*graph, handle, offset);
+        }
+    }
+
+    let divided = cuts.divide(graph);
+
+    alignments
+        .iter()
+        .zip(accepted)
+        .map(|(aln, accepted)| {
+            if !accepted {
+                return None;
+            }
+            let path_id = graph.create_path(aln.name(), false)?;
+            let walk = aln.walk.iter().copied();
+            divided.append_range(graph, path_id, walk, 0, aln.walk_range());
+            Some(path_id)
+        })
+        .collect()
+}
+
+/// The number of aligned bases on each node, summed over all
+/// alignments. Dividing by the node length gives the mean depth of
+/// coverage of a node; nodes without any aligned bases are left out.
+pub fn alignment_coverage<G: HandleGraphRef>(
+    graph: G,
+    alignments: &[GafAlignment],
+) -> FnvHashMap<NodeId, usize> {
+    let mut coverage: FnvHashMap<NodeId, usize> = FnvHashMap::default();
+
+    for aln in alignments {
+        let (start, end) = aln.walk_range();
+        let mut pos = 0;
+
+        for &handle in aln.walk.iter() {
+            let node_end = pos + graph.node_len(handle);
+            let overlap_start = pos.max(start);
+            let overlap_end = node_end.min(end);
+            if overlap_start < overlap_end {
+                *coverage.entry(handle.id()).or_default() +=
+                    overlap_end - overlap_start;
+            }
+            pos = node_end;
+        }
+    }
+
+    coverage
+}
+
+/// Build a GAF record for an interval on a path, aligned end to end
+/// to the walk of the steps that cover the interval, and named after
+/// the interval. The record has a `cg` CIGAR tag consisting of a
+/// single match, and a mapping quality of 255, i.e. missing.
+///
+/// Returns `None` if the interval is empty, or can't be found on its
+/// source path.
+pub fn interval_gaf_record(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    interval: &PathInterval,
+) -> Option<GAF<OptionalFields>> {
+    let steps = covering_steps(graph, index, interval)?;
+
+    let len = interval.end - interval.start;
+
+    let path = steps
+        .handles
+        .iter()
+        .map(|h| {
+            let orient = if h.is_reverse() {
+                Orientation::Backward
+            } else {
+                Orientation::Forward
+            };
+            let name = h.id().0.to_string().into_bytes();
+            GAFStep::SegId(orient, name)
+        })
+        .collect();
+
+    let path_len: usize =
+        steps.handles.iter().map(|&h| graph.node_len(h)).sum();
+    let path_start = interval.start - steps.first_step_pos;
+
+    let cigar = format!("{}M", len).into_bytes();
+
+    Some(GAF {
+        seq_name: interval.name.clone(),
+        seq_len: len,
+        seq_range: (0, len),
+        strand: Orientation::Forward,
+        path: GAFPath::OrientIntv(path),
+        path_len,
+        path_range: (path_start, path_start + len),
+        residue_matches: len,
+        block_length: len,
+        quality: 255,
+        optional: vec![OptField::new(b"cg", OptFieldVal::Z(cigar))],
+    })
+}
+
+/// Write each of the provided paths as a GAF record of the path
+/// aligned to itself, in order. Empty paths, and paths that don't
+/// exist in the graph, are skipped.
+///
+/// `index` must be up to date with the paths in `graph`.
+pub fn write_paths_gaf<W, I>(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    paths: I,
+    out: &mut W,
+) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = PathId>,
+{
+    for source in paths {
+        let name = match graph.get_path_name(source) {
+            Some(name) => name.collect::<Vec<_>>(),
+            None => continue,
+        };
+
+        // the positions in the index are 1-based
+        let end = match index.path_base_len(source) {
+            Some(base_len) => base_len - 1,
+            None => continue,
+        };
+
+        let interval = PathInterval {
+            source,
+            start: 0,
+            end,
+            name,
+        };
+
+        if let Some(record) = interval_gaf_record(graph, index, &interval) {
+            writeln!(out, "{}", record)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the intervals as GAF records, as produced by
+/// `interval_gaf_record`. Intervals that can't be found on their
+/// source paths are skipped.
+pub fn write_intervals_gaf<W: Write>(
+    graph: &PackedGraph,
+    index: &PathPositionMap,
+    intervals: &[PathInterval],
+    out: &mut W,
+) -> io::Result<()> {
+    for interval in intervals {
+        if let Some(record) = interval_gaf_record(graph, index, interval) {
+            writeln!(out, "{}", record)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conversion::fasta::path_sequence;
+    use crate::handle::Edge;
+    use crate::hashgraph::HashGraph;
+    use crate::util::test::{add_path, hnd, small_graph};
+
+    // 1: AAAC, 2: GG, 3: TCA, 4: TT
+    //
+    // 1+ -> 2+ -> 3+
+    // 1+ -> 4+ -> 3+
+    fn build_graph<G>() -> G
+    where
+        G: Default + AdditiveHandleGraph + MutableGraphPaths,
+    {
+        let mut graph: G = small_graph();
+
+        let n4 = graph.append_handle(b"TT");
+        graph.create_edge(Edge(hnd(1), n4));
+        graph.create_edge(Edge(n4, hnd(3)));
+
+        add_path(&mut graph, b"ref", &[hnd(1), hnd(2), hnd(3)]);
+
+        graph
+    }
+
+    const GAF_IN: &[u8] = b"# comment
+read1\t6\t0\t6\t+\t>1>2>3\t9\t2\t8\t6\t6\t60\tcg:Z:6M
+read2\t5\t0\t5\t+\t<3<4\t5\t0\t5\t5\t5\t60\tcg:Z:5M
+
+read3\t3\t0\t3\t+\t>2>3\t5\t1\t4\t3\t3\t60
+";
+
+    fn check_alignments<G>(graph: &mut G)
+    where
+        G: MutableHandles + MutableGraphPaths,
+        for<'a> &'a G: HandleGraphRef + GraphPathsSteps + GraphPathNames,
+    {
+        let alignments = read_gaf(&*graph, GAF_IN)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(alignments.len(), 3);
+
+        let h = |id: u64, rev: bool| Handle::pack(id, rev);
+
+        assert_eq!(
+            alignments[0].walk,
+            vec![h(1, false), h(2, false), h(3, false)]
+        );
+        assert_eq!(alignments[0].start, (h(1, false), 2));
+        assert_eq!(alignments[0].end, (h(3, false), 2));
+
+        assert_eq!(alignments[1].walk, vec![h(3, true), h(4, true)]);
+        assert_eq!(alignments[1].start, (h(3, true), 0));
+        assert_eq!(alignments[1].end, (h(4, true), 2));
+
+        assert_eq!(alignments[2].start, (h(2, false), 1));
+        assert_eq!(alignments[2].end, (h(3, false), 2));
+
+        let coverage = alignment_coverage(&*graph, &alignments);
+        let expected: FnvHashMap<NodeId, usize> =
+            [(1, 2), (2, 3), (3, 7), (4, 2)]
+                .iter()
+                .map(|&(id, cov)| (NodeId::from(id), cov))
+                .collect();
+        assert_eq!(coverage, expected);
+
+        let paths = inject_alignments(graph, &alignments);
+        let paths = paths.into_iter().map(Option::unwrap).collect::<Vec<_>>();
+
+        assert_eq!(path_sequence(&*graph, paths[0]).unwrap(), b"ACGGTC");
+        assert_eq!(path_sequence(&*graph, paths[1]).unwrap(), b"TGAAA");
+        assert_eq!(path_sequence(&*graph, paths[2]).unwrap(), b"GTC");
+
+        let ref_path = graph.get_path_id(b"ref").unwrap();
+        assert_eq!(path_sequence(&*graph, ref_path).unwrap(), b"AAACGGTCA");
+
+        // the path names are now taken
+        let paths = inject_alignments(graph, &alignments[..1]);
+        assert_eq!(paths, vec![None]);
+    }
+
+    #[test]
+    fn gaf_packedgraph() {
+        let mut graph: PackedGraph = build_graph();
+        check_alignments(&mut graph);
+    }
+
+    #[test]
+    fn gaf_hashgraph() {
+        let mut graph: HashGraph = build_graph();
+        check_alignments(&mut graph);
+    }
+
+    #[test]
+    fn gaf_inject_name_collisions() {
+        let mut graph: HashGraph = build_graph();
+        add_path(&mut graph, b"read1", &[]);
+
+        let mut alignments = read_gaf(&graph, GAF_IN)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        alignments.push(alignments[2].clone());
+
+        let node_count = graph.node_count();
+
+        // read1 would cut nodes 1 and 3, and read2 doesn't need any
+        // cuts, so only the cuts at the ends of read3 are made
+        let paths = inject_alignments(&mut graph, &alignments);
+        assert_eq!(paths[0], None);
+        assert!(paths[1].is_some());
+        assert!(paths[2].is_some());
+        assert_eq!(paths[3], None);
+
+        assert_eq!(graph.node_count(), node_count + 2);
+        assert_eq!(path_sequence(&graph, paths[2].unwrap()).unwrap(), b"GTC");
+    }
+
+    #[test]
+    fn gaf_invalid_records() {
+        let graph: HashGraph = build_graph();
+
+        let parse = |line: &[u8]| GafAlignment::parse_line(&graph, line, 1);
+
+        let missing_edge = b"r\t5\t0\t5\t+\t>2>4\t4\t0\t4\t4\t4\t60";
+        assert!(matches!(
+            parse(missing_edge),
+            Err(GafError::MissingEdge(..))
+        ));
+
+        let missing_node = b"r\t5\t0\t5\t+\t>1>9\t4\t0\t4\t4\t4\t60";
+        assert!(matches!(parse(missing_node), Err(GafError::MissingNode(_))));
+
+        let stable = b"r\t5\t0\t5\t+\tchr1\t4\t0\t4\t4\t4\t60";
+        assert!(matches!(parse(stable), Err(GafError::StableCoordinates)));
+
+        let bad_len = b"r\t5\t0\t5\t+\t>1>2\t7\t0\t4\t4\t4\t60";
+        assert!(matches!(
+            parse(bad_len),
+            Err(GafError::PathLength {
+                expected: 7,
+                found: 6
+            })
+        ));
+
+        let bad_range = b"r\t5\t0\t5\t+\t>1>2\t6\t3\t8\t4\t4\t60";
+        assert!(matches!(
+            parse(bad_range),
+            Err(GafError::InvalidRange(3, 8))
+        ));
+
+        assert!(matches!(parse(b"r\t5\t0"), Err(GafError::Parse(1))));
+    }
+
+    #[test]
+    fn gaf_write_paths() {
+        let mut graph: PackedGraph = build_graph();
+
+        let alt = graph.create_path(b"alt", false).unwrap();
+        for &id in [3u64, 4, 1].iter() {
+            graph.path_append_step(alt, Handle::pack(id, true));
+        }
+
+        let index = PathPositionMap::index_paths(&graph);
+
+        let ref_path = graph.get_path_id(b"ref").unwrap();
+
+        let mut out: Vec<u8> = Vec::new();
+        write_paths_gaf(&graph, &index, vec![ref_path, alt], &mut out).unwrap();
+
+        let expected: &[u8] = b"\
+ref\t9\t0\t9\t+\t>1>2>3\t9\t0\t9\t9\t9\t255\tcg:Z:9M
+alt\t9\t0\t9\t+\t<3<4<1\t9\t0\t9\t9\t9\t255\tcg:Z:9M
+";
+        assert_eq!(out.as_bstr(), expected.as_bstr());
+
+        let intervals = vec![PathInterval {
+            source: ref_path,
+            start: 3,
+            end: 7,
+            name: b"sub".to_vec(),
+        }];
+
+        let mut out: Vec<u8> = Vec::new();
+        write_intervals_gaf(&graph, &index, &intervals, &mut out).unwrap();
+        assert_eq!(
+            out.as_bstr(),
+            b"sub\t4\t0\t4\t+\t>1>2>3\t9\t3\t7\t4\t4\t255\tcg:Z:4M\n".as_bstr()
+        );
+
+        // written records can be read back
+        let alignments = read_gaf(&graph, out.as_slice())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(alignments[0].start, (Handle::pack(1, false), 3));
+        assert_eq!(alignments[0].end, (Handle::pack(3, false), 1));
+    }
+}
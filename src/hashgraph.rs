@@ -4,6 +4,8 @@ A handlegraph implementation using `HashMap` to represent the graph
 topology and nodes, and each path as a `Vec` of nodes.
 */
 
+use fnv::FnvHashMap;
+
 use rayon::prelude::*;
 
 use crate::{
@@ -11,8 +13,9 @@ use crate::{
     handlegraph::*,
     mutablehandlegraph::*,
     pathhandlegraph::{
-        GraphPathNames, GraphPaths, GraphPathsRef, IntoNodeOccurrences,
-        IntoPathIds, MutableGraphPaths, PathId, PathSequences,
+        GraphPathNames, GraphPaths, GraphPathsRef, GraphPathsSteps,
+        IntoNodeOccurrences, IntoPathIds, MutableGraphPaths, PathId,
+        PathSequences, PathSteps,
     },
     util::dna,
 };
@@ -108,6 +111,15 @@ impl<'a> IntoEdges for &'a HashGraph {
     }
 }
 
+impl<'a> IntoEdgesPar for &'a HashGraph {
+    type EdgesPar = rayon::iter::IterBridge<EdgesIter<&'a HashGraph>>;
+
+    #[inline]
+    fn edges_par(self) -> Self::EdgesPar {
+        self.edges().par_bridge()
+    }
+}
+
 impl<'a> IntoNeighbors for &'a HashGraph {
     type Neighbors = NeighborIter<'a, std::slice::Iter<'a, Handle>>;
 
@@ -128,9 +140,11 @@ impl<'a> IntoNeighbors for &'a HashGraph {
     #[inline]
     fn degree(self, handle: Handle, dir: Direction) -> usize {
         let n = self.get_node_unchecked(&handle.id());
-        match dir {
-            Direction::Right => n.right_edges.len(),
-            Direction::Left => n.left_edges.len(),
+        match (dir, handle.is_reverse()) {
+            (Direction::Left, true) => n.right_edges.len(),
+            (Direction::Left, false) => n.left_edges.len(),
+            (Direction::Right, true) => n.left_edges.len(),
+            (Direction::Right, false) => n.right_edges.len(),
         }
     }
 }
@@ -237,30 +251,172 @@ impl AdditiveHandleGraph for HashGraph {
     }
 }
 
+impl SubtractiveHandleGraph for HashGraph {
+    fn remove_handle(&mut self, handle: Handle) -> bool {
+        let id = handle.id();
+
+        let paths = match self.graph.get(&id) {
+            Some(node) => node.occurrences.keys().copied().collect::<Vec<_>>(),
+            None => return false,
+        };
+
+        // Paths that cover the handle are destroyed, as they would
+        // otherwise step on a node that doesn't exist
+        for path in paths {
+            self.destroy_path(path);
+        }
+
+        let handle = handle.forward();
+        let rights =
+            self.neighbors(handle, Direction::Right).collect::<Vec<_>>();
+        let lefts = self.neighbors(handle, Direction::Left).collect::<Vec<_>>();
+
+        for next in rights {
+            self.remove_edge(Edge(handle, next));
+        }
+        for prev in lefts {
+            self.remove_edge(Edge(prev, handle));
+        }
+
+        self.graph.remove(&id);
+
+        if id == self.min_id || id == self.max_id {
+            self.min_id = self
+                .graph
+                .keys()
+                .copied()
+                .min()
+                .unwrap_or_else(|| NodeId::from(u64::MAX));
+            self.max_id = self
+                .graph
+                .keys()
+                .copied()
+                .max()
+                .unwrap_or_else(|| NodeId::from(0));
+        }
+
+        true
+    }
+
+    fn remove_edge(&mut self, Edge(left, right): Edge) -> bool {
+        let left_node = match self.graph.get_mut(&left.id()) {
+            Some(node) => node,
+            None => return false,
+        };
+        let left_edges = if left.is_reverse() {
+            &mut left_node.left_edges
+        } else {
+            &mut left_node.right_edges
+        };
+        let existed = match left_edges.iter().position(|&h| h == right) {
+            Some(ix) => {
+                left_edges.remove(ix);
+                true
+            }
+            None => false,
+        };
+
+        if existed && left != right.flip() {
+            if let Some(right_node) = self.graph.get_mut(&right.id()) {
+                let right_edges = if right.is_reverse() {
+                    &mut right_node.right_edges
+                } else {
+                    &mut right_node.left_edges
+                };
+                right_edges.retain(|&h| h != left.flip());
+            }
+        }
+
+        existed
+    }
+
+    fn clear_graph(&mut self) {
+        *self = HashGraph::default();
+    }
+}
+
+impl TransformNodeIds for HashGraph {
+    fn transform_node_ids<F>(&mut self, transform: F)
+    where
+        F: Fn(NodeId) -> NodeId + Copy + Send + Sync,
+    {
+        self.transform_node_ids_mut(transform);
+    }
+
+    fn transform_node_ids_mut<F>(&mut self, mut transform: F)
+    where
+        F: FnMut(NodeId) -> NodeId,
+    {
+        // `transform` is called once for each node, in order of ID
+        let mut ids = self.graph.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+
+        let new_ids: FnvHashMap<NodeId, NodeId> =
+            ids.into_iter().map(|id| (id, transform(id))).collect();
+
+        let update = |handle: &mut Handle| {
+            *handle = Handle::pack(new_ids[&handle.id()], handle.is_reverse());
+        };
+
+        let graph = std::mem::take(&mut self.graph);
+        self.graph = graph
+            .into_iter()
+            .map(|(id, mut node)| {
+                node.left_edges.iter_mut().for_each(update);
+                node.right_edges.iter_mut().for_each(update);
+                (new_ids[&id], node)
+            })
+            .collect();
+
+        for path in self.paths.values_mut() {
+            path.nodes.iter_mut().for_each(update);
+        }
+
+        let default = HashGraph::default();
+        self.min_id = new_ids.values().copied().min().unwrap_or(default.min_id);
+        self.max_id = new_ids.values().copied().max().unwrap_or(default.max_id);
+    }
+
+    fn apply_ordering(&mut self, order: &[Handle]) {
+        assert!(order.len() == self.node_count());
+
+        let mut ids = self.graph.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+
+        let rank: FnvHashMap<NodeId, usize> = ids
+            .into_iter()
+            .enumerate()
+            .map(|(ix, id)| (id, ix))
+            .collect();
+
+        self.transform_node_ids_mut(|node| order[rank[&node]].id());
+    }
+}
+
 impl MutableHandles for HashGraph {
     fn divide_handle(
         &mut self,
         handle: Handle,
         offsets: &[usize],
     ) -> Vec<Handle> {
-        let mut result = vec![handle];
-        let node_len = self.node_len(handle);
-        let sequence = self.sequence_vec(handle);
-
         let fwd_handle = handle.forward();
 
-        let mut offsets = offsets.to_vec();
-
-        // Push the node length as a last offset to make constructing
-        // the ranges nicer
-        offsets.push(node_len);
+        let mut result = vec![fwd_handle];
+        let node_len = self.node_len(handle);
+        let sequence = self.sequence_vec(fwd_handle);
 
-        let fwd_offsets: Vec<usize> = if handle.is_reverse() {
-            offsets.iter().map(|o| node_len - o).collect()
+        // The offsets are in terms of the orientation of `handle`,
+        // but the node is divided in its forward orientation
+        let mut fwd_offsets: Vec<usize> = if handle.is_reverse() {
+            offsets.iter().rev().map(|o| node_len - o).collect()
         } else {
-            offsets
+            offsets.to_vec()
         };
 
+        // Push the node length as a last offset to make constructing
+        // the ranges nicer
+        fwd_offsets.push(node_len);
+
         // staggered zip of the offsets with themselves to make the ranges
         let ranges: Vec<_> = fwd_offsets
             .iter()
@@ -284,6 +440,15 @@ impl MutableHandles for HashGraph {
             &mut self.get_node_mut(&handle.id()).unwrap().right_edges,
         );
 
+        // a reversing self-edge on the right-hand side now connects
+        // the last segment to itself
+        let last_handle = *result.last().unwrap();
+        for right in orig_rights.iter_mut() {
+            if *right == fwd_handle.flip() {
+                *right = last_handle.flip();
+            }
+        }
+
         let new_rights = &mut self
             .get_node_mut(&result.last().unwrap().id())
             .unwrap()
@@ -295,7 +460,8 @@ impl MutableHandles for HashGraph {
         let orig_node = &mut self.get_node_mut(&handle.id()).unwrap();
         orig_node.sequence = orig_node.sequence[0..fwd_offsets[0]].into();
 
-        // update backwards references
+        // update backwards references, including the left edges of
+        // the original node if it has a self-loop
         // first collect all the handles whose nodes we need to update
         let last_neighbors: Vec<_> = self
             .neighbors(*result.last().unwrap(), Direction::Right)
@@ -322,17 +488,36 @@ impl MutableHandles for HashGraph {
             self.create_edge(Edge(*this, *next));
         }
 
-        // update paths and path occurrences
-        let affected_paths: Vec<(_, _)> = self
+        // update paths and path occurrences; steps that traverse the
+        // node in reverse get the new handles flipped and in reverse
+        // order
+        let rev_result =
+            result.iter().rev().map(|h| h.flip()).collect::<Vec<_>>();
+
+        let affected_paths: Vec<_> = self
             .get_node_unchecked(&handle.id())
             .occurrences
-            .iter()
-            .map(|(k, v)| (*k, *v))
+            .keys()
+            .copied()
             .collect();
 
-        for (path_id, ix) in affected_paths.into_iter() {
-            let step = path::StepIx::Step(ix);
-            self.path_rewrite_segment(path_id, step, step, &result);
+        for path_id in affected_paths.into_iter() {
+            // a path can visit the node more than once, so find all
+            // the steps, and rewrite them back-to-front to keep the
+            // indices valid
+            let steps: Vec<(usize, bool)> = self.paths[&path_id]
+                .nodes
+                .iter()
+                .enumerate()
+                .filter(|(_, h)| h.id() == handle.id())
+                .map(|(ix, h)| (ix, h.is_reverse()))
+                .collect();
+
+            for &(ix, is_rev) in steps.iter().rev() {
+                let step = path::StepIx::Step(ix);
+                let segment = if is_rev { &rev_result } else { &result };
+                self.path_rewrite_segment(path_id, step, step, segment);
+            }
         }
 
         result
@@ -346,27 +531,30 @@ impl MutableHandles for HashGraph {
         let node = self.get_node_mut(&handle.id()).unwrap();
         node.sequence = dna::rev_comp(node.sequence.as_slice()).into();
 
-        let edges = {
+        // every edge that points to the node must be flipped; these
+        // are all in the edge lists of the node's neighbors, which
+        // includes the node itself if it has any self-edges
+        let mut neighbor_ids = {
             let node = self.get_node(&handle.id()).unwrap();
             node.left_edges
                 .iter()
                 .chain(node.right_edges.iter())
-                .copied()
+                .map(|h| h.id())
                 .collect::<Vec<_>>()
         };
+        neighbor_ids.sort();
+        neighbor_ids.dedup();
 
-        for target in edges {
-            let other = self.get_node_mut(&target.id()).unwrap();
-            let backward_edges = if target.is_reverse() {
-                other.right_edges.iter_mut()
-            } else {
-                other.left_edges.iter_mut()
-            };
+        for other_id in neighbor_ids {
+            let other = self.get_node_mut(&other_id).unwrap();
+            let backward_edges = other
+                .left_edges
+                .iter_mut()
+                .chain(other.right_edges.iter_mut());
 
             for backward_handle in backward_edges {
                 if backward_handle.id() == handle.id() {
                     *backward_handle = backward_handle.flip();
-                    break;
                 }
             }
         }
@@ -374,13 +562,22 @@ impl MutableHandles for HashGraph {
         let node = self.get_node_mut(&handle.id()).unwrap();
         std::mem::swap(&mut node.left_edges, &mut node.right_edges);
 
-        let occurrences = &self.graph.get(&handle.id()).unwrap().occurrences;
-        let paths = &mut self.paths;
-
-        for (path_id, index) in occurrences.iter() {
-            let path = paths.get_mut(&path_id).unwrap();
-            let step = path.nodes.get_mut(*index).unwrap();
-            *step = step.flip();
+        // a path can visit the node more than once, so flip all the
+        // steps on the node
+        let path_ids = self
+            .get_node_unchecked(&handle.id())
+            .occurrences
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+
+        for path_id in path_ids {
+            let path = self.paths.get_mut(&path_id).unwrap();
+            for step in path.nodes.iter_mut() {
+                if step.id() == handle.id() {
+                    *step = step.flip();
+                }
+            }
         }
 
         handle.flip()
@@ -505,6 +702,31 @@ impl<'a> GraphPathsRef for &'a HashGraph {
     }
 }
 
+impl<'a> GraphPathsSteps for &'a HashGraph {
+    type Step = path::Step;
+    type Steps = path::StepsIter<'a>;
+
+    fn path_steps(self, id: PathId) -> Option<Self::Steps> {
+        let path = self.paths.get(&id)?;
+        Some(path.steps())
+    }
+
+    fn path_steps_range(
+        self,
+        id: PathId,
+        from: Self::StepIx,
+        to: Self::StepIx,
+    ) -> Option<Self::Steps> {
+        let path = self.paths.get(&id)?;
+        if path.is_empty() {
+            return Some(path.steps());
+        }
+        let from = path.step_index_offset(from);
+        let to = path.step_index_offset(to).min(path.len() - 1);
+        Some(path::StepsIter::new_range(&path.nodes, from, to))
+    }
+}
+
 impl MutableGraphPaths for HashGraph {
     fn create_path(&mut self, name: &[u8], circular: bool) -> Option<PathId> {
         if self.path_id.contains_key(name) {
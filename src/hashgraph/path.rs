@@ -134,9 +134,16 @@ pub struct StepsIter<'a> {
 
 impl<'a> StepsIter<'a> {
     fn new(nodes: &'a [Handle]) -> Self {
-        let left = 0;
-        let right = nodes.len() - 1;
-        let finished = false;
+        Self::new_range(nodes, 0, nodes.len().saturating_sub(1))
+    }
+
+    /// Iterate through the steps from `left` to `right`, inclusive.
+    pub(super) fn new_range(
+        nodes: &'a [Handle],
+        left: usize,
+        right: usize,
+    ) -> Self {
+        let finished = nodes.is_empty() || left > right;
         Self {
             nodes,
             left,
@@ -158,9 +165,10 @@ impl<'a> Iterator for StepsIter<'a> {
         let handle = *self.nodes.get(self.left)?;
         let index = StepIx::Step(self.left);
 
-        self.left += 1;
-        if self.left > self.right {
+        if self.left == self.right {
             self.finished = true;
+        } else {
+            self.left += 1;
         }
 
         Some(Step(index, handle))
@@ -177,9 +185,10 @@ impl<'a> DoubleEndedIterator for StepsIter<'a> {
         let handle = *self.nodes.get(self.right)?;
         let index = StepIx::Step(self.right);
 
-        self.right -= 1;
-        if self.left > self.right {
+        if self.left == self.right {
             self.finished = true;
+        } else {
+            self.right -= 1;
         }
 
         Some(Step(index, handle))
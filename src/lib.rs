@@ -18,6 +18,10 @@ handlegraphs that implement them:
 * [`PackedGraph`](packedgraph::PackedGraph) uses packed integer
   vectors to greatly reduce memory usage.
 
+There is also [`StaticGraph`](staticgraph::StaticGraph), an immutable
+graph that can be built from either of the above, and which uses
+succinct data structures to be both compact and fast to query.
+
 
 # The interface
 
@@ -42,6 +46,7 @@ The core types, used all over the various traits, are defined in [`handle`]:
 
 * [`conversion`] has some functions for converting from GFA to a handlegraph and back
 * [`packed`] is where the packed vector collection types used by `PackedGraph` are implemented
+* [`transaction`] applies batches of graph mutations that can be rolled back
 
 */
 
@@ -53,6 +58,7 @@ pub mod pathhandlegraph;
 
 pub mod hashgraph;
 pub mod packedgraph;
+pub mod staticgraph;
 
 pub mod conversion;
 pub mod disjoint;
@@ -63,3 +69,4 @@ pub mod algorithms;
 pub mod consensus;
 
 pub mod path_position;
+pub mod transaction;
@@ -43,11 +43,15 @@ pub trait SubtractiveHandleGraph {
 /// Methods for manipulating handles that already exist in a graph.
 pub trait MutableHandles: AdditiveHandleGraph {
     /// Divide the given handle at the provided `offsets`, in terms of
-    /// the node's sequence. Creates `offsets.len() - 1` new handles,
-    /// and updates the edges accordingly.
+    /// the sequence of `handle` in its orientation. Creates
+    /// `offsets.len()` new handles, and updates the edges accordingly.
+    /// Returns the handles of all the parts of the node, in forward
+    /// orientation and left-to-right order.
     ///
     /// Implementations should update paths that include a step on
-    /// `handle` by inserting the new handles after that step.
+    /// `handle` by inserting the new handles after that step, or, if
+    /// the step traverses the node in reverse, by replacing it with
+    /// the flipped handles in reverse order.
     fn divide_handle(
         &mut self,
         handle: Handle,
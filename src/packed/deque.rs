@@ -258,7 +258,7 @@ impl<'a> Iter<'a> {
             len: deque.len(),
             diff,
             ix_gte_diff: false,
-            finished: false,
+            finished: deque.is_empty(),
         }
     }
 }
@@ -120,6 +120,16 @@ impl<'a> IntoEdgesPar for &'a PackedGraph {
     }
 }
 
+/// The side of the node record whose edge list holds the neighbors
+/// of `handle` in the direction `dir`.
+#[inline]
+fn edge_list_direction(handle: Handle, dir: Direction) -> Direction {
+    match (dir, handle.is_reverse()) {
+        (Direction::Left, true) | (Direction::Right, false) => Direction::Right,
+        (Direction::Left, false) | (Direction::Right, true) => Direction::Left,
+    }
+}
+
 impl<'a> IntoNeighbors for &'a PackedGraph {
     type Neighbors = EdgeListHandleIter<'a>;
 
@@ -134,19 +144,35 @@ impl<'a> IntoNeighbors for &'a PackedGraph {
         }
         let g_ix = self.nodes.handle_record(handle).unwrap();
 
-        let edge_list_ix = match (dir, handle.is_reverse()) {
-            (Dir::Left, true) | (Dir::Right, false) => {
-                self.nodes.get_edge_list(g_ix, Direction::Right)
-            }
-            (Dir::Left, false) | (Dir::Right, true) => {
-                self.nodes.get_edge_list(g_ix, Direction::Left)
-            }
-        };
+        let list_dir = edge_list_direction(handle, dir);
+
+        if let Some(range) = self.frozen_edge_range(g_ix, list_dir) {
+            return EdgeListHandleIter::new_frozen(
+                &self.edges,
+                range,
+                dir == Dir::Left,
+            );
+        }
+
+        let edge_list_ix = self.nodes.get_edge_list(g_ix, list_dir);
 
         let iter = self.edges.iter(edge_list_ix);
 
         EdgeListHandleIter::new(iter, dir == Dir::Left)
     }
+
+    #[inline]
+    fn degree(self, handle: Handle, dir: Direction) -> usize {
+        if self.edges.is_frozen() {
+            if let Some(g_ix) = self.nodes.handle_record(handle) {
+                let list_dir = edge_list_direction(handle, dir);
+                if let Some(range) = self.frozen_edge_range(g_ix, list_dir) {
+                    return range.len();
+                }
+            }
+        }
+        self.neighbors(handle, dir).count()
+    }
 }
 
 impl<'a> IntoSequences for &'a PackedGraph {
@@ -287,6 +313,10 @@ impl MutableHandles for PackedGraph {
         handle: Handle,
         offsets: &[usize],
     ) -> Vec<Handle> {
+        // the edge lists of the divided node are moved around before
+        // any edge records are modified
+        self.edges.unfreeze();
+
         let node_len = self.node_len(handle);
 
         let fwd_handle = handle.forward();
@@ -320,11 +350,11 @@ impl MutableHandles for PackedGraph {
             .and_then(SeqRecordIx::from_one_based_ix)
             .unwrap();
 
-        // Split the sequence and get the new sequence records
-        let new_seq_ixs =
+        // Split the sequence and get the new sequence ranges
+        let new_seqs =
             self.nodes.sequences_mut().split_sequence(seq_ix, &lengths);
 
-        if new_seq_ixs.is_none() {
+        if new_seqs.is_none() {
             panic!(
                 "Something went wrong when \
                  dividing the handle {:?} with offsets {:#?}",
@@ -332,12 +362,21 @@ impl MutableHandles for PackedGraph {
             );
         }
 
-        let new_seq_ixs = new_seq_ixs.unwrap();
+        let new_seqs = new_seqs.unwrap();
 
-        // Add new nodes and graph records for the new sequence records
-        for _s_ix in new_seq_ixs.iter() {
+        // Add new nodes and graph records, and point their sequence
+        // records to the new sequence ranges
+        for (seq_offset, seq_len) in new_seqs {
             let n_id = self.nodes.append_empty_node();
             let h = Handle::pack(n_id, false);
+            let new_seq_ix = self
+                .nodes
+                .handle_record(h)
+                .and_then(SeqRecordIx::from_one_based_ix)
+                .unwrap();
+            self.nodes
+                .sequences_mut()
+                .set_record(new_seq_ix, seq_offset, seq_len);
             result.push(h);
         }
 
@@ -364,10 +403,21 @@ impl MutableHandles for PackedGraph {
             EdgeListIx::null(),
         );
 
+        // A reversing self-edge on the right-hand side of the original
+        // handle is stored as a single record pointing to the
+        // original handle in reverse, and now has to point to the
+        // last handle in reverse instead
+        self.edges.update_edge_record(
+            old_right_record_edges,
+            |_, (h, _)| h == fwd_handle.flip(),
+            |(_, n)| (last_handle.flip(), n),
+        );
+
         // Update back references for the nodes connected to the
-        // right-hand side of the original handle
+        // right-hand side of the original handle. This includes the
+        // left-hand side of the original handle itself, if it has a
+        // self-loop.
 
-        // TODO handle self-edges
         // Get the edge lists with the back references
         let right_neighbors = self
             .neighbors(last_handle, Direction::Right)
@@ -386,7 +436,7 @@ impl MutableHandles for PackedGraph {
         for edge_list in right_neighbors {
             self.edges.update_edge_record(
                 edge_list,
-                |_, (h, _)| h.flip() == handle,
+                |_, (h, _)| h.flip() == fwd_handle,
                 |(_, n)| (last_handle.flip(), n),
             );
         }
@@ -401,15 +451,44 @@ impl MutableHandles for PackedGraph {
         let occurrences =
             self.steps_on_handle(handle).unwrap().collect::<Vec<_>>();
 
+        // Steps that traverse the node in reverse need the new
+        // handles flipped and in reverse order, with the original
+        // node last
+        let rev_segment =
+            result.iter().rev().map(|h| h.flip()).collect::<Vec<_>>();
+
         for (path_id, step_ix) in occurrences {
-            self.with_path_mut_ctx(path_id, |path_mut| {
-                let last_step = step_ix;
-                result
-                    .iter()
-                    .skip(1)
-                    .filter_map(|&h| path_mut.insert_step_after(last_step, h))
-                    .collect()
-            });
+            let step_rev = self
+                .path_handle_at_step(path_id, step_ix)
+                .map(|h| h.is_reverse())
+                .unwrap_or(false);
+
+            if step_rev {
+                let next = self
+                    .path_next_step(path_id, step_ix)
+                    .unwrap_or_else(StepPtr::null);
+
+                self.with_path_mut_ctx(path_id, |path_mut| {
+                    path_mut
+                        .rewrite_segment(step_ix, next, &rev_segment)
+                        .map(|(_, _, updates)| updates)
+                        .unwrap_or_default()
+                });
+            } else {
+                self.with_path_mut_ctx(path_id, |path_mut| {
+                    let mut last_step = step_ix;
+                    let mut updates = Vec::with_capacity(result.len() - 1);
+                    for &h in result.iter().skip(1) {
+                        if let Some(update) =
+                            path_mut.insert_step_after(last_step, h)
+                        {
+                            last_step = update.step();
+                            updates.push(update);
+                        }
+                    }
+                    updates
+                });
+            }
         }
 
         result
@@ -420,6 +499,8 @@ impl MutableHandles for PackedGraph {
             return handle;
         }
 
+        self.edges.unfreeze();
+
         let g_ix = self.nodes.handle_record(handle).unwrap();
 
         // Overwrite the sequence with its reverse complement
@@ -428,25 +509,34 @@ impl MutableHandles for PackedGraph {
             .sequences_mut()
             .overwrite_sequence(g_ix, &rev_seq);
 
-        // Flip the handle on the incoming edges
-        let edges = self
-            .neighbors(handle, Direction::Left)
-            .chain(self.neighbors(handle, Direction::Right))
+        // Flip every edge record that points to the node. These are
+        // all in the edge lists of the node's neighbors, which
+        // includes the node itself if it has any self-edges.
+        let (left, right) = self.nodes.get_node_edge_lists(g_ix).unwrap();
+        let mut neighbor_ids = self
+            .edges
+            .iter(left)
+            .chain(self.edges.iter(right))
+            .map(|(_, (h, _))| h.id())
             .collect::<Vec<_>>();
-
-        for target in edges {
-            let tgt_g_ix = self.nodes.handle_record(target).unwrap();
-            let backward_edge_list = if target.is_reverse() {
-                self.nodes.get_edge_list(tgt_g_ix, Direction::Right)
-            } else {
-                self.nodes.get_edge_list(tgt_g_ix, Direction::Left)
-            };
-
-            self.edges.update_edge_record(
-                backward_edge_list,
-                |_, (h, _)| h == handle,
-                |(h, n)| (h.flip(), n),
-            );
+        neighbor_ids.sort();
+        neighbor_ids.dedup();
+
+        for other_id in neighbor_ids {
+            let other_gix = self
+                .nodes
+                .handle_record(Handle::pack(other_id, false))
+                .unwrap();
+            let (left, right) =
+                self.nodes.get_node_edge_lists(other_gix).unwrap();
+
+            for &edge_list in [left, right].iter() {
+                self.edges.update_edge_records(
+                    edge_list,
+                    |_, (h, _)| h.id() == handle.id(),
+                    |(h, n)| (h.flip(), n),
+                );
+            }
         }
 
         // Swap the left and right edges on the handle
@@ -940,6 +1030,38 @@ pub(crate) mod tests {
         assert!(path_3.is_none());
     }
 
+    #[test]
+    fn removing_nodes_reverse_handle() {
+        let mut graph = test_graph_no_paths();
+        assert_eq!(graph.edge_count(), 13);
+
+        let affected_left_edges = vec_hnd(vec![7, 8]);
+        let affected_right_edges = vec_hnd(vec![1, 5]);
+
+        // removing node 2 through its reverse handle removes the same
+        // edges as through the forward handle
+        graph.remove_handle(r_hnd(2));
+
+        assert!(!graph.has_node(NodeId::from(2)));
+        assert_eq!(graph.edge_count(), 9);
+
+        let affected_left_post =
+            get_all_neighbors(&graph, &affected_left_edges, Direction::Left);
+        let affected_right_post =
+            get_all_neighbors(&graph, &affected_right_edges, Direction::Right);
+
+        assert_eq!(affected_left_post, vec![(7, vec![5]), (8, vec![1])]);
+        assert_eq!(affected_right_post, vec![(1, vec![8]), (5, vec![7])]);
+
+        let reverse_post = get_all_neighbors(
+            &graph,
+            &[r_hnd(1), r_hnd(5), r_hnd(7), r_hnd(8)],
+            Direction::Left,
+        );
+        assert!(reverse_post.iter().all(|(_, ns)| !ns.contains(&2)));
+        assert_eq!(graph.edges().count(), 9);
+    }
+
     #[test]
     fn removing_edges() {
         let mut graph = test_graph_with_paths();
@@ -1204,6 +1326,69 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn divide_handle_path_orientation() {
+        use crate::pathhandlegraph::GraphPathsSteps;
+
+        let mut graph = PackedGraph::new();
+
+        let h1 = graph.append_handle(b"AAAA");
+        let h2 = graph.append_handle(b"CCGGTT");
+        let h3 = graph.append_handle(b"TTTT");
+
+        graph.create_edge(Edge(h1, h2));
+        graph.create_edge(Edge(h2, h3));
+
+        let fwd = graph.create_path(b"fwd", false).unwrap();
+        let rev = graph.create_path(b"rev", false).unwrap();
+
+        for &h in [h1, h2, h3].iter() {
+            graph.path_append_step(fwd, h);
+        }
+        for &h in [h3.flip(), h2.flip(), h1.flip()].iter() {
+            graph.path_append_step(rev, h);
+        }
+
+        // offsets on the reverse handle, which reads AACCGG: AA|CC|GG
+        let parts = graph.divide_handle(h2.flip(), &[2, 4]);
+        assert_eq!(parts, vec![h2, hnd(4), hnd(5)]);
+
+        let seqs = parts
+            .iter()
+            .map(|&h| graph.sequence_vec(h))
+            .collect::<Vec<_>>();
+        assert_eq!(seqs, vec![b"CC".to_vec(), b"GG".to_vec(), b"TT".to_vec()]);
+
+        let path_handles = |graph: &PackedGraph, path: PathId| {
+            graph
+                .path_steps(path)
+                .unwrap()
+                .map(|(_, step)| step.handle)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(path_handles(&graph, fwd), vec![h1, h2, hnd(4), hnd(5), h3]);
+        assert_eq!(
+            path_handles(&graph, rev),
+            vec![
+                h3.flip(),
+                hnd(5).flip(),
+                hnd(4).flip(),
+                h2.flip(),
+                h1.flip()
+            ]
+        );
+
+        for &h in parts.iter() {
+            let mut occurs = get_occurs(&graph, h.id().0)
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>();
+            occurs.sort();
+            assert_eq!(occurs, vec![0, 1]);
+        }
+    }
+
     #[test]
     fn defrag_packed_graph() {
         use bstr::B;
@@ -1608,4 +1793,105 @@ pub(crate) mod tests {
 
         assert_eq!(pre_occurs, post_occurs);
     }
+
+    #[test]
+    fn packedgraph_freeze_edges() {
+        use defragment::Defragment;
+
+        // each oriented handle, with its left and right neighbors and
+        // degrees
+        type Neighbors = (Handle, Vec<Handle>, Vec<Handle>, usize, usize);
+
+        fn all_neighbors(graph: &PackedGraph) -> Vec<Neighbors> {
+            let mut handles = graph.handles().collect::<Vec<_>>();
+            handles.sort();
+            handles
+                .into_iter()
+                .flat_map(|h| vec![h, h.flip()])
+                .map(|h| {
+                    let mut left =
+                        graph.neighbors(h, Direction::Left).collect::<Vec<_>>();
+                    let mut right = graph
+                        .neighbors(h, Direction::Right)
+                        .collect::<Vec<_>>();
+                    left.sort();
+                    right.sort();
+                    let left_deg = graph.degree(h, Direction::Left);
+                    let right_deg = graph.degree(h, Direction::Right);
+                    (h, left, right, left_deg, right_deg)
+                })
+                .collect()
+        }
+
+        let mut graph = PackedGraph::new();
+        for seq in [b"GTCA", b"AAGT", b"CCAA", b"TTTT", b"GGCC"].iter() {
+            graph.append_handle(&seq[..]);
+        }
+
+        let edges = [
+            edge(1, 2),
+            edge(1, 3),
+            edge(2, 4),
+            edge(3, 4),
+            Edge(hnd(4), r_hnd(5)),
+            // self-edge and reversing self-edges
+            edge(2, 2),
+            Edge(hnd(3), r_hnd(3)),
+            Edge(r_hnd(1), hnd(1)),
+        ];
+        for &e in edges.iter() {
+            graph.create_edge(e);
+        }
+
+        graph.remove_edge(edge(1, 3));
+        graph.remove_edge(Edge(hnd(3), r_hnd(3)));
+
+        let edge_count = graph.edge_count();
+        let expected = all_neighbors(&graph);
+
+        graph.freeze_edges();
+        assert!(graph.edges_frozen());
+        assert_eq!(graph.edges.removed_count, 0);
+        assert_eq!(graph.edge_count(), edge_count);
+        assert_eq!(all_neighbors(&graph), expected);
+
+        // adding a node doesn't unfreeze the edges
+        let n6 = graph.append_handle(b"ACGT");
+        assert!(graph.edges_frozen());
+        assert_eq!(graph.degree(n6, Direction::Left), 0);
+        assert_eq!(graph.neighbors(n6, Direction::Right).count(), 0);
+
+        let frozen_expected = all_neighbors(&graph);
+
+        // but modifying the edges does
+        graph.create_edge(Edge(hnd(5), n6));
+        assert!(!graph.edges_frozen());
+        assert!(graph.has_edge(hnd(5), n6));
+        assert_eq!(graph.edge_count(), edge_count + 1);
+
+        graph.remove_edge(Edge(hnd(5), n6));
+        assert_eq!(all_neighbors(&graph), frozen_expected);
+
+        graph.freeze_edges();
+        assert!(graph.remove_edge(edge(2, 2)));
+        assert!(!graph.edges_frozen());
+        assert!(!graph.has_edge(hnd(2), hnd(2)));
+
+        graph.freeze_edges();
+        let parts = graph.divide_handle(hnd(2), &[2]);
+        assert!(!graph.edges_frozen());
+        assert!(graph.has_edge(hnd(1), parts[0]));
+        assert!(graph.has_edge(parts[0], parts[1]));
+        assert!(graph.has_edge(parts[1], hnd(4)));
+
+        let expected = all_neighbors(&graph);
+        graph.freeze_edges();
+        assert_eq!(all_neighbors(&graph), expected);
+
+        let expected = all_neighbors(&graph);
+        graph.freeze_edges();
+        graph.defragment();
+        assert!(!graph.edges_frozen());
+        assert_eq!(all_neighbors(&graph), expected);
+    }
 }
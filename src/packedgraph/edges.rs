@@ -62,6 +62,13 @@ impl RecordIndex for EdgeVecIx {
 ///
 /// Outwardly this is indexed using `EdgeListIx`, and the parts of a
 /// record is indexed using `EdgeVecIx`.
+///
+/// The edge lists can also be *frozen*, in which case the records of
+/// each edge list are stored contiguously, in node record order, and
+/// `frozen_offsets` holds the start of each edge list, so that the
+/// lists can be read without following the pointers. The linked
+/// lists are still valid while frozen, and any modification of the
+/// records unfreezes the lists.
 #[derive(Debug, Clone)]
 pub struct EdgeLists {
     pub record_vec: PagedIntVec,
@@ -69,9 +76,14 @@ pub struct EdgeLists {
     pub removed_count: usize,
     pub(crate) reversing_self_edge_records: usize,
     pub(crate) removed_reversing_self_edge_records: usize,
+    frozen: bool,
+    frozen_offsets: PackedIntVec,
 }
 
-crate::impl_space_usage!(EdgeLists, [record_vec, removed_records]);
+crate::impl_space_usage!(
+    EdgeLists,
+    [record_vec, removed_records, frozen_offsets]
+);
 
 pub type EdgeRecord = (Handle, EdgeListIx);
 
@@ -112,6 +124,8 @@ impl PackedListMut for EdgeLists {
 
     #[inline]
     fn remove_at_pointer(&mut self, ptr: EdgeListIx) -> Option<EdgeListIx> {
+        self.unfreeze();
+
         let h_ix = ptr.to_record_ix(2, 0)?;
         let n_ix = h_ix + 1;
 
@@ -145,6 +159,8 @@ impl Default for EdgeLists {
             removed_count: 0,
             reversing_self_edge_records: 0,
             removed_reversing_self_edge_records: 0,
+            frozen: false,
+            frozen_offsets: PackedIntVec::new(),
         }
     }
 }
@@ -194,7 +210,9 @@ impl EdgeLists {
     pub(crate) fn len(&self) -> usize {
         let num_records = self.record_vec.len() / EdgeVecIx::RECORD_WIDTH;
         let num_edges = (num_records + self.reversing_self_edge_records) / 2;
-        num_edges - (self.removed_count / 2)
+        let removed =
+            self.removed_count + self.removed_reversing_self_edge_records;
+        num_edges - (removed / 2)
     }
 
     #[inline]
@@ -230,6 +248,8 @@ impl EdgeLists {
         handle: Handle,
         next: EdgeListIx,
     ) -> EdgeListIx {
+        self.unfreeze();
+
         let rec_ix = EdgeListIx::from_record_start(self.record_vec.len(), 2);
         self.record_vec.append(handle.pack());
         self.record_vec.append(next.pack());
@@ -256,6 +276,8 @@ impl EdgeLists {
         handle: Handle,
         next: EdgeListIx,
     ) -> Option<()> {
+        self.unfreeze();
+
         let h_ix = ix.to_record_ix(2, 0)?;
         let n_ix = ix.to_record_ix(2, 1)?;
 
@@ -309,6 +331,132 @@ impl EdgeLists {
         }
     }
 
+    /// Returns `true` if the edge lists are frozen, i.e. stored as
+    /// contiguous runs of records.
+    #[inline]
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Drop the frozen edge list offsets. The edge lists themselves
+    /// are left as they are.
+    #[inline]
+    pub(crate) fn unfreeze(&mut self) {
+        if self.frozen {
+            self.frozen = false;
+            self.frozen_offsets = PackedIntVec::new();
+        }
+    }
+
+    /// Rewrite the edge lists so that the records of each list are
+    /// stored contiguously and in order, with the lists ordered by
+    /// the provided heads, and freeze them.
+    ///
+    /// `heads` must contain the head of every edge list in the
+    /// graph, with the left and right edge lists of each node record
+    /// in that order; returns the new heads, in the same order.
+    pub(crate) fn freeze<I>(&mut self, heads: I) -> Vec<EdgeListIx>
+    where
+        I: IntoIterator<Item = EdgeListIx>,
+    {
+        let mut record_vec = PagedIntVec::new(WIDE_PAGE_WIDTH);
+        record_vec.reserve(self.record_vec.len());
+
+        let mut offsets = PackedIntVec::new();
+        let mut new_heads = Vec::new();
+
+        let mut handles: Vec<Handle> = Vec::new();
+
+        for head in heads {
+            let start = record_vec.len() / EdgeVecIx::RECORD_WIDTH;
+            offsets.append(start as u64);
+
+            handles.clear();
+            handles.extend(self.iter(head).map(|(_, (h, _))| h));
+
+            for (ix, handle) in handles.iter().enumerate() {
+                let next = if ix + 1 < handles.len() {
+                    EdgeListIx::from_zero_based(start + ix + 1)
+                } else {
+                    EdgeListIx::null()
+                };
+                record_vec.append(handle.pack());
+                record_vec.append(next.pack());
+            }
+
+            if handles.is_empty() {
+                new_heads.push(EdgeListIx::null());
+            } else {
+                new_heads.push(EdgeListIx::from_zero_based(start));
+            }
+        }
+
+        offsets.append((record_vec.len() / EdgeVecIx::RECORD_WIDTH) as u64);
+
+        self.record_vec = record_vec;
+        self.removed_records.clear();
+        self.removed_count = 0;
+        self.reversing_self_edge_records -=
+            self.removed_reversing_self_edge_records;
+        self.removed_reversing_self_edge_records = 0;
+
+        self.frozen = true;
+        self.frozen_offsets = offsets;
+
+        new_heads
+    }
+
+    /// If the edge lists are frozen, returns the range of records in
+    /// the edge list with the given index in the heads that were
+    /// provided to `freeze`. Returns `None` if the lists aren't
+    /// frozen, or if the list was created after freezing, in which
+    /// case the list must be empty.
+    #[inline]
+    pub(crate) fn frozen_range(
+        &self,
+        list_ix: usize,
+    ) -> Option<std::ops::Range<usize>> {
+        if !self.frozen || list_ix + 1 >= self.frozen_offsets.len() {
+            return None;
+        }
+        let start = self.frozen_offsets.get(list_ix) as usize;
+        let end = self.frozen_offsets.get(list_ix + 1) as usize;
+        Some(start..end)
+    }
+
+    /// The target handle of the record at the zero-based record
+    /// index `ix`.
+    #[inline]
+    pub(crate) fn record_handle(&self, ix: usize) -> Handle {
+        Handle::from_integer(self.record_vec.get(ix * EdgeVecIx::RECORD_WIDTH))
+    }
+
+    /// Updates every edge record in the provided edge list that
+    /// fulfills the predicate `pred`, using the provided update
+    /// function `f`. Returns the number of updated records.
+    pub(super) fn update_edge_records<P, F>(
+        &mut self,
+        start: EdgeListIx,
+        pred: P,
+        f: F,
+    ) -> usize
+    where
+        P: Fn(EdgeListIx, EdgeRecord) -> bool,
+        F: Fn(EdgeRecord) -> EdgeRecord,
+    {
+        let entries = self
+            .iter(start)
+            .filter(|&(ix, rec)| pred(ix, rec))
+            .collect::<Vec<_>>();
+
+        for &(edge_ix, record) in entries.iter() {
+            let (handle, next) = f(record);
+            self.set_record(edge_ix, handle, next);
+        }
+
+        entries.len()
+    }
+
     pub fn print_diagnostics(&self) {
         println!("\n ~~ BEGIN EdgeLists diagnostics ~~ \n");
 
@@ -333,6 +481,8 @@ impl Defragment for EdgeLists {
     type Updates = FnvHashMap<EdgeListIx, EdgeListIx>;
 
     fn defragment(&mut self) -> Option<Self::Updates> {
+        self.unfreeze();
+
         let total_records = self.record_vec.len() / EdgeVecIx::RECORD_WIDTH;
 
         let mut new_record_vec = PagedIntVec::new(WIDE_PAGE_WIDTH);
@@ -112,6 +112,8 @@ impl PackedGraph {
     where
         I: Iterator<Item = Edge>,
     {
+        self.edges.unfreeze();
+
         let edge_page_size = self.edges.record_vec.page_size();
 
         let mut page_buf: Vec<u64> = Vec::with_capacity(edge_page_size);
@@ -176,6 +178,57 @@ impl PackedGraph {
         }
     }
 
+    /// Compact the edge lists so that the edges of each node are
+    /// stored contiguously, in node record order, and freeze them.
+    /// While the edges are frozen, neighbor iteration reads the edges
+    /// in order instead of following the list pointers, and `degree`
+    /// is constant time.
+    ///
+    /// The edges are unfrozen by any modification of the edges, after
+    /// which the compacted edge lists behave like any other. Adding
+    /// nodes does not unfreeze the edges.
+    pub fn freeze_edges(&mut self) {
+        let node_records = self.nodes.records_vec.len() / 2;
+
+        let heads = (0..node_records).flat_map(|ix| {
+            let rec_id = NodeRecordId::from_zero_based(ix);
+            let left = self.nodes.get_edge_list(rec_id, Direction::Left);
+            let right = self.nodes.get_edge_list(rec_id, Direction::Right);
+            std::iter::once(left).chain(std::iter::once(right))
+        });
+        let heads = heads.collect::<Vec<_>>();
+
+        let new_heads = self.edges.freeze(heads);
+
+        for (ix, heads) in new_heads.chunks(2).enumerate() {
+            let rec_id = NodeRecordId::from_zero_based(ix);
+            self.nodes.set_node_edge_lists(rec_id, heads[0], heads[1]);
+        }
+    }
+
+    /// Returns `true` if the edges are currently frozen; see
+    /// `freeze_edges`.
+    #[inline]
+    pub fn edges_frozen(&self) -> bool {
+        self.edges.is_frozen()
+    }
+
+    /// The range of edge records on the `dir` side of the node record
+    /// `rec_id`, if the edges are frozen.
+    #[inline]
+    pub(super) fn frozen_edge_range(
+        &self,
+        rec_id: NodeRecordId,
+        dir: Direction,
+    ) -> Option<std::ops::Range<usize>> {
+        let rec_ix = rec_id.to_zero_based()?;
+        let list_ix = match dir {
+            Direction::Left => 2 * rec_ix,
+            Direction::Right => 2 * rec_ix + 1,
+        };
+        self.edges.frozen_range(list_ix)
+    }
+
     pub(crate) fn remove_edge_from(
         &mut self,
         on: Handle,
@@ -272,7 +325,7 @@ impl PackedGraph {
             self.remove_edge_from(prev, handle);
 
             if prev != handle.flip() {
-                self.remove_edge_from(handle.forward().flip(), prev.flip());
+                self.remove_edge_from(handle.flip(), prev.flip());
             }
         }
 
@@ -281,7 +334,7 @@ impl PackedGraph {
             self.remove_edge_from(next.flip(), handle.flip());
 
             if next != handle.flip() {
-                self.remove_edge_from(handle.forward(), next);
+                self.remove_edge_from(handle, next);
             }
         }
 
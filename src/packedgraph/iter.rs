@@ -6,19 +6,41 @@ use super::{index::list, EdgeLists};
 use fnv::FnvHashSet;
 
 /// Iterator for stepping through an edge list, returning Handles.
+/// If the edge lists are frozen, the records are read in order
+/// rather than by following the list pointers.
 #[derive(Clone)]
 pub struct EdgeListHandleIter<'a> {
-    edge_list_iter: list::Iter<'a, EdgeLists>,
+    records: EdgeRecords<'a>,
     flip: bool,
 }
 
+#[derive(Clone)]
+enum EdgeRecords<'a> {
+    List(list::Iter<'a, EdgeLists>),
+    Frozen {
+        edges: &'a EdgeLists,
+        range: std::ops::Range<usize>,
+    },
+}
+
 impl<'a> EdgeListHandleIter<'a> {
     pub(super) fn new(
         edge_list_iter: list::Iter<'a, EdgeLists>,
         flip: bool,
     ) -> Self {
         Self {
-            edge_list_iter,
+            records: EdgeRecords::List(edge_list_iter),
+            flip,
+        }
+    }
+
+    pub(super) fn new_frozen(
+        edges: &'a EdgeLists,
+        range: std::ops::Range<usize>,
+        flip: bool,
+    ) -> Self {
+        Self {
+            records: EdgeRecords::Frozen { edges, range },
             flip,
         }
     }
@@ -29,7 +51,12 @@ impl<'a> Iterator for EdgeListHandleIter<'a> {
 
     #[inline]
     fn next(&mut self) -> Option<Handle> {
-        let (_, (handle, _)) = self.edge_list_iter.next()?;
+        let handle = match &mut self.records {
+            EdgeRecords::List(iter) => iter.next()?.1 .0,
+            EdgeRecords::Frozen { edges, range } => {
+                edges.record_handle(range.next()?)
+            }
+        };
         if self.flip {
             Some(handle.flip())
         } else {
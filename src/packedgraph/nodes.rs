@@ -504,7 +504,6 @@ impl NodeRecords {
         Some((left, right))
     }
 
-    #[allow(dead_code)]
     pub(super) fn set_node_edge_lists(
         &mut self,
         rec_id: NodeRecordId,
@@ -10,9 +10,9 @@ use crate::{
         SubtractiveHandleGraph, TransformNodeIds,
     },
     pathhandlegraph::{
-        GraphPathNames, GraphPaths, GraphPathsRef, IntoNodeOccurrences,
-        IntoPathIds, MutPath, MutableGraphPaths, PathId, PathSequences,
-        PathSteps,
+        GraphPathNames, GraphPaths, GraphPathsRef, GraphPathsSteps,
+        IntoNodeOccurrences, IntoPathIds, MutPath, MutableGraphPaths, PathId,
+        PathSequences, PathStep, PathSteps,
     },
 };
 
@@ -34,15 +34,17 @@ use quickcheck::{Arbitrary, Gen, QuickCheck};
 use fnv::{FnvHashMap, FnvHashSet};
 
 mod delta;
-mod ops;
 mod traits;
 
+use crate::transaction::{
+    apply_transaction, CreateOp, GraphOp, Journal, MutHandleOp, MutPathOp,
+    RemoveOp,
+};
 pub use delta::{
     AddDel, AddDelDelta, DeltaEq, EdgesDelta, GraphOpDelta, NodesDelta,
     PathsDelta,
 };
-use ops::{CreateOp, GraphOp, GraphWideOp, MutHandleOp, MutPathOp, RemoveOp};
-use traits::{DeriveDelta, GraphApply, GraphDelta};
+use traits::DeriveDelta;
 
 use rand::prelude::*;
 
@@ -79,7 +81,7 @@ fn print_graph_ops(ops: &[GraphOp]) {
                     }
                     println!();
                 }
-                CreateOp::Path { name } => {}
+                CreateOp::Path { .. } => {}
             },
             GraphOp::Remove { op } => match op {
                 RemoveOp::Handle { handle } => {}
@@ -98,11 +100,24 @@ fn print_graph_ops(ops: &[GraphOp]) {
             },
             GraphOp::MutHandle { op } => {}
             GraphOp::MutPath { op } => {}
-            GraphOp::GraphWide { op } => {}
         }
     }
 }
 
+/// Apply `op` to `graph`, using `PackedGraph::create_edges_iter` for
+/// batches of edges, so that batched and unbatched ops can be
+/// compared.
+fn apply_op(graph: &mut PackedGraph, op: &GraphOp) {
+    if let GraphOp::Create {
+        op: CreateOp::EdgesIter { edges },
+    } = op
+    {
+        graph.create_edges_iter(edges.iter().copied());
+    } else {
+        op.apply(graph, &mut GraphOpDelta::default()).unwrap();
+    }
+}
+
 /// Takes a sequence of [`GraphOp`]s and batches sequences of
 /// `CreateOp::Edge` into equivalent `CreateOp::EdgeIter` ops.
 // fn batch_edge_ops(ops: &[GraphOp], mut batch_freq: f64) -> Vec<GraphOp> {
@@ -192,37 +207,23 @@ fn gen_edge_ops(edges: &[Edge], mut del_r: f64, shuffle: bool) -> Vec<GraphOp> {
     };
     */
 
-    let mut remove: Vec<(usize, Edge)> = Vec::new();
-
-    let mut ops = Vec::with_capacity(edges.len());
+    let mut ops = edges
+        .iter()
+        .map(|&edge| create_op(edge))
+        .collect::<Vec<_>>();
 
-    for edge in edges {
-        if remove_add.contains(&edge) {
-            remove.push((ops.len(), edge));
+    // each removal is placed somewhere after the edge's first create
+    // op, and the edge is added back somewhere after the removal
+    for &edge in edges.iter() {
+        if !remove_add.contains(&edge) {
+            continue;
         }
-        ops.push(create_op(edge));
-    }
-
-    let mut readd: Vec<(usize, Edge)> = Vec::new();
-
-    let mut count = 0usize;
-
-    // `ix` is the index of the edge's create op, so we know to add the remove op somewhere after
-    for &(ix, edge) in remove.iter() {
-        let ix = ix + count;
+        let create = create_op(edge);
+        let ix = ops.iter().position(|op| *op == create).unwrap();
         let rem_ix = rng.gen_range(ix + 1, ops.len() + 1);
         ops.insert(rem_ix, remove_op(edge));
-        readd.push((rem_ix, edge));
-        count += 1;
-    }
-
-    count = 0;
-
-    for &(ix, edge) in readd.iter() {
-        let ix = ix + count;
-        let add_ix = rng.gen_range(ix + 1, ops.len() + 1);
-        ops.insert(add_ix, create_op(edge));
-        count += 1;
+        let add_ix = rng.gen_range(rem_ix + 1, ops.len() + 1);
+        ops.insert(add_ix, create);
     }
 
     ops
@@ -233,19 +234,21 @@ fn adding_nodes_prop() {
     let mut graph_1 = crate::packedgraph::tests::test_graph_no_paths();
     let mut graph_2 = crate::packedgraph::tests::test_graph_no_paths();
 
-    let op_1 = CreateOp::Handle {
+    let op_1: GraphOp = CreateOp::Handle {
         id: 10u64.into(),
         seq: vec![b'A', b'G', b'G', b'T', b'C'],
-    };
+    }
+    .into();
 
     // let op_2 = CreateOp::Handle {
     //     id: 11u64.into(),
     //     seq: vec![b'A', b'A', b'A'],
     // };
 
-    let op_2 = RemoveOp::Handle {
+    let op_2: GraphOp = RemoveOp::Handle {
         handle: Handle::pack(8u64, false),
-    };
+    }
+    .into();
 
     // let mut count
     let count = 0usize;
@@ -253,7 +256,7 @@ fn adding_nodes_prop() {
     let delta_1 = op_1.derive_delta(&graph_1, count);
     let count = delta_1.count;
     let delta_eq_1 = DeltaEq::new(&graph_1, delta_1.clone());
-    op_1.apply(&mut graph_1);
+    apply_op(&mut graph_1, &op_1);
 
     println!("---------------------------");
     println!("  op 1");
@@ -264,7 +267,7 @@ fn adding_nodes_prop() {
     let delta_2 = op_2.derive_delta(&graph_1, count);
     let count = delta_2.count;
     let delta_eq_2 = DeltaEq::new(&graph_1, delta_2.clone());
-    op_2.apply(&mut graph_1);
+    apply_op(&mut graph_1, &op_2);
 
     println!("---------------------------");
     println!("  op 2");
@@ -321,11 +324,11 @@ fn create_edges_iter_eq() {
     println!("---------------------");
 
     for op in ops_simple {
-        op.apply(&mut graph_simple);
+        apply_op(&mut graph_simple, &op);
     }
 
     for op in ops_batch {
-        op.apply(&mut graph_batch);
+        apply_op(&mut graph_batch, &op);
     }
 
     let mut simple_edges = graph_simple.edges().collect::<Vec<_>>();
@@ -531,35 +534,35 @@ fn adding_edges_ops() {
     let mut graph_shuffle_batched = graph.clone();
 
     for op in edge_ops_zero {
-        op.apply(&mut graph_zero);
+        apply_op(&mut graph_zero, &op);
     }
 
     for op in edge_ops_mid {
-        op.apply(&mut graph_mid);
+        apply_op(&mut graph_mid, &op);
     }
 
     for op in edge_ops_one {
-        op.apply(&mut graph_one);
+        apply_op(&mut graph_one, &op);
     }
 
     for op in edge_ops_shuffle {
-        op.apply(&mut graph_shuffle);
+        apply_op(&mut graph_shuffle, &op);
     }
 
     for op in batched_zero {
-        op.apply(&mut graph_zero_batched);
+        apply_op(&mut graph_zero_batched, &op);
     }
 
     for op in batched_mid {
-        op.apply(&mut graph_mid_batched);
+        apply_op(&mut graph_mid_batched, &op);
     }
 
     for op in batched_one {
-        op.apply(&mut graph_one_batched);
+        apply_op(&mut graph_one_batched, &op);
     }
 
     for op in batched_shuffle {
-        op.apply(&mut graph_shuffle_batched);
+        apply_op(&mut graph_shuffle_batched, &op);
     }
 
     println!("expected edge count:      {}", orig_graph.edge_count());
@@ -715,3 +718,345 @@ fn adding_edges_ops() {
     assert_eq!(expected, edges_shuffle);
     */
 }
+
+#[test]
+fn journal_edge_ops_replay() {
+    let orig_graph = crate::packedgraph::tests::test_graph_no_paths();
+
+    let mut graph = PackedGraph::new();
+    for h in orig_graph.handles() {
+        graph.create_handle(&orig_graph.sequence_vec(h), h.id());
+    }
+
+    let edges = orig_graph.edges().collect::<Vec<_>>();
+
+    let edge_set = |graph: &PackedGraph| {
+        let mut edges = graph
+            .edges()
+            .map(|Edge(l, r)| Edge::edge_handle(l, r))
+            .collect::<Vec<_>>();
+        edges.sort();
+        edges.dedup();
+        edges
+    };
+
+    let edge_ops = gen_edge_ops(&edges, 0.5, true);
+    let batched = batch_edge_ops(&edge_ops);
+
+    let mut direct = graph.clone();
+    for op in edge_ops.iter() {
+        apply_op(&mut direct, op);
+    }
+    assert_eq!(edge_set(&direct), edge_set(&orig_graph));
+
+    for ops in [edge_ops, batched].iter() {
+        // each op is recorded as its own entry
+        let mut recorded = graph.clone();
+        let mut journal = Journal::new();
+        for op in ops.iter() {
+            journal.apply(&mut recorded, op.clone()).unwrap();
+        }
+        assert_eq!(journal.ops().cloned().collect::<Vec<_>>(), *ops);
+        assert_eq!(edge_set(&recorded), edge_set(&direct));
+
+        let mut buf: Vec<u8> = Vec::new();
+        journal.write(&mut buf).unwrap();
+        let loaded = Journal::read(buf.as_slice()).unwrap();
+        assert_eq!(loaded.entries(), journal.entries());
+
+        let mut replayed = graph.clone();
+        loaded.replay(&mut replayed).unwrap();
+        assert_eq!(edge_set(&replayed), edge_set(&direct));
+
+        apply_transaction(&mut replayed, loaded.undo_ops()).unwrap();
+        assert_eq!(replayed.edge_count(), 0);
+    }
+}
+
+/// A small random graph where one node, `target`, has any
+/// combination of self-loops and reversing self-edges, along with
+/// paths that visit it in both orientations. Used to check that
+/// dividing and reorienting nodes works with self-edges.
+#[derive(Debug, Clone)]
+struct SelfEdgeGraph {
+    nodes: Vec<Vec<u8>>,
+    edges: Vec<Edge>,
+    paths: Vec<Vec<Handle>>,
+    target: Handle,
+    offsets: Vec<usize>,
+    freeze: bool,
+}
+
+impl Arbitrary for SelfEdgeGraph {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        let node_count = g.gen_range(1, 6);
+
+        let nodes = (0..node_count)
+            .map(|_| {
+                let len = g.gen_range(1, 9);
+                (0..len).map(|_| *b"ACGT".choose(g).unwrap()).collect()
+            })
+            .collect::<Vec<Vec<u8>>>();
+
+        let random_handle = |g: &mut G| {
+            let id = g.gen_range(1, node_count + 1) as u64;
+            Handle::pack(id, g.gen())
+        };
+
+        let target = random_handle(g);
+        let fwd = target.forward();
+
+        let mut edges = (0..g.gen_range(0, node_count * 2))
+            .map(|_| Edge(random_handle(g), random_handle(g)))
+            .collect::<Vec<_>>();
+
+        // self-loop, and reversing self-edges on either side
+        let self_edges =
+            [Edge(fwd, fwd), Edge(fwd, fwd.flip()), Edge(fwd.flip(), fwd)];
+        for &edge in self_edges.iter() {
+            if g.gen() {
+                edges.push(edge);
+            }
+        }
+        edges.shuffle(g);
+
+        let paths = (0..g.gen_range(0, 3))
+            .map(|_| {
+                let mut steps = (0..g.gen_range(0, 4))
+                    .map(|_| random_handle(g))
+                    .collect::<Vec<_>>();
+                steps.push(fwd);
+                steps.push(fwd.flip());
+                steps.shuffle(g);
+                steps
+            })
+            .collect();
+
+        let len = nodes[u64::from(target.id()) as usize - 1].len();
+        let mut offsets = (1..len).filter(|_| g.gen()).collect::<Vec<_>>();
+        if offsets.is_empty() && len > 1 {
+            offsets.push(g.gen_range(1, len));
+        }
+
+        SelfEdgeGraph {
+            nodes,
+            edges,
+            paths,
+            target,
+            offsets,
+            freeze: g.gen(),
+        }
+    }
+}
+
+impl SelfEdgeGraph {
+    fn build<G>(&self) -> (G, Vec<PathId>)
+    where
+        G: Default + MutableHandles + MutableGraphPaths,
+    {
+        let mut graph = G::default();
+        for (ix, seq) in self.nodes.iter().enumerate() {
+            graph.create_handle(seq, ix as u64 + 1);
+        }
+        for &edge in self.edges.iter() {
+            graph.create_edge(edge);
+        }
+
+        let paths = self
+            .paths
+            .iter()
+            .enumerate()
+            .map(|(ix, steps)| {
+                let name = format!("path_{}", ix);
+                let path = graph.create_path(name.as_bytes(), false).unwrap();
+                for &handle in steps.iter() {
+                    graph.path_append_step(path, handle);
+                }
+                path
+            })
+            .collect();
+
+        (graph, paths)
+    }
+
+    /// The set of canonical edges after transforming every edge
+    /// endpoint on the target node with `left` and `right`, which
+    /// are applied to handles on the left and right side of each
+    /// edge, respectively.
+    fn mapped_edges<L, R>(&self, left: L, right: R) -> FnvHashSet<Edge>
+    where
+        L: Fn(Handle) -> Handle,
+        R: Fn(Handle) -> Handle,
+    {
+        let id = self.target.id();
+        self.edges
+            .iter()
+            .map(|&Edge(l, r)| {
+                let l = if l.id() == id { left(l) } else { l };
+                let r = if r.id() == id { right(r) } else { r };
+                Edge::edge_handle(l, r)
+            })
+            .collect()
+    }
+}
+
+fn self_edge_graph_consistent<G>(
+    graph: &G,
+    expected_edges: &FnvHashSet<Edge>,
+    paths: &[PathId],
+    path_seqs: &[Vec<u8>],
+) -> bool
+where
+    G: HandleGraph,
+    for<'a> &'a G: HandleGraphRef + GraphPathsSteps + IntoSequences,
+{
+    let edges = graph
+        .edges()
+        .map(|Edge(l, r)| Edge::edge_handle(l, r))
+        .collect::<FnvHashSet<_>>();
+
+    if &edges != expected_edges || graph.edge_count() != edges.len() {
+        return false;
+    }
+
+    // every edge must be visible from both of its sides
+    for handle in graph.handles() {
+        for &h in [handle, handle.flip()].iter() {
+            for next in graph.neighbors(h, Direction::Right) {
+                if !graph.neighbors(next, Direction::Left).any(|p| p == h) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    paths.iter().zip(path_seqs.iter()).all(|(&path, seq)| {
+        let path_seq = graph
+            .path_steps(path)
+            .unwrap()
+            .flat_map(|step| graph.sequence(step.handle()))
+            .collect::<Vec<_>>();
+        &path_seq == seq
+    })
+}
+
+fn path_sequences<G>(graph: &G, paths: &[PathId]) -> Vec<Vec<u8>>
+where
+    for<'a> &'a G: GraphPathsSteps + IntoSequences,
+{
+    paths
+        .iter()
+        .map(|&path| {
+            graph
+                .path_steps(path)
+                .unwrap()
+                .flat_map(|step| graph.sequence(step.handle()))
+                .collect()
+        })
+        .collect()
+}
+
+fn divide_self_edges<G>(
+    input: &SelfEdgeGraph,
+    graph: &mut G,
+    paths: &[PathId],
+) -> bool
+where
+    G: HandleGraph + MutableHandles,
+    for<'a> &'a G: HandleGraphRef + GraphPathsSteps + IntoSequences,
+{
+    if input.offsets.is_empty() {
+        return true;
+    }
+
+    let path_seqs = path_sequences(graph, paths);
+    let fwd_seq = graph.sequence_vec(input.target.forward());
+
+    let parts = graph.divide_handle(input.target, &input.offsets);
+    if parts.len() != input.offsets.len() + 1 {
+        return false;
+    }
+
+    let parts_seq = parts
+        .iter()
+        .flat_map(|&h| graph.sequence(h))
+        .collect::<Vec<_>>();
+    if parts_seq != fwd_seq {
+        return false;
+    }
+
+    let first = parts[0];
+    let last = *parts.last().unwrap();
+
+    // the right side of the node is now the right side of the
+    // last part, and the left side is the left side of the first
+    let mut expected = input.mapped_edges(
+        |l| if l.is_reverse() { first.flip() } else { last },
+        |r| if r.is_reverse() { last.flip() } else { first },
+    );
+    for w in parts.windows(2) {
+        expected.insert(Edge::edge_handle(w[0], w[1]));
+    }
+
+    self_edge_graph_consistent(&*graph, &expected, paths, &path_seqs)
+}
+
+fn flip_self_edges<G>(
+    input: &SelfEdgeGraph,
+    graph: &mut G,
+    paths: &[PathId],
+) -> bool
+where
+    G: HandleGraph + MutableHandles,
+    for<'a> &'a G: HandleGraphRef + GraphPathsSteps + IntoSequences,
+{
+    let path_seqs = path_sequences(graph, paths);
+
+    let handle = input.target.forward().flip();
+    let rev_seq = graph.sequence_vec(handle);
+
+    let flipped = graph.apply_orientation(handle);
+    if flipped.id() != handle.id()
+        || graph.sequence_vec(flipped.forward()) != rev_seq
+    {
+        return false;
+    }
+
+    let expected = input.mapped_edges(|l| l.flip(), |r| r.flip());
+
+    self_edge_graph_consistent(&*graph, &expected, paths, &path_seqs)
+}
+
+fn packedgraph_self_edges_prop(input: SelfEdgeGraph) -> bool {
+    let (mut graph, paths) = input.build::<PackedGraph>();
+    if input.freeze {
+        graph.freeze_edges();
+    }
+
+    let mut flip_graph = graph.clone();
+
+    divide_self_edges(&input, &mut graph, &paths)
+        && flip_self_edges(&input, &mut flip_graph, &paths)
+}
+
+fn hashgraph_self_edges_prop(input: SelfEdgeGraph) -> bool {
+    let (mut graph, paths) = input.build::<HashGraph>();
+    let (mut flip_graph, _) = input.build::<HashGraph>();
+
+    divide_self_edges(&input, &mut graph, &paths)
+        && flip_self_edges(&input, &mut flip_graph, &paths)
+}
+
+#[test]
+fn packedgraph_divide_flip_self_edges() {
+    QuickCheck::new()
+        .tests(500)
+        .quickcheck(packedgraph_self_edges_prop as fn(SelfEdgeGraph) -> bool);
+}
+
+#[test]
+fn hashgraph_divide_flip_self_edges() {
+    QuickCheck::new()
+        .tests(500)
+        .quickcheck(hashgraph_self_edges_prop as fn(SelfEdgeGraph) -> bool);
+}
@@ -23,12 +23,9 @@ use crate::packedgraph::{
     PackedGraph,
 };
 
-use super::DeltaEq;
-use super::GraphOpDelta;
+use crate::transaction::GraphOp;
 
-pub trait GraphApply {
-    fn apply(&self, graph: &mut PackedGraph);
-}
+use super::GraphOpDelta;
 
 pub trait DeriveDelta {
     fn derive_compose(
@@ -38,14 +35,30 @@ pub trait DeriveDelta {
     ) -> GraphOpDelta;
 
     fn derive_delta(&self, graph: &PackedGraph, count: usize) -> GraphOpDelta {
-        let mut delta = GraphOpDelta::default();
-        delta.count = count;
+        let delta = GraphOpDelta {
+            count,
+            ..GraphOpDelta::default()
+        };
         self.derive_compose(graph, delta)
     }
 }
 
-pub trait GraphDelta: Sized + Clone {
-    fn compose(self, rhs: Self) -> Self;
-
-    fn into_graph_delta(self) -> GraphOpDelta;
+/// The delta is derived by applying the operation to a copy of the
+/// graph; operations that fail produce an empty delta.
+impl DeriveDelta for GraphOp {
+    fn derive_compose(
+        &self,
+        graph: &PackedGraph,
+        lhs: GraphOpDelta,
+    ) -> GraphOpDelta {
+        let empty = GraphOpDelta {
+            count: lhs.count,
+            ..GraphOpDelta::default()
+        };
+        let mut delta = empty.clone();
+        if self.apply(&mut graph.clone(), &mut delta).is_err() {
+            delta = empty;
+        }
+        lhs.compose(delta)
+    }
 }
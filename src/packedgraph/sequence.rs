@@ -17,13 +17,6 @@ pub struct SeqRecordIx(usize);
 
 crate::impl_space_usage_stack_newtype!(SeqRecordIx);
 
-impl SeqRecordIx {
-    #[inline]
-    fn new<I: Into<usize>>(x: I) -> Self {
-        Self(x.into())
-    }
-}
-
 impl RecordIndex for SeqRecordIx {
     const RECORD_WIDTH: usize = 1;
 
@@ -76,7 +69,7 @@ impl Sequences {
     }
 
     #[inline]
-    fn set_record(
+    pub(super) fn set_record(
         &mut self,
         seq_ix: SeqRecordIx,
         offset: usize,
@@ -108,15 +101,6 @@ impl Sequences {
         self.removed_records.push(seq_ix);
     }
 
-    #[inline]
-    fn append_record(&mut self, offset: usize, length: usize) -> SeqRecordIx {
-        let seq_ix = SeqRecordIx::new(self.lengths.len());
-
-        self.lengths.append(length as u64);
-        self.offsets.append(offset as u64);
-        seq_ix
-    }
-
     /// Adds a sequence and updates the sequence records for the
     /// provided `NodeRecordId` to the correct length and offset.
     #[inline]
@@ -148,7 +132,7 @@ impl Sequences {
     ) {
         let seq_ix = SeqRecordIx::from_one_based_ix(rec_id).unwrap();
 
-        let (old_len, offset) = self.get_record(seq_ix);
+        let (offset, old_len) = self.get_record(seq_ix);
 
         assert!(old_len == seq.len());
 
@@ -167,15 +151,16 @@ impl Sequences {
     /// record will be added with the missing length.
     ///
     /// Returns `None` if the `lengths` slice is somehow incorrect,
-    /// otherwise returns the new indices of the new sequence records.
-    /// New nodes/graph records *must* be added to match the new
-    /// sequence records.
+    /// otherwise returns the offsets and lengths of the new
+    /// sequences. No new sequence records are added, as they must
+    /// match the node records; the new sequences should be assigned
+    /// to new nodes with `set_record`.
     #[must_use]
     pub(super) fn split_sequence(
         &mut self,
         seq_ix: SeqRecordIx,
         lengths: &[usize],
-    ) -> Option<Vec<SeqRecordIx>> {
+    ) -> Option<Vec<(usize, usize)>> {
         let (seq_offset, seq_len) = self.get_record(seq_ix);
 
         let lengths_sum: usize = lengths.iter().sum();
@@ -198,14 +183,12 @@ impl Sequences {
         let mut offset = seq_offset + lengths[0];
 
         for &len in lengths.iter().skip(1) {
-            let new_seq_ix = self.append_record(offset, len);
-            results.push(new_seq_ix);
+            results.push((offset, len));
             offset += len;
         }
 
         if extra_record > 0 {
-            let new_seq_ix = self.append_record(offset, extra_record);
-            results.push(new_seq_ix);
+            results.push((offset, extra_record));
         }
 
         Some(results)
@@ -316,12 +299,54 @@ mod tests {
 
         let lens = vec![6, 3, 4];
 
-        let seq_indices = seqs.split_sequence(s0, &lens).unwrap();
+        let new_seqs = seqs.split_sequence(s0, &lens).unwrap();
 
         assert_eq!(B("GTCCAC"), seq_bstr(&seqs, s0));
 
-        assert_eq!(B("TTT"), seq_bstr(&seqs, seq_indices[0]));
-        assert_eq!(B("GTGT"), seq_bstr(&seqs, seq_indices[1]));
+        let g1 = NodeRecordId::unpack(2);
+        let g2 = NodeRecordId::unpack(3);
+        seqs.append_empty_record();
+        seqs.append_empty_record();
+
+        let s1 = SeqRecordIx::from_one_based_ix(g1).unwrap();
+        let s2 = SeqRecordIx::from_one_based_ix(g2).unwrap();
+
+        let (offset, len) = new_seqs[0];
+        seqs.set_record(s1, offset, len);
+        let (offset, len) = new_seqs[1];
+        seqs.set_record(s2, offset, len);
+
+        assert_eq!(B("TTT"), seq_bstr(&seqs, s1));
+        assert_eq!(B("GTGT"), seq_bstr(&seqs, s2));
+    }
+
+    #[test]
+    fn overwrite_sequence_in_place() {
+        use bstr::{BString, B};
+        let mut seqs = Sequences::default();
+        let g0 = NodeRecordId::unpack(1);
+        let g1 = NodeRecordId::unpack(2);
+        let g2 = NodeRecordId::unpack(3);
+        seqs.append_empty_record();
+        seqs.append_empty_record();
+        seqs.append_empty_record();
+
+        let s0 = seqs.add_sequence(g0, b"GTCCACTTTGTGT").unwrap();
+        let s1 = seqs.add_sequence(g1, b"GTCCAGT").unwrap();
+        let s2 = seqs.add_sequence(g2, b"CACGCTGT").unwrap();
+
+        let seq_bstr = |sq: &Sequences, ix: SeqRecordIx| -> BString {
+            sq.iter(ix, false).collect()
+        };
+
+        // the new sequence must be written at the record's offset,
+        // not its length
+        seqs.overwrite_sequence(g1, b"AAAAAAA");
+
+        assert_eq!(B("GTCCACTTTGTGT"), seq_bstr(&seqs, s0));
+        assert_eq!(B("AAAAAAA"), seq_bstr(&seqs, s1));
+        assert_eq!(B("CACGCTGT"), seq_bstr(&seqs, s2));
+        assert_eq!(seqs.get_record(s1), (13, 7));
     }
 
     #[test]
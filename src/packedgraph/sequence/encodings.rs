@@ -221,7 +221,7 @@ impl EncodedSequence {
                     x if x == 2 || x == 5 => {
                         let pair =
                             [self.vec[byte_index], self.vec[byte_index + 1]];
-                        u16::from_le_bytes(pair)
+                        u16::from_be_bytes(pair)
                     }
                     _ => self.vec[byte_index] as u16,
                 };
@@ -378,31 +378,24 @@ impl EncodedSequence {
                 let index_base = 3 * (index >> 3);
                 let byte_index = index_base + index_offset as usize;
 
-                let enc_base = DNA_BASE_3BIT_ENCODING[base as usize];
-
-                let len_mod_8 = self.len % 8;
-                let mask = APPEND_MASK_3BITS_U8[(len_mod_8 as u8) as usize];
-                let shift = SHIFT_OFFSET_3BITS_U8[(len_mod_8 as u8) as usize];
+                let enc_base = DNA_BASE_3BIT_ENCODING[base as usize] as u16;
+                let shift = SHIFT_OFFSET_3BITS_U8[((index % 8) as u8) as usize];
+                let mask = !(0x07u16 << shift);
 
-                match len_mod_8 {
+                match index % 8 {
                     x if x == 2 || x == 5 => {
-                        let bytes = &mut self.vec[byte_index..byte_index + 1];
-
-                        bytes[0] &= mask | (enc_base >> shift);
-
-                        let right_len_mod_8 = (len_mod_8 + 1) % 8;
-
-                        let shift = SHIFT_OFFSET_3BITS_U8
-                            [(right_len_mod_8 as u8) as usize];
-
-                        let mask = APPEND_MASK_3BITS_U8
-                            [(right_len_mod_8 as u8) as usize];
-
-                        bytes[1] &= mask | (enc_base << shift);
+                        let pair =
+                            [self.vec[byte_index], self.vec[byte_index + 1]];
+                        let bytes = u16::from_be_bytes(pair);
+                        let bytes = (bytes & mask) | (enc_base << shift);
+                        let [high, low] = bytes.to_be_bytes();
+                        self.vec[byte_index] = high;
+                        self.vec[byte_index + 1] = low;
                     }
                     _ => {
                         let byte = &mut self.vec[byte_index];
-                        *byte &= mask | (enc_base << shift);
+                        *byte =
+                            (*byte & mask as u8) | (enc_base << shift) as u8;
                     }
                 }
             }
@@ -825,6 +818,55 @@ mod tests {
         assert_eq!(encoded_seqs.len(), 27);
     }
 
+    #[test]
+    fn bytevec_3bit_get_base() {
+        // bases 2 and 5 of every group of 8 are split across two
+        // bytes, with the high bits in the first
+        let seq = b"ACGTNTGCAAGTCCTAGCAN";
+
+        let mut encoded_seqs = EncodedSequence::new_3bits();
+        encoded_seqs.append_seq(seq);
+
+        for (ix, &base) in seq.iter().enumerate() {
+            assert_eq!(encoded_seqs.get_base(ix, false), Some(base));
+        }
+
+        let decoded =
+            encoded_seqs.iter(0, seq.len(), false).collect::<Vec<_>>();
+        assert_eq!(&decoded, seq);
+    }
+
+    #[test]
+    fn bytevec_3bit_write_base() {
+        let seq = b"CAGTNAAGTGCTAGTAGTTTAACTNGA";
+
+        let mut encoded_seqs = EncodedSequence::new_3bits();
+        encoded_seqs.append_seq(seq);
+
+        let bases = (0..seq.len())
+            .map(|ix| encoded_seqs.get_base(ix, false).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(&bases, seq);
+
+        // overwrite every position, including the bases that are
+        // split across two bytes
+        let new_seq = b"TTCAGGANNACGTTGCAAGTCCTAGCA";
+        encoded_seqs.rewrite_section(0, new_seq);
+
+        let bases = (0..seq.len())
+            .map(|ix| encoded_seqs.get_base(ix, false).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(&bases, new_seq);
+
+        let decoded =
+            encoded_seqs.iter(0, seq.len(), false).collect::<Vec<_>>();
+        assert_eq!(&decoded, new_seq);
+
+        encoded_seqs.rewrite_section(5, b"C");
+        let decoded = encoded_seqs.iter(4, 3, false).collect::<Vec<_>>();
+        assert_eq!(&decoded, b"GCA");
+    }
+
     #[test]
     fn bytevec_4bit_encoding() {
         use bstr::{ByteSlice, B};
@@ -20,9 +20,10 @@ pub struct PathPositionMap {
 impl PathPositionMap {
     /// Build a `PathPositionMap` index from a `PackedGraph`.
     ///
-    /// IMPORTANT: Assumes that each path in the graph has been
-    /// constructed by appending steps in order, and that no steps
-    /// have been deleted!
+    /// The positions are stored by step index, along with the order
+    /// of the steps on each path, so paths that have had steps
+    /// inserted or removed are also indexed correctly. The index must
+    /// be rebuilt if the paths are modified.
     pub fn index_paths(graph: &PackedGraph) -> Self {
         let mut paths: Vec<PathPositionIndex> =
             Vec::with_capacity(graph.path_count());
@@ -37,10 +38,15 @@ impl PathPositionMap {
             let mut last_step_offset = 0usize;
 
             if let Some(steps) = graph.path_steps(path_id) {
-                for (_step_ix, step) in steps {
+                for (step_ix, step) in steps {
                     let seq_len = graph.sequence(step.handle).count();
 
-                    path_index.step_positions.append(pos_offset as u64);
+                    let ix = step_ix.to_zero_based().unwrap();
+                    if ix >= path_index.step_positions.len() {
+                        path_index.step_positions.resize(ix + 1);
+                    }
+                    path_index.step_positions.set(ix, pos_offset as u64);
+                    path_index.step_order.append(ix as u64);
 
                     last_step_offset = pos_offset;
                     pos_offset += seq_len;
@@ -70,9 +76,18 @@ impl PathPositionMap {
 
         let step_ix = step.to_zero_based()?;
 
+        if step_ix >= path_indices.step_positions.len() {
+            return None;
+        }
+
         Some(path_indices.step_positions.get(step_ix) as usize)
     }
 
+    /// Find the step on `path` that covers the base at `base_pos`.
+    ///
+    /// Positions are 1-based, consistent with the positions returned
+    /// by `path_step_position`. Returns `None` if the path doesn't
+    /// exist or the position is outside the path.
     pub fn find_step_at_base(
         &self,
         path: PathId,
@@ -80,48 +95,37 @@ impl PathPositionMap {
     ) -> Option<StepPtr> {
         let path_indices = self.paths.get(path.0 as usize)?;
 
-        if base_pos >= path_indices.last_step_offset {
-            if base_pos > path_indices.base_len {
-                return None;
-            } else {
-                return Some(StepPtr::from_zero_based(
-                    path_indices.step_positions.len(),
-                ));
-            }
-        }
+        let positions = &path_indices.step_positions;
+        let steps = &path_indices.step_order;
+        let step_count = steps.len();
 
-        let step_count = path_indices.step_positions.len();
+        if step_count == 0 || base_pos == 0 || base_pos >= path_indices.base_len
+        {
+            return None;
+        }
 
-        let steps = &path_indices.step_positions;
+        if base_pos >= path_indices.last_step_offset {
+            let last = steps.get(step_count - 1) as usize;
+            return Some(StepPtr::from_zero_based(last));
+        }
 
+        // find the last step in path order that starts at or before
+        // `base_pos`; the first step always starts at 1, so `left` is
+        // always valid
         let mut left = 0;
-        let mut right = step_count - 1;
-        let mut mid;
-
-        loop {
-            if left > right {
-                return None;
-            }
-
-            mid = (left + right) / 2;
-
-            let at_mid = steps.get(mid) as usize;
-            let at_next = if mid == step_count {
-                path_indices.base_len
-            } else {
-                steps.get(mid + 1) as usize
-            };
+        let mut right = step_count;
 
-            if at_mid < base_pos {
-                left = mid + 1;
-            } else if at_next > base_pos {
-                right = mid - 1;
+        while right - left > 1 {
+            let mid = (left + right) / 2;
+            let step_pos = positions.get(steps.get(mid) as usize);
+            if step_pos as usize <= base_pos {
+                left = mid;
             } else {
-                break;
+                right = mid;
             }
         }
 
-        Some(StepPtr::from_zero_based(mid))
+        Some(StepPtr::from_zero_based(steps.get(left) as usize))
     }
 
     pub fn handle_positions(
@@ -196,7 +200,11 @@ impl PathPositionMap {
 
 #[derive(Debug, Clone)]
 pub struct PathPositionIndex {
+    /// The position of each step, indexed by the zero-based step
+    /// index.
     pub(crate) step_positions: RobustPagedIntVec,
+    /// The zero-based step indices, in path order.
+    pub(crate) step_order: RobustPagedIntVec,
     pub(crate) base_len: usize,
     pub(crate) last_step_offset: usize,
 }
@@ -205,6 +213,7 @@ impl std::default::Default for PathPositionIndex {
     fn default() -> Self {
         Self {
             step_positions: RobustPagedIntVec::new(NARROW_PAGE_WIDTH),
+            step_order: RobustPagedIntVec::new(NARROW_PAGE_WIDTH),
             base_len: 0usize,
             last_step_offset: 0usize,
         }
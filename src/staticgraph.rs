@@ -0,0 +1,956 @@
+/*!
+
+An immutable, succinct handlegraph, in the spirit of vg's XG.
+
+A [`StaticGraph`] is built once from another graph, and can then only
+be queried. In exchange, all of its data is stored in a handful of
+packed vectors, with bit vectors supporting rank and select queries
+([`bits::RankSelectBits`]) used to find the start and end of each
+node's sequence, edge list, and path occurrences. The sequence
+positions of path steps are sampled, see [`paths`].
+
+Node IDs are mapped to contiguous node indices by ranking a bit
+vector over the range of IDs in the graph, so graphs with very sparse
+IDs should be compacted before conversion.
+
+*/
+
+use crate::{
+    handle::{Direction, Edge, Handle, NodeId},
+    handlegraph::*,
+    packed::*,
+    packedgraph::sequence::{DecodeIter, EncodedSequence},
+    pathhandlegraph::{
+        GraphPathNames, GraphPaths, GraphPathsRef, GraphPathsSteps,
+        IntoNodeOccurrences, IntoPathIds, PathId, PathSequences, PathStep,
+    },
+};
+
+use rayon::prelude::*;
+
+use succinct::{BitVecPush, BitVector};
+
+pub mod bits;
+pub mod paths;
+
+use self::bits::RankSelectBits;
+use self::paths::{OccurrencesIter, PathData, StaticPath, StaticPaths};
+
+pub use self::paths::{Step, StepIx, StepsIter};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace};
+
+#[derive(Debug, Clone)]
+pub struct StaticGraph {
+    min_id: u64,
+    max_id: u64,
+    /// One bit per ID in `min_id..=max_id`, set if the node exists.
+    node_ids: RankSelectBits,
+    /// The forward sequences of all nodes, in node index order.
+    sequence: EncodedSequence,
+    /// Unary encoded sequence length of each node.
+    seq_bounds: RankSelectBits,
+    /// Unary encoded number of edges on the right hand side of each
+    /// handle, indexed by `2 * node_index + is_reverse`.
+    edge_bounds: RankSelectBits,
+    edge_targets: PackedIntVec,
+    edge_count: usize,
+    paths: StaticPaths,
+}
+
+crate::impl_space_usage!(
+    StaticGraph,
+    [
+        node_ids,
+        sequence,
+        seq_bounds,
+        edge_bounds,
+        edge_targets,
+        paths
+    ]
+);
+
+impl Default for StaticGraph {
+    fn default() -> Self {
+        Self {
+            min_id: 0,
+            max_id: 0,
+            node_ids: Default::default(),
+            sequence: EncodedSequence::new_3bits(),
+            seq_bounds: RankSelectBits::from_lengths(std::iter::empty()),
+            edge_bounds: RankSelectBits::from_lengths(std::iter::empty()),
+            edge_targets: Default::default(),
+            edge_count: 0,
+            paths: Default::default(),
+        }
+    }
+}
+
+impl StaticGraph {
+    /// Build a `StaticGraph` containing all nodes, edges, and paths
+    /// of `graph`. Path IDs are renumbered to `0..path_count`, in the
+    /// order of their IDs in `graph`.
+    pub fn from_graph<G>(graph: G) -> Self
+    where
+        G: HandleGraphRef + GraphPathsSteps + GraphPathNames + IntoPathIds,
+    {
+        let mut node_ids = graph.handles().map(|h| h.id()).collect::<Vec<_>>();
+        node_ids.sort_unstable();
+        node_ids.dedup();
+
+        let (min_id, max_id) = match (node_ids.first(), node_ids.last()) {
+            (Some(min), Some(max)) => (u64::from(*min), u64::from(*max)),
+            _ => return Self::default(),
+        };
+
+        let mut id_bits: BitVector<u64> = BitVector::new();
+        let mut next_id = min_id;
+        for &id in node_ids.iter() {
+            let id = u64::from(id);
+            while next_id < id {
+                id_bits.push_bit(false);
+                next_id += 1;
+            }
+            id_bits.push_bit(true);
+            next_id += 1;
+        }
+
+        let node_index = |handle: Handle| -> usize {
+            node_ids.binary_search(&handle.id()).unwrap()
+        };
+
+        let mut sequence = EncodedSequence::new_3bits();
+        let mut lengths = Vec::with_capacity(node_ids.len());
+        for &id in node_ids.iter() {
+            let seq = graph.sequence_vec(Handle::pack(id, false));
+            lengths.push(seq.len());
+            if !seq.is_empty() {
+                sequence.append_seq(&seq);
+            }
+        }
+
+        let mut edges = graph
+            .edges()
+            .map(|Edge(l, r)| Edge::edge_handle(l, r))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges.dedup();
+
+        let mut adjacency: Vec<Vec<Handle>> =
+            vec![Vec::new(); node_ids.len() * 2];
+        let slot = |h: Handle| 2 * node_index(h) + h.is_reverse() as usize;
+
+        for &Edge(left, right) in edges.iter() {
+            adjacency[slot(left)].push(right);
+            if (right.flip(), left.flip()) != (left, right) {
+                adjacency[slot(right.flip())].push(left.flip());
+            }
+        }
+
+        let edge_bounds =
+            RankSelectBits::from_lengths(adjacency.iter().map(|a| a.len()));
+        let mut edge_targets = PackedIntVec::new();
+        for targets in adjacency.iter_mut() {
+            targets.sort_unstable();
+            for &handle in targets.iter() {
+                edge_targets.append(handle.as_integer());
+            }
+        }
+
+        let mut path_ids = graph.path_ids().collect::<Vec<_>>();
+        path_ids.sort_unstable();
+
+        let paths = path_ids
+            .into_iter()
+            .filter_map(|id| {
+                let name = graph.get_path_name_vec(id)?;
+                let circular = graph.path_circular(id)?;
+                let steps = graph.path_steps(id)?.map(|s| s.handle()).collect();
+                Some(PathData {
+                    name,
+                    circular,
+                    steps,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let paths = StaticPaths::new(paths, node_ids.len(), node_index, |h| {
+            lengths[node_index(h)]
+        });
+
+        debug!(
+            "built static graph with {} nodes, {} edges, {} paths",
+            node_ids.len(),
+            edges.len(),
+            paths.path_count()
+        );
+
+        Self {
+            min_id,
+            max_id,
+            node_ids: RankSelectBits::new(id_bits),
+            sequence,
+            seq_bounds: RankSelectBits::from_lengths(lengths),
+            edge_bounds,
+            edge_targets,
+            edge_count: edges.len(),
+            paths,
+        }
+    }
+
+    /// Return the index of the node `id`, in `0..node_count`, if it
+    /// exists. Node indices are ordered by ID.
+    #[inline]
+    pub fn node_index<I: Into<NodeId>>(&self, id: I) -> Option<usize> {
+        let id = u64::from(id.into());
+        if id < self.min_id || id > self.max_id || self.node_ids.is_empty() {
+            return None;
+        }
+        let bit = (id - self.min_id) as usize;
+        if self.node_ids.get(bit) {
+            Some(self.node_ids.rank1(bit))
+        } else {
+            None
+        }
+    }
+
+    /// Return the ID of the node with index `ix`, if it exists.
+    #[inline]
+    pub fn index_node_id(&self, ix: usize) -> Option<NodeId> {
+        let bit = self.node_ids.select1(ix)?;
+        Some(NodeId::from(self.min_id + bit as u64))
+    }
+
+    #[inline]
+    fn handle_slot(&self, handle: Handle) -> Option<usize> {
+        let ix = self.node_index(handle.id())?;
+        Some(2 * ix + handle.is_reverse() as usize)
+    }
+
+    /// Return the offset of the forward sequence of the node `id` in
+    /// the concatenation of all node sequences, in node index order.
+    #[inline]
+    pub fn node_offset<I: Into<NodeId>>(&self, id: I) -> Option<usize> {
+        let ix = self.node_index(id)?;
+        self.seq_bounds.unary_start(ix)
+    }
+
+    /// Find the node covering position `pos` in the concatenation of
+    /// all node sequences, returning the forward handle and the
+    /// offset of `pos` in the node.
+    #[inline]
+    pub fn node_at_offset(&self, pos: usize) -> Option<(Handle, usize)> {
+        let ix = self.seq_bounds.unary_entry(pos)?;
+        let start = self.seq_bounds.unary_start(ix)?;
+        let id = self.index_node_id(ix)?;
+        Some((Handle::pack(id, false), pos - start))
+    }
+
+    /// Return the sequence offset of the step `index` on path `id`,
+    /// counting from the start of the first path, along with the
+    /// offset of the first step of path `id`.
+    #[inline]
+    fn path_base_offsets(
+        &self,
+        id: PathId,
+        index: usize,
+    ) -> Option<(usize, usize)> {
+        let range = self.paths.step_range(id)?;
+        if index > range.len() {
+            return None;
+        }
+        let node_len = |h| self.node_len(h);
+        let path_start = self.paths.global_base_offset(range.start, node_len);
+        let step_start =
+            self.paths.global_base_offset(range.start + index, node_len);
+        Some((path_start, step_start))
+    }
+}
+
+impl HandleGraph for StaticGraph {
+    #[inline]
+    fn min_node_id(&self) -> NodeId {
+        self.min_id.into()
+    }
+
+    #[inline]
+    fn max_node_id(&self) -> NodeId {
+        self.max_id.into()
+    }
+
+    #[inline]
+    fn node_count(&self) -> usize {
+        self.node_ids.count_ones()
+    }
+
+    #[inline]
+    fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    #[inline]
+    fn total_length(&self) -> usize {
+        self.sequence.len()
+    }
+}
+
+/// Iterator through the handles of a `StaticGraph`, in ID order.
+pub struct StaticHandles<'a> {
+    node_ids: &'a RankSelectBits,
+    min_id: u64,
+    bit: usize,
+}
+
+impl<'a> Iterator for StaticHandles<'a> {
+    type Item = Handle;
+
+    #[inline]
+    fn next(&mut self) -> Option<Handle> {
+        while self.bit < self.node_ids.len() {
+            let bit = self.bit;
+            self.bit += 1;
+            if self.node_ids.get(bit) {
+                let id = self.min_id + bit as u64;
+                return Some(Handle::pack(id, false));
+            }
+        }
+        None
+    }
+}
+
+impl<'a> IntoHandles for &'a StaticGraph {
+    type Handles = StaticHandles<'a>;
+
+    #[inline]
+    fn handles(self) -> Self::Handles {
+        StaticHandles {
+            node_ids: &self.node_ids,
+            min_id: self.min_id,
+            bit: 0,
+        }
+    }
+
+    #[inline]
+    fn has_node<I: Into<NodeId>>(self, n_id: I) -> bool {
+        self.node_index(n_id).is_some()
+    }
+}
+
+impl<'a> IntoHandlesPar for &'a StaticGraph {
+    type HandlesPar = rayon::iter::IterBridge<StaticHandles<'a>>;
+
+    #[inline]
+    fn handles_par(self) -> Self::HandlesPar {
+        self.handles().par_bridge()
+    }
+}
+
+/// Iterator through the neighbors of a handle, stored as a range in
+/// the edge targets vector.
+pub struct StaticNeighbors<'a> {
+    targets: &'a PackedIntVec,
+    range: std::ops::Range<usize>,
+    flip: bool,
+}
+
+impl<'a> Iterator for StaticNeighbors<'a> {
+    type Item = Handle;
+
+    #[inline]
+    fn next(&mut self) -> Option<Handle> {
+        let ix = self.range.next()?;
+        let handle = Handle::from_integer(self.targets.get(ix));
+        if self.flip {
+            Some(handle.flip())
+        } else {
+            Some(handle)
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for StaticNeighbors<'a> {}
+
+impl<'a> IntoNeighbors for &'a StaticGraph {
+    type Neighbors = StaticNeighbors<'a>;
+
+    #[inline]
+    fn neighbors(self, handle: Handle, dir: Direction) -> Self::Neighbors {
+        let (handle, flip) = match dir {
+            Direction::Right => (handle, false),
+            Direction::Left => (handle.flip(), true),
+        };
+
+        let slot = self.handle_slot(handle).unwrap_or_else(|| {
+            panic!(
+                "tried to get neighbors of node {} which doesn't exist",
+                handle.id().0
+            )
+        });
+
+        let range = self.edge_bounds.unary_range(slot).unwrap();
+
+        StaticNeighbors {
+            targets: &self.edge_targets,
+            range,
+            flip,
+        }
+    }
+
+    #[inline]
+    fn degree(self, handle: Handle, dir: Direction) -> usize {
+        self.neighbors(handle, dir).len()
+    }
+}
+
+/// Iterator through the edges of a `StaticGraph`, producing each
+/// edge once, in its canonical orientation.
+pub struct StaticEdges<'a> {
+    graph: &'a StaticGraph,
+    handles: StaticHandles<'a>,
+    current: Option<(Handle, StaticNeighbors<'a>)>,
+}
+
+impl<'a> Iterator for StaticEdges<'a> {
+    type Item = Edge;
+
+    fn next(&mut self) -> Option<Edge> {
+        loop {
+            if let Some((left, neighbors)) = self.current.as_mut() {
+                for right in neighbors {
+                    let edge = Edge(*left, right);
+                    if Edge::edge_handle(*left, right) == edge {
+                        return Some(edge);
+                    }
+                }
+            }
+
+            let next_left = match self.current {
+                Some((left, _)) if !left.is_reverse() => left.flip(),
+                _ => self.handles.next()?,
+            };
+
+            let neighbors = self.graph.neighbors(next_left, Direction::Right);
+            self.current = Some((next_left, neighbors));
+        }
+    }
+}
+
+impl<'a> IntoEdges for &'a StaticGraph {
+    type Edges = StaticEdges<'a>;
+
+    #[inline]
+    fn edges(self) -> Self::Edges {
+        StaticEdges {
+            graph: self,
+            handles: self.handles(),
+            current: None,
+        }
+    }
+}
+
+impl<'a> IntoEdgesPar for &'a StaticGraph {
+    type EdgesPar = rayon::iter::IterBridge<StaticEdges<'a>>;
+
+    #[inline]
+    fn edges_par(self) -> Self::EdgesPar {
+        self.edges().par_bridge()
+    }
+}
+
+/// Iterator on the sequence of a single node.
+pub struct StaticSequence<'a> {
+    iter: Option<DecodeIter<'a>>,
+}
+
+impl<'a> Iterator for StaticSequence<'a> {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        self.iter.as_mut()?.next()
+    }
+}
+
+impl<'a> IntoSequences for &'a StaticGraph {
+    type Sequence = StaticSequence<'a>;
+
+    #[inline]
+    fn sequence(self, handle: Handle) -> Self::Sequence {
+        let ix = self.node_index(handle.id()).unwrap();
+        let range = self.seq_bounds.unary_range(ix).unwrap();
+        let iter = if range.is_empty() {
+            None
+        } else {
+            Some(self.sequence.iter(
+                range.start,
+                range.len(),
+                handle.is_reverse(),
+            ))
+        };
+        StaticSequence { iter }
+    }
+
+    #[inline]
+    fn node_len(self, handle: Handle) -> usize {
+        let ix = self.node_index(handle.id()).unwrap();
+        self.seq_bounds.unary_range(ix).unwrap().len()
+    }
+}
+
+impl GraphPaths for StaticGraph {
+    type StepIx = StepIx;
+
+    #[inline]
+    fn path_count(&self) -> usize {
+        self.paths.path_count()
+    }
+
+    #[inline]
+    fn path_len(&self, id: PathId) -> Option<usize> {
+        Some(self.paths.step_range(id)?.len())
+    }
+
+    #[inline]
+    fn path_circular(&self, id: PathId) -> Option<bool> {
+        self.paths.circular(id)
+    }
+
+    #[inline]
+    fn path_handle_at_step(
+        &self,
+        id: PathId,
+        index: Self::StepIx,
+    ) -> Option<Handle> {
+        let range = self.paths.step_range(id)?;
+        if index.0 < range.len() {
+            Some(self.paths.handle_at(range.start + index.0))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn path_first_step(&self, id: PathId) -> Option<Self::StepIx> {
+        let range = self.paths.step_range(id)?;
+        if range.is_empty() {
+            None
+        } else {
+            Some(StepIx(0))
+        }
+    }
+
+    #[inline]
+    fn path_last_step(&self, id: PathId) -> Option<Self::StepIx> {
+        let range = self.paths.step_range(id)?;
+        let last = range.len().checked_sub(1)?;
+        Some(StepIx(last))
+    }
+
+    #[inline]
+    fn path_next_step(
+        &self,
+        id: PathId,
+        index: Self::StepIx,
+    ) -> Option<Self::StepIx> {
+        let range = self.paths.step_range(id)?;
+        let next = index.0 + 1;
+        if next < range.len() {
+            Some(StepIx(next))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn path_prev_step(
+        &self,
+        id: PathId,
+        index: Self::StepIx,
+    ) -> Option<Self::StepIx> {
+        let range = self.paths.step_range(id)?;
+        if index.0 > 0 && index.0 <= range.len() {
+            Some(StepIx(index.0 - 1))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> GraphPathNames for &'a StaticGraph {
+    type PathName = std::iter::Copied<std::slice::Iter<'a, u8>>;
+
+    #[inline]
+    fn get_path_id(self, name: &[u8]) -> Option<PathId> {
+        self.paths.find_name(name)
+    }
+
+    #[inline]
+    fn get_path_name(self, id: PathId) -> Option<Self::PathName> {
+        let name = self.paths.name(id)?;
+        Some(name.iter().copied())
+    }
+}
+
+impl IntoPathIds for &StaticGraph {
+    type PathIds = std::iter::Map<std::ops::Range<u64>, fn(u64) -> PathId>;
+
+    #[inline]
+    fn path_ids(self) -> Self::PathIds {
+        let count = self.paths.path_count() as u64;
+        (0..count).map(PathId)
+    }
+}
+
+impl<'a> IntoNodeOccurrences for &'a StaticGraph {
+    type Occurrences = OccurrencesIter<'a>;
+
+    #[inline]
+    fn steps_on_handle(self, handle: Handle) -> Option<Self::Occurrences> {
+        let ix = self.node_index(handle.id())?;
+        Some(self.paths.occurrences(ix))
+    }
+}
+
+impl<'a> GraphPathsRef for &'a StaticGraph {
+    type PathRef = StaticPath<'a>;
+
+    #[inline]
+    fn get_path_ref(self, id: PathId) -> Option<Self::PathRef> {
+        self.paths.path_ref(id)
+    }
+}
+
+impl<'a> GraphPathsSteps for &'a StaticGraph {
+    type Step = Step;
+    type Steps = StepsIter<'a>;
+
+    #[inline]
+    fn path_steps(self, id: PathId) -> Option<Self::Steps> {
+        use crate::pathhandlegraph::PathSteps;
+        Some(self.paths.path_ref(id)?.steps())
+    }
+
+    #[inline]
+    fn path_steps_range(
+        self,
+        id: PathId,
+        from: Self::StepIx,
+        to: Self::StepIx,
+    ) -> Option<Self::Steps> {
+        let path = self.paths.path_ref(id)?;
+        Some(path.steps_range(from, to))
+    }
+}
+
+impl PathSequences for StaticGraph {
+    #[inline]
+    fn path_bases_len(&self, id: PathId) -> Option<usize> {
+        let len = self.path_len(id)?;
+        let (start, end) = self.path_base_offsets(id, len)?;
+        Some(end - start)
+    }
+
+    fn path_step_at_base(
+        &self,
+        id: PathId,
+        pos: usize,
+    ) -> Option<Self::StepIx> {
+        let range = self.paths.step_range(id)?;
+        let (start, end) = self.path_base_offsets(id, range.len())?;
+        if start + pos >= end {
+            return None;
+        }
+        let global_ix = self
+            .paths
+            .global_step_at_base(start + pos, |h| self.node_len(h))?;
+        Some(StepIx(global_ix - range.start))
+    }
+
+    #[inline]
+    fn path_step_base_offset(
+        &self,
+        id: PathId,
+        index: Self::StepIx,
+    ) -> Option<usize> {
+        let len = self.path_len(id)?;
+        if index.0 >= len {
+            return None;
+        }
+        let (start, step_start) = self.path_base_offsets(id, index.0)?;
+        Some(step_start - start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hashgraph::HashGraph;
+    use crate::mutablehandlegraph::*;
+    use crate::packedgraph::PackedGraph;
+    use crate::pathhandlegraph::*;
+    use crate::util::test::{add_path, hnd, small_graph};
+
+    use fnv::FnvHashSet;
+
+    fn build_graph<G>() -> G
+    where
+        G: Default + AdditiveHandleGraph + MutableGraphPaths,
+    {
+        let mut graph: G = small_graph();
+
+        // leave a gap in the node IDs
+        let mut hs = vec![hnd(1), hnd(2), hnd(3)];
+        let seqs: [&[u8]; 3] = [b"C", b"GTTA", b"NA"];
+        for (ix, seq) in seqs.iter().enumerate() {
+            hs.push(graph.create_handle(seq, ix as u64 + 6));
+        }
+
+        let edges = [
+            (hs[0], hs[2].flip()),
+            (hs[1], hs[3]),
+            (hs[2].flip(), hs[3]),
+            (hs[3], hs[4]),
+            (hs[4], hs[4].flip()),
+            (hs[4], hs[5]),
+            (hs[5], hs[5]),
+        ];
+        for &(l, r) in edges.iter() {
+            graph.create_edge(Edge(l, r));
+        }
+
+        add_path(&mut graph, b"x", &[hs[0], hs[1], hs[3], hs[4], hs[5]]);
+        let a = add_path(&mut graph, b"a", &[hs[0], hs[2].flip(), hs[3]]);
+        graph.path_set_circularity(a, true).unwrap();
+        add_path(
+            &mut graph,
+            b"rev",
+            &[hs[4].flip(), hs[3].flip(), hs[1].flip()],
+        );
+        add_path(&mut graph, b"empty", &[]);
+
+        graph
+    }
+
+    fn edge_set<G: IntoEdges>(graph: G) -> Vec<Edge> {
+        let mut edges = graph
+            .edges()
+            .map(|Edge(l, r)| Edge::edge_handle(l, r))
+            .collect::<Vec<_>>();
+        edges.sort();
+        edges.dedup();
+        edges
+    }
+
+    fn compare_graphs<G>(source: G, graph: &StaticGraph)
+    where
+        G: HandleGraphRef
+            + GraphPathsSteps
+            + GraphPathNames
+            + IntoPathIds
+            + IntoNodeOccurrences,
+    {
+        let mut handles = source.handles().collect::<Vec<_>>();
+        handles.sort();
+        assert_eq!(graph.handles().collect::<Vec<_>>(), handles);
+        assert_eq!(graph.node_count(), handles.len());
+
+        let edges = edge_set(source);
+        assert_eq!(edge_set(graph), edges);
+        assert_eq!(graph.edges().count(), edges.len());
+        assert_eq!(graph.edge_count(), edges.len());
+
+        let par_edges = graph.edges_par().collect::<FnvHashSet<_>>();
+        assert_eq!(par_edges.len(), edges.len());
+
+        for &handle in handles.iter() {
+            for &h in [handle, handle.flip()].iter() {
+                assert_eq!(graph.sequence_vec(h), source.sequence_vec(h));
+                assert_eq!(graph.node_len(h), source.node_len(h));
+
+                for &dir in [Direction::Left, Direction::Right].iter() {
+                    let mut expected =
+                        source.neighbors(h, dir).collect::<Vec<_>>();
+                    expected.sort();
+                    expected.dedup();
+                    let mut neighbors =
+                        graph.neighbors(h, dir).collect::<Vec<_>>();
+                    neighbors.sort();
+                    assert_eq!(neighbors, expected);
+                    assert_eq!(graph.degree(h, dir), expected.len());
+                }
+            }
+
+            let mut expected = source
+                .steps_on_handle(handle)
+                .unwrap()
+                .map(|(path, step)| {
+                    (path, source.path_handle_at_step(path, step).unwrap())
+                })
+                .collect::<Vec<_>>();
+            expected.sort();
+            expected.dedup();
+
+            let mut occurrences = graph
+                .steps_on_handle(handle)
+                .unwrap()
+                .map(|(path, step)| {
+                    (path, graph.path_handle_at_step(path, step).unwrap())
+                })
+                .collect::<Vec<_>>();
+            occurrences.sort();
+            occurrences.dedup();
+            assert_eq!(occurrences, expected);
+        }
+
+        assert_eq!(graph.path_count(), source.path_count());
+
+        for path in source.path_ids() {
+            let name = source.get_path_name_vec(path).unwrap();
+            assert_eq!(graph.get_path_id(&name), Some(path));
+            assert_eq!(graph.get_path_name_vec(path), Some(name));
+            assert_eq!(graph.path_circular(path), source.path_circular(path));
+
+            let steps = source
+                .path_steps(path)
+                .unwrap()
+                .map(|s| s.handle())
+                .collect::<Vec<_>>();
+            let static_steps = graph
+                .path_steps(path)
+                .unwrap()
+                .map(|s| s.handle())
+                .collect::<Vec<_>>();
+            assert_eq!(static_steps, steps);
+
+            let rev_steps = graph
+                .path_steps(path)
+                .unwrap()
+                .rev()
+                .map(|s| s.handle())
+                .collect::<Vec<_>>();
+            assert!(rev_steps.iter().eq(steps.iter().rev()));
+
+            assert_eq!(graph.path_len(path), Some(steps.len()));
+
+            let seq = steps
+                .iter()
+                .flat_map(|&h| source.sequence(h))
+                .collect::<Vec<_>>();
+
+            assert_eq!(graph.path_bases_len(path), Some(seq.len()));
+
+            let mut pos = 0;
+            for (ix, &handle) in steps.iter().enumerate() {
+                let len = graph.node_len(handle);
+                assert_eq!(
+                    graph.path_step_base_offset(path, StepIx(ix)),
+                    Some(pos)
+                );
+                for p in pos..pos + len {
+                    assert_eq!(
+                        graph.path_step_at_base(path, p),
+                        Some(StepIx(ix))
+                    );
+                }
+                pos += len;
+            }
+            assert_eq!(graph.path_step_at_base(path, pos), None);
+        }
+
+        assert_eq!(graph.get_path_id(b"missing"), None);
+    }
+
+    #[test]
+    fn static_from_packedgraph() {
+        let source: PackedGraph = build_graph();
+        let graph = StaticGraph::from_graph(&source);
+        compare_graphs(&source, &graph);
+
+        assert_eq!(graph.min_node_id(), NodeId::from(1));
+        assert_eq!(graph.max_node_id(), NodeId::from(8));
+        assert_eq!(graph.total_length(), source.total_length());
+    }
+
+    #[test]
+    fn static_from_hashgraph() {
+        let source: HashGraph = build_graph();
+        let graph = StaticGraph::from_graph(&source);
+        compare_graphs(&source, &graph);
+    }
+
+    #[test]
+    fn static_sampled_step_offsets() {
+        let mut source: PackedGraph = build_graph();
+        let handles = source.handles().collect::<Vec<_>>();
+
+        // paths that cross and end on the step offset samples
+        let lens = [paths::OFFSET_SAMPLE_RATE * 2 + 7, 3, 61];
+        for (ix, &len) in lens.iter().enumerate() {
+            let name = format!("long{}", ix);
+            let path = source.create_path(name.as_bytes(), false).unwrap();
+            for step in 0..len {
+                let handle = handles[(step * 5 + ix) % handles.len()];
+                source.path_append_step(path, handle);
+            }
+        }
+
+        let graph = StaticGraph::from_graph(&source);
+        compare_graphs(&source, &graph);
+    }
+
+    #[test]
+    fn static_graph_queries() {
+        let source: PackedGraph = build_graph();
+        let graph = StaticGraph::from_graph(&source);
+
+        assert_eq!(graph.node_index(1u64), Some(0));
+        assert_eq!(graph.node_index(4u64), None);
+        assert_eq!(graph.node_index(6u64), Some(3));
+        assert_eq!(graph.node_index(10u64), None);
+        assert_eq!(graph.index_node_id(3), Some(NodeId::from(6)));
+
+        // AAAC GG TCA C GTTA NA
+        assert_eq!(graph.node_offset(6u64), Some(9));
+        assert_eq!(
+            graph.node_at_offset(10),
+            Some((Handle::pack(7u64, false), 0))
+        );
+        assert_eq!(
+            graph.node_at_offset(12),
+            Some((Handle::pack(7u64, false), 2))
+        );
+        assert_eq!(graph.node_at_offset(16), None);
+
+        let x = graph.get_path_id(b"x").unwrap();
+        let path = graph.get_path_ref(x).unwrap();
+        assert_eq!(path.len(), 5);
+        assert_eq!(
+            path.next_step(StepIx(0)).map(|s| s.handle()),
+            Some(Handle::pack(2u64, false))
+        );
+        assert_eq!(path.prev_step(StepIx(0)), None);
+
+        let range = graph
+            .path_steps_range(x, StepIx(1), StepIx(3))
+            .unwrap()
+            .map(|s| u64::from(s.handle().id()))
+            .collect::<Vec<_>>();
+        assert_eq!(range, vec![2, 6, 7]);
+
+        let empty = graph.get_path_id(b"empty").unwrap();
+        assert_eq!(graph.path_first_step(empty), None);
+        assert_eq!(graph.path_steps(empty).unwrap().count(), 0);
+
+        let empty_graph = StaticGraph::from_graph(&HashGraph::default());
+        assert_eq!(empty_graph.node_count(), 0);
+        assert_eq!(empty_graph.handles().count(), 0);
+        assert_eq!(empty_graph.path_count(), 0);
+    }
+}
@@ -0,0 +1,302 @@
+/*!
+
+A bit vector with rank and select support, used for all the
+offset mappings in a [`StaticGraph`](super::StaticGraph).
+
+Most of the uses are for unary encoded lists of lengths, where each
+entry is stored as a set bit followed by as many unset bits as the
+length of the entry, plus a final set bit. The set bits then mark the
+boundaries between entries, and the unset bits the elements.
+
+Rank queries use `succinct`'s `Rank9` index. For select, the position
+of every 512th set bit and every 512th unset bit is sampled; a query
+binary searches the `Rank9` blocks between two samples, and then
+scans the words of a single block.
+
+*/
+
+use succinct::{BitRankSupport, BitVec, BitVecPush, BitVector, Rank9};
+
+/// The number of set (or unset) bits between select samples.
+const SELECT_SAMPLE_RATE: usize = 512;
+
+/// The number of bits in each `Rank9` basic block.
+const RANK_BLOCK_BITS: usize = 512;
+
+#[derive(Debug, Clone)]
+pub struct RankSelectBits {
+    bits: Rank9<BitVector<u64>>,
+    ones: usize,
+    select1_samples: Vec<usize>,
+    select0_samples: Vec<usize>,
+}
+
+crate::impl_space_usage!(
+    RankSelectBits,
+    [bits, select1_samples, select0_samples]
+);
+
+impl Default for RankSelectBits {
+    fn default() -> Self {
+        Self::new(BitVector::new())
+    }
+}
+
+impl RankSelectBits {
+    pub fn new(bits: BitVector<u64>) -> Self {
+        let bits = Rank9::new(bits);
+        let len = bits.bit_len() as usize;
+
+        let mut select1_samples = Vec::new();
+        let mut select0_samples = Vec::new();
+        let mut ones = 0;
+
+        for word_ix in 0..bits.block_len() {
+            let word = bits.get_block(word_ix);
+            let start = word_ix * 64;
+            for offset in 0..(len - start).min(64) {
+                let ix = start + offset;
+                if (word >> offset) & 1 == 1 {
+                    if ones % SELECT_SAMPLE_RATE == 0 {
+                        select1_samples.push(ix);
+                    }
+                    ones += 1;
+                } else if (ix - ones) % SELECT_SAMPLE_RATE == 0 {
+                    select0_samples.push(ix);
+                }
+            }
+        }
+
+        Self {
+            bits,
+            ones,
+            select1_samples,
+            select0_samples,
+        }
+    }
+
+    /// Build the unary encoding of the provided lengths.
+    pub fn from_lengths<I>(lengths: I) -> Self
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let mut bits: BitVector<u64> = BitVector::new();
+        for len in lengths {
+            bits.push_bit(true);
+            for _ in 0..len {
+                bits.push_bit(false);
+            }
+        }
+        bits.push_bit(true);
+        Self::new(bits)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bits.bit_len() as usize
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn count_ones(&self) -> usize {
+        self.ones
+    }
+
+    #[inline]
+    pub fn get(&self, ix: usize) -> bool {
+        self.bits.get_bit(ix as u64)
+    }
+
+    /// The number of set bits before position `ix`, i.e. in the
+    /// range `0..ix`.
+    #[inline]
+    pub fn rank1(&self, ix: usize) -> usize {
+        if ix == 0 {
+            0
+        } else {
+            self.bits.rank1((ix - 1) as u64) as usize
+        }
+    }
+
+    /// The number of unset bits before position `ix`.
+    #[inline]
+    pub fn rank0(&self, ix: usize) -> usize {
+        ix - self.rank1(ix)
+    }
+
+    /// The position of the set bit with rank `n`, counting from zero.
+    #[inline]
+    pub fn select1(&self, n: usize) -> Option<usize> {
+        if n >= self.ones {
+            return None;
+        }
+        Some(self.select(n, true))
+    }
+
+    /// The position of the unset bit with rank `n`, counting from
+    /// zero.
+    #[inline]
+    pub fn select0(&self, n: usize) -> Option<usize> {
+        if n >= self.len() - self.ones {
+            return None;
+        }
+        Some(self.select(n, false))
+    }
+
+    /// The position of the set or unset bit with rank `n`, which must
+    /// exist.
+    fn select(&self, n: usize, bit: bool) -> usize {
+        let (samples, rank): (_, &dyn Fn(usize) -> usize) = if bit {
+            (&self.select1_samples, &|ix| self.rank1(ix))
+        } else {
+            (&self.select0_samples, &|ix| self.rank0(ix))
+        };
+
+        // the bit is between the two closest samples, so find the
+        // last Rank9 block between them that starts at a rank of at
+        // most `n`
+        let sample_ix = n / SELECT_SAMPLE_RATE;
+        let lo = samples[sample_ix];
+        let hi = samples
+            .get(sample_ix + 1)
+            .copied()
+            .unwrap_or_else(|| self.len() - 1);
+
+        let mut lo_block = lo / RANK_BLOCK_BITS;
+        let mut hi_block = hi / RANK_BLOCK_BITS;
+        while lo_block < hi_block {
+            let mid = lo_block + (hi_block - lo_block).div_ceil(2);
+            if rank(mid * RANK_BLOCK_BITS) <= n {
+                lo_block = mid;
+            } else {
+                hi_block = mid - 1;
+            }
+        }
+
+        // then scan the words of that block
+        let mut word_ix = lo_block * RANK_BLOCK_BITS / 64;
+        let mut rank_before = rank(word_ix * 64);
+        loop {
+            let mut word = self.bits.get_block(word_ix);
+            if !bit {
+                word = !word;
+            }
+            let count = word.count_ones() as usize;
+            if rank_before + count > n {
+                for _ in 0..(n - rank_before) {
+                    word &= word - 1;
+                }
+                return word_ix * 64 + word.trailing_zeros() as usize;
+            }
+            rank_before += count;
+            word_ix += 1;
+        }
+    }
+
+    /// The number of entries in a unary encoded vector.
+    #[inline]
+    pub fn unary_count(&self) -> usize {
+        self.ones.saturating_sub(1)
+    }
+
+    /// The range of element indices covered by entry `n` in a unary
+    /// encoded vector.
+    #[inline]
+    pub fn unary_range(&self, n: usize) -> Option<std::ops::Range<usize>> {
+        if n >= self.unary_count() {
+            return None;
+        }
+        let start_bit = self.select1(n)?;
+        let end_bit = self.select1(n + 1)?;
+        let start = start_bit - n;
+        Some(start..start + (end_bit - start_bit - 1))
+    }
+
+    /// The index of the entry containing element `ix` in a unary
+    /// encoded vector.
+    #[inline]
+    pub fn unary_entry(&self, ix: usize) -> Option<usize> {
+        let bit = self.select0(ix)?;
+        Some(self.rank1(bit) - 1)
+    }
+
+    /// The index of the first element of entry `n` in a unary
+    /// encoded vector; `n` may be equal to the number of entries, in
+    /// which case the total number of elements is returned.
+    #[inline]
+    pub fn unary_start(&self, n: usize) -> Option<usize> {
+        let bit = self.select1(n)?;
+        Some(bit - n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unary_lengths() {
+        let lengths = [3, 0, 1, 0, 0, 5, 2];
+        let bits = RankSelectBits::from_lengths(lengths.iter().copied());
+
+        assert_eq!(bits.unary_count(), lengths.len());
+
+        let mut elem = 0;
+        for (ix, &len) in lengths.iter().enumerate() {
+            let range = bits.unary_range(ix).unwrap();
+            assert_eq!(range, elem..elem + len);
+            assert_eq!(bits.unary_start(ix), Some(elem));
+            for e in range {
+                assert_eq!(bits.unary_entry(e), Some(ix));
+            }
+            elem += len;
+        }
+
+        assert_eq!(bits.unary_start(lengths.len()), Some(elem));
+        assert_eq!(bits.unary_range(lengths.len()), None);
+        assert_eq!(bits.unary_entry(elem), None);
+
+        let empty = RankSelectBits::from_lengths(std::iter::empty());
+        assert_eq!(empty.unary_count(), 0);
+        assert_eq!(empty.unary_start(0), Some(0));
+        assert_eq!(empty.unary_range(0), None);
+    }
+
+    #[test]
+    fn select_sampled() {
+        // dense, sparse, and clustered bits, with runs that span
+        // several samples and Rank9 blocks
+        let patterns: [fn(usize) -> bool; 4] = [
+            |ix| ix % 3 != 0,
+            |ix| ix % 1500 == 7,
+            |ix| (ix / 3000) % 2 == 0 || ix % 5 == 0,
+            |ix| !(100..=19_000).contains(&ix),
+        ];
+
+        for pattern in patterns.iter() {
+            let mut vec: BitVector<u64> = BitVector::new();
+            for ix in 0..20_011 {
+                vec.push_bit(pattern(ix));
+            }
+            let bits = RankSelectBits::new(vec);
+
+            let (mut ones, mut zeros) = (0, 0);
+            for ix in 0..bits.len() {
+                if bits.get(ix) {
+                    assert_eq!(bits.select1(ones), Some(ix));
+                    ones += 1;
+                } else {
+                    assert_eq!(bits.select0(zeros), Some(ix));
+                    zeros += 1;
+                }
+            }
+            assert_eq!(bits.count_ones(), ones);
+            assert_eq!(bits.select1(ones), None);
+            assert_eq!(bits.select0(zeros), None);
+        }
+    }
+}
@@ -0,0 +1,452 @@
+/*!
+
+The embedded paths of a [`StaticGraph`](super::StaticGraph).
+
+The steps of all paths are stored in a single packed vector, in path
+order, and the boundaries between paths and the path names are found
+using unary encoded [`RankSelectBits`]. The sequence positions of the
+steps are sampled, so that only one in every [`OFFSET_SAMPLE_RATE`]
+steps has its position stored, and the rest are found by adding up
+the lengths of the nodes from the closest sample.
+
+*/
+
+use succinct::{BitVec, BitVecPush, BitVector};
+
+use crate::{
+    handle::Handle,
+    packed::*,
+    pathhandlegraph::{PathBase, PathId, PathStep, PathSteps},
+};
+
+use super::bits::RankSelectBits;
+
+/// The distance in steps between the stored sequence offsets of the
+/// path steps.
+pub const OFFSET_SAMPLE_RATE: usize = 64;
+
+/// The zero-based index of a step on a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StepIx(pub usize);
+
+crate::impl_space_usage_stack_newtype!(StepIx);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step(pub StepIx, pub Handle);
+
+impl PathStep for Step {
+    #[inline]
+    fn handle(&self) -> Handle {
+        self.1
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StaticPaths {
+    pub(super) names: Vec<u8>,
+    pub(super) name_bounds: RankSelectBits,
+    /// Path IDs ordered by the names of the paths.
+    pub(super) sorted_names: PackedIntVec,
+    pub(super) circular: BitVector<u64>,
+    pub(super) steps: PackedIntVec,
+    /// Unary encoded number of steps of each path.
+    pub(super) step_bounds: RankSelectBits,
+    /// The sequence offset of every `OFFSET_SAMPLE_RATE`th step in
+    /// `steps`, counting from the start of the first path, including
+    /// the end of the last path if it falls on a sample.
+    pub(super) step_offsets: PackedIntVec,
+    /// Unary encoded number of occurrences on each node, by node
+    /// index.
+    pub(super) occur_bounds: RankSelectBits,
+    pub(super) occur_paths: PackedIntVec,
+    pub(super) occur_steps: PackedIntVec,
+}
+
+crate::impl_space_usage!(
+    StaticPaths,
+    [
+        names,
+        name_bounds,
+        sorted_names,
+        circular,
+        steps,
+        step_bounds,
+        step_offsets,
+        occur_bounds,
+        occur_paths,
+        occur_steps
+    ]
+);
+
+impl Default for StaticPaths {
+    fn default() -> Self {
+        Self::new(Vec::new(), 0, |_| 0, |_| 0)
+    }
+}
+
+/// A single path, as it's extracted from the source graph.
+pub(super) struct PathData {
+    pub(super) name: Vec<u8>,
+    pub(super) circular: bool,
+    pub(super) steps: Vec<Handle>,
+}
+
+impl StaticPaths {
+    /// Build the paths from `paths`, where `node_index` maps a handle
+    /// to the index of its node, and `node_len` to its sequence
+    /// length.
+    pub(super) fn new<F, L>(
+        paths: Vec<PathData>,
+        node_count: usize,
+        node_index: F,
+        node_len: L,
+    ) -> Self
+    where
+        F: Fn(Handle) -> usize,
+        L: Fn(Handle) -> usize,
+    {
+        let mut names = Vec::new();
+        let mut circular: BitVector<u64> = BitVector::new();
+        let mut steps = PackedIntVec::new();
+
+        for path in paths.iter() {
+            names.extend_from_slice(&path.name);
+            circular.push_bit(path.circular);
+            for &handle in path.steps.iter() {
+                steps.append(handle.as_integer());
+            }
+        }
+
+        let name_bounds =
+            RankSelectBits::from_lengths(paths.iter().map(|p| p.name.len()));
+        let step_bounds =
+            RankSelectBits::from_lengths(paths.iter().map(|p| p.steps.len()));
+
+        let mut step_offsets = PackedIntVec::new();
+        let mut offset = 0;
+        for (ix, &handle) in paths.iter().flat_map(|p| &p.steps).enumerate() {
+            if ix.is_multiple_of(OFFSET_SAMPLE_RATE) {
+                step_offsets.append(offset as u64);
+            }
+            offset += node_len(handle);
+        }
+        if steps.len().is_multiple_of(OFFSET_SAMPLE_RATE) {
+            step_offsets.append(offset as u64);
+        }
+
+        let mut sorted_ids = (0..paths.len()).collect::<Vec<_>>();
+        sorted_ids.sort_by(|&a, &b| paths[a].name.cmp(&paths[b].name));
+
+        let mut sorted_names = PackedIntVec::new();
+        for id in sorted_ids {
+            sorted_names.append(id as u64);
+        }
+
+        let mut occurrences: Vec<Vec<(usize, usize)>> =
+            vec![Vec::new(); node_count];
+
+        for (path_ix, path) in paths.iter().enumerate() {
+            for (step_ix, &handle) in path.steps.iter().enumerate() {
+                occurrences[node_index(handle)].push((path_ix, step_ix));
+            }
+        }
+
+        let occur_bounds =
+            RankSelectBits::from_lengths(occurrences.iter().map(|o| o.len()));
+
+        let mut occur_paths = PackedIntVec::new();
+        let mut occur_steps = PackedIntVec::new();
+
+        for &(path_ix, step_ix) in occurrences.iter().flatten() {
+            occur_paths.append(path_ix as u64);
+            occur_steps.append(step_ix as u64);
+        }
+
+        Self {
+            names,
+            name_bounds,
+            sorted_names,
+            circular,
+            steps,
+            step_bounds,
+            step_offsets,
+            occur_bounds,
+            occur_paths,
+            occur_steps,
+        }
+    }
+
+    #[inline]
+    pub fn path_count(&self) -> usize {
+        self.step_bounds.unary_count()
+    }
+
+    #[inline]
+    pub(super) fn name(&self, id: PathId) -> Option<&[u8]> {
+        let range = self.name_bounds.unary_range(id.0 as usize)?;
+        Some(&self.names[range])
+    }
+
+    pub(super) fn find_name(&self, name: &[u8]) -> Option<PathId> {
+        let mut lo = 0;
+        let mut hi = self.sorted_names.len();
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let id = PathId(self.sorted_names.get(mid));
+            match self.name(id)?.cmp(name) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(id),
+            }
+        }
+
+        None
+    }
+
+    #[inline]
+    pub(super) fn circular(&self, id: PathId) -> Option<bool> {
+        if (id.0 as usize) < self.path_count() {
+            Some(self.circular.get_bit(id.0))
+        } else {
+            None
+        }
+    }
+
+    /// The range of the steps of path `id` in the global steps
+    /// vector.
+    #[inline]
+    pub(super) fn step_range(
+        &self,
+        id: PathId,
+    ) -> Option<std::ops::Range<usize>> {
+        self.step_bounds.unary_range(id.0 as usize)
+    }
+
+    #[inline]
+    pub(super) fn handle_at(&self, global_ix: usize) -> Handle {
+        Handle::from_integer(self.steps.get(global_ix))
+    }
+
+    /// The sequence offset of the step at `global_ix` in the global
+    /// steps vector, counting from the start of the first path, using
+    /// `node_len` to find the lengths of the steps after the closest
+    /// sample. `global_ix` may be equal to the total number of steps.
+    pub(super) fn global_base_offset<L>(
+        &self,
+        global_ix: usize,
+        node_len: L,
+    ) -> usize
+    where
+        L: Fn(Handle) -> usize,
+    {
+        let sample = global_ix / OFFSET_SAMPLE_RATE;
+        let mut offset = self.step_offsets.get(sample) as usize;
+        for ix in (sample * OFFSET_SAMPLE_RATE)..global_ix {
+            offset += node_len(self.handle_at(ix));
+        }
+        offset
+    }
+
+    /// The index in the global steps vector of the step covering the
+    /// sequence offset `pos`, counting from the start of the first
+    /// path, using `node_len` to find the lengths of the steps.
+    pub(super) fn global_step_at_base<L>(
+        &self,
+        pos: usize,
+        node_len: L,
+    ) -> Option<usize>
+    where
+        L: Fn(Handle) -> usize,
+    {
+        // the last sample at or before `pos`; the first one is always
+        // zero
+        let mut lo = 0;
+        let mut hi = self.step_offsets.len();
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.step_offsets.get(mid) as usize <= pos {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        let sample = lo.checked_sub(1)?;
+
+        let mut offset = self.step_offsets.get(sample) as usize;
+        for ix in (sample * OFFSET_SAMPLE_RATE)..self.steps.len() {
+            offset += node_len(self.handle_at(ix));
+            if pos < offset {
+                return Some(ix);
+            }
+        }
+        None
+    }
+
+    #[inline]
+    pub(super) fn path_ref(&self, id: PathId) -> Option<StaticPath<'_>> {
+        let range = self.step_range(id)?;
+        let circular = self.circular.get_bit(id.0);
+        Some(StaticPath {
+            steps: &self.steps,
+            offset: range.start,
+            len: range.len(),
+            circular,
+        })
+    }
+
+    /// The occurrences on the node with index `node_ix`, as ranges
+    /// into the occurrence vectors.
+    #[inline]
+    pub(super) fn occurrences(&self, node_ix: usize) -> OccurrencesIter<'_> {
+        let range = self.occur_bounds.unary_range(node_ix).unwrap_or(0..0);
+        OccurrencesIter {
+            paths: &self.occur_paths,
+            steps: &self.occur_steps,
+            range,
+        }
+    }
+}
+
+/// A reference to a single path in a `StaticGraph`.
+#[derive(Debug, Clone, Copy)]
+pub struct StaticPath<'a> {
+    steps: &'a PackedIntVec,
+    offset: usize,
+    len: usize,
+    circular: bool,
+}
+
+impl<'a> StaticPath<'a> {
+    #[inline]
+    fn handle(&self, ix: usize) -> Handle {
+        Handle::from_integer(self.steps.get(self.offset + ix))
+    }
+
+    /// Iterate through the steps from `left` to `right`, inclusive.
+    #[inline]
+    pub fn steps_range(self, left: StepIx, right: StepIx) -> StepsIter<'a> {
+        let end = (right.0 + 1).min(self.len);
+        let start = left.0.min(end);
+        StepsIter {
+            steps: self.steps,
+            offset: self.offset,
+            range: start..end,
+        }
+    }
+}
+
+impl<'a> PathBase for StaticPath<'a> {
+    type Step = Step;
+
+    type StepIx = StepIx;
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn circular(&self) -> bool {
+        self.circular
+    }
+
+    #[inline]
+    fn step_at(&self, index: StepIx) -> Option<Step> {
+        if index.0 < self.len {
+            Some(Step(index, self.handle(index.0)))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn first_step(&self) -> StepIx {
+        StepIx(0)
+    }
+
+    #[inline]
+    fn last_step(&self) -> StepIx {
+        StepIx(self.len.saturating_sub(1))
+    }
+
+    #[inline]
+    fn next_step(&self, step: StepIx) -> Option<Step> {
+        self.step_at(StepIx(step.0 + 1))
+    }
+
+    #[inline]
+    fn prev_step(&self, step: StepIx) -> Option<Step> {
+        let ix = step.0.checked_sub(1)?;
+        self.step_at(StepIx(ix))
+    }
+}
+
+impl<'a> PathSteps for StaticPath<'a> {
+    type Steps = StepsIter<'a>;
+
+    #[inline]
+    fn steps(self) -> Self::Steps {
+        StepsIter {
+            steps: self.steps,
+            offset: self.offset,
+            range: 0..self.len,
+        }
+    }
+}
+
+pub struct StepsIter<'a> {
+    steps: &'a PackedIntVec,
+    offset: usize,
+    range: std::ops::Range<usize>,
+}
+
+impl<'a> Iterator for StepsIter<'a> {
+    type Item = Step;
+
+    #[inline]
+    fn next(&mut self) -> Option<Step> {
+        let ix = self.range.next()?;
+        let handle = Handle::from_integer(self.steps.get(self.offset + ix));
+        Some(Step(StepIx(ix), handle))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for StepsIter<'a> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Step> {
+        let ix = self.range.next_back()?;
+        let handle = Handle::from_integer(self.steps.get(self.offset + ix));
+        Some(Step(StepIx(ix), handle))
+    }
+}
+
+impl<'a> ExactSizeIterator for StepsIter<'a> {}
+
+pub struct OccurrencesIter<'a> {
+    paths: &'a PackedIntVec,
+    steps: &'a PackedIntVec,
+    range: std::ops::Range<usize>,
+}
+
+impl<'a> Iterator for OccurrencesIter<'a> {
+    type Item = (PathId, StepIx);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let ix = self.range.next()?;
+        let path = PathId(self.paths.get(ix));
+        let step = StepIx(self.steps.get(ix) as usize);
+        Some((path, step))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
@@ -0,0 +1,639 @@
+/*!
+
+Batches of graph mutations that can be rolled back.
+
+A [`Transaction`] wraps a mutable reference to a graph, and applies
+[`GraphOp`]s to it while recording the changes made by each
+operation in a [`GraphOpDelta`]. If an operation fails, or the
+transaction is explicitly aborted, the operations that undo each
+delta, from [`GraphOpDelta::undo_ops`], are applied in reverse order,
+restoring the graph to its state from before the transaction.
+
+Paths are referred to by name rather than `PathId`, as a path that is
+destroyed and then restored by a rollback may get a new `PathId`.
+
+```ignore
+let mut transaction = Transaction::new(&mut graph);
+let result = transaction.apply_batch(vec![
+    CreateOp::Handle { id: 10.into(), seq: b"GATTACA".to_vec() }.into(),
+    CreateOp::Edge { edge: Edge(h1, Handle::pack(10, false)) }.into(),
+]);
+
+match result {
+    Ok(()) => {
+        let delta = transaction.commit();
+    }
+    Err(_) => transaction.abort()?,
+}
+```
+
+*/
+
+use crate::{
+    handle::{Edge, Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+};
+
+use bstr::ByteSlice;
+
+mod delta;
+mod journal;
+mod ops;
+
+pub use self::delta::{
+    AddDel, AddDelDelta, EdgesDelta, GraphOpDelta, NodesDelta, PathStepsDelta,
+    PathsDelta,
+};
+pub use self::journal::{Journal, JournalEntry, JournalError};
+pub use self::ops::{CreateOp, GraphOp, MutHandleOp, MutPathOp, RemoveOp};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionError {
+    /// A node with the given ID already exists.
+    NodeExists(NodeId),
+    /// The node ID is 0, or the node sequence is empty.
+    InvalidNode(NodeId),
+    MissingNode(NodeId),
+    MissingEdge(Edge),
+    /// The node can't be removed as there are paths on it.
+    NodeOnPath(NodeId),
+    /// The offsets are empty, unsorted, or outside the node.
+    InvalidOffsets(Handle),
+    /// The handles don't form a chain that can be joined.
+    InvalidJoin(Vec<Handle>),
+    /// The parts don't cover the node, or a path on the node can't
+    /// be rewritten.
+    InvalidSplit(Handle),
+    PathExists(Vec<u8>),
+    MissingPath(Vec<u8>),
+    /// The step rank is past the end of the path.
+    InvalidRank(Vec<u8>, usize),
+    /// An operation that undoes part of the transaction failed, e.g.
+    /// because the graph was modified outside the transaction. The
+    /// graph is left with the operations up to and including the one
+    /// that could not be undone applied.
+    RollbackFailed(Box<TransactionError>),
+}
+
+impl std::fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let oriented = |h: &Handle| {
+            format!("{}{}", h.id().0, if h.is_reverse() { "-" } else { "+" })
+        };
+
+        match self {
+            TransactionError::NodeExists(id) => {
+                write!(f, "node {} already exists", id.0)
+            }
+            TransactionError::InvalidNode(id) => {
+                write!(f, "invalid node {}", id.0)
+            }
+            TransactionError::MissingNode(id) => {
+                write!(f, "node {} is not in the graph", id.0)
+            }
+            TransactionError::MissingEdge(Edge(from, to)) => {
+                write!(f, "missing edge {} -> {}", oriented(from), oriented(to))
+            }
+            TransactionError::NodeOnPath(id) => {
+                write!(f, "node {} is on a path", id.0)
+            }
+            TransactionError::InvalidOffsets(handle) => {
+                write!(f, "invalid offsets for node {}", oriented(handle))
+            }
+            TransactionError::InvalidJoin(handles) => {
+                let handles =
+                    handles.iter().map(oriented).collect::<Vec<_>>().join(",");
+                write!(f, "can't join handles {}", handles)
+            }
+            TransactionError::InvalidSplit(handle) => {
+                write!(f, "can't split node {}", oriented(handle))
+            }
+            TransactionError::PathExists(name) => {
+                write!(f, "path {} already exists", name.as_bstr())
+            }
+            TransactionError::MissingPath(name) => {
+                write!(f, "path {} is not in the graph", name.as_bstr())
+            }
+            TransactionError::InvalidRank(name, rank) => {
+                write!(f, "path {} has no step {}", name.as_bstr(), rank)
+            }
+            TransactionError::RollbackFailed(err) => {
+                write!(f, "rollback failed: {}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
+/// A batch of operations applied to a graph, which can be rolled back
+/// until the transaction is committed.
+///
+/// The operations are applied to the graph immediately, so the graph
+/// can be queried with [`Transaction::graph`] during the
+/// transaction. Dropping a transaction without aborting it keeps the
+/// changes, the same as committing it.
+#[must_use]
+pub struct Transaction<'a, G> {
+    graph: &'a mut G,
+    ops: Vec<GraphOp>,
+    deltas: Vec<GraphOpDelta>,
+}
+
+impl<'a, G> Transaction<'a, G>
+where
+    G: MutableHandleGraph + MutableGraphPaths,
+    for<'b> &'b G:
+        HandleGraphRef + GraphPathNames + IntoNodeOccurrences + IntoPathIds,
+{
+    pub fn new(graph: &'a mut G) -> Self {
+        Self {
+            graph,
+            ops: Vec::new(),
+            deltas: Vec::new(),
+        }
+    }
+
+    /// The graph, with all operations applied so far.
+    #[inline]
+    pub fn graph(&self) -> &G {
+        self.graph
+    }
+
+    /// The operations that have been applied so far.
+    #[inline]
+    pub fn ops(&self) -> &[GraphOp] {
+        &self.ops
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    fn composed_delta(&self) -> GraphOpDelta {
+        self.deltas
+            .iter()
+            .cloned()
+            .fold(GraphOpDelta::default(), |lhs, rhs| lhs.compose(rhs))
+    }
+
+    /// The net changes made by the transaction so far.
+    pub fn delta(&self) -> GraphOpDelta {
+        let mut delta = self.composed_delta();
+        delta.compact();
+        delta
+    }
+
+    /// Apply a single operation. If the operation fails, the graph is
+    /// left unchanged, and the transaction can still be used.
+    pub fn apply(&mut self, op: GraphOp) -> Result<(), TransactionError> {
+        let mut delta = GraphOpDelta {
+            count: self.deltas.last().map(|d| d.count).unwrap_or(0),
+            ..GraphOpDelta::default()
+        };
+
+        op.apply(self.graph, &mut delta)?;
+        self.ops.push(op);
+        self.deltas.push(delta);
+        Ok(())
+    }
+
+    /// Apply the operations in order. If any of them fails, the ones
+    /// from this batch that were applied are rolled back before the
+    /// error is returned, so the batch is applied either completely
+    /// or not at all.
+    ///
+    /// If the rollback itself fails, a
+    /// [`TransactionError::RollbackFailed`] is returned instead.
+    pub fn apply_batch<I>(&mut self, ops: I) -> Result<(), TransactionError>
+    where
+        I: IntoIterator<Item = GraphOp>,
+    {
+        let start = self.ops.len();
+        for op in ops {
+            if let Err(err) = self.apply(op) {
+                self.rollback_to(start)?;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Undo the operations applied after the first `len`, last
+    /// first. If an operation can't be undone, it is kept, along with
+    /// all operations before it.
+    fn rollback_to(&mut self, len: usize) -> Result<(), TransactionError> {
+        while self.ops.len() > len {
+            let undo = self.deltas.last().unwrap().undo_ops();
+
+            let mut rollback = Transaction::new(&mut *self.graph);
+            if let Err(err) = rollback.apply_batch(undo) {
+                return Err(TransactionError::RollbackFailed(Box::new(err)));
+            }
+            let _ = rollback.commit();
+
+            self.ops.pop();
+            self.deltas.pop();
+        }
+        Ok(())
+    }
+
+    /// The operations that would undo the transaction so far, in the
+    /// order they must be applied.
+    pub fn undo_ops(&self) -> Vec<GraphOp> {
+        self.composed_delta().undo_ops()
+    }
+
+    /// Undo the last applied operation, returning it, if any.
+    pub fn undo_last(&mut self) -> Result<Option<GraphOp>, TransactionError> {
+        let op = match self.ops.last() {
+            Some(op) => op.clone(),
+            None => return Ok(None),
+        };
+        self.rollback_to(self.ops.len() - 1)?;
+        Ok(Some(op))
+    }
+
+    /// Keep the changes, returning the net changes that were made.
+    pub fn commit(self) -> GraphOpDelta {
+        self.delta()
+    }
+
+    /// Roll back every operation in the transaction.
+    pub fn abort(mut self) -> Result<(), TransactionError> {
+        self.rollback_to(0)
+    }
+}
+
+/// Apply all operations in `ops` to `graph`, or none of them if any
+/// operation fails. Returns the net changes that were made.
+pub fn apply_transaction<G, I>(
+    graph: &mut G,
+    ops: I,
+) -> Result<GraphOpDelta, TransactionError>
+where
+    G: MutableHandleGraph + MutableGraphPaths,
+    for<'b> &'b G:
+        HandleGraphRef + GraphPathNames + IntoNodeOccurrences + IntoPathIds,
+    I: IntoIterator<Item = GraphOp>,
+{
+    let mut transaction = Transaction::new(graph);
+    transaction.apply_batch(ops)?;
+    Ok(transaction.commit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::packedgraph::PackedGraph;
+    use crate::util;
+    pub(super) use crate::util::test::{hnd, r_hnd};
+
+    type PathState = (Vec<u8>, bool, Vec<Handle>);
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub(super) struct GraphState {
+        nodes: Vec<(NodeId, Vec<u8>)>,
+        edges: Vec<Edge>,
+        paths: Vec<PathState>,
+    }
+
+    pub(super) fn graph_state(graph: &PackedGraph) -> GraphState {
+        let mut nodes = graph
+            .handles()
+            .map(|h| (h.id(), graph.sequence_vec(h)))
+            .collect::<Vec<_>>();
+        nodes.sort();
+
+        let mut edges = graph
+            .edges()
+            .map(|Edge(l, r)| Edge::edge_handle(l, r))
+            .collect::<Vec<_>>();
+        edges.sort();
+        edges.dedup();
+
+        let mut paths = graph
+            .path_ids()
+            .map(|path| {
+                let name = graph.get_path_name_vec(path).unwrap();
+                let circular = graph.path_circular(path).unwrap();
+                let steps = graph
+                    .path_steps(path)
+                    .unwrap()
+                    .map(|s| s.handle())
+                    .collect();
+                (name, circular, steps)
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        GraphState {
+            nodes,
+            edges,
+            paths,
+        }
+    }
+
+    pub(super) fn test_graph() -> PackedGraph {
+        let mut graph: PackedGraph = util::test::test_graph();
+
+        // node 6 has a self-loop and a reversing self-edge, and path2
+        // steps through the self-loop
+        graph.create_edge(Edge(hnd(6), hnd(6)));
+        graph.create_edge(Edge(hnd(6), r_hnd(6)));
+
+        let p2 = graph.get_path_id(b"path2").unwrap();
+        graph.path_prepend_step(p2, hnd(6));
+
+        graph
+    }
+
+    pub(super) fn divide(handle: Handle, offsets: &[usize]) -> GraphOp {
+        MutHandleOp::Divide {
+            handle,
+            offsets: offsets.to_vec(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn transaction_commit() {
+        let mut graph = test_graph();
+
+        let ops: Vec<GraphOp> = vec![
+            CreateOp::Handle {
+                id: 10.into(),
+                seq: b"GATTACA".to_vec(),
+            }
+            .into(),
+            CreateOp::Edge {
+                edge: Edge(hnd(1), hnd(10)),
+            }
+            .into(),
+            CreateOp::Edge {
+                edge: Edge(hnd(10), hnd(2)),
+            }
+            .into(),
+            // already exists, so nothing happens
+            CreateOp::Edge {
+                edge: Edge(r_hnd(2), r_hnd(1)),
+            }
+            .into(),
+            RemoveOp::Edge {
+                edge: Edge(hnd(1), hnd(8)),
+            }
+            .into(),
+            MutPathOp::InsertStep {
+                path: b"path1".to_vec(),
+                rank: 1,
+                handle: hnd(10),
+            }
+            .into(),
+        ];
+
+        let delta = apply_transaction(&mut graph, ops).unwrap();
+
+        let path1 = graph.get_path_id(b"path1").unwrap();
+        let steps = graph
+            .path_steps(path1)
+            .unwrap()
+            .map(|s| s.handle())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            vec![hnd(1), hnd(10), hnd(2), hnd(7), hnd(3), hnd(9)]
+        );
+
+        let nodes = delta.nodes_iter().copied().collect::<Vec<_>>();
+        assert_eq!(nodes, vec![AddDel::Add(0, hnd(10))]);
+
+        let mut edges = delta
+            .edges_iter()
+            .map(|ad| (ad.is_add(), ad.value()))
+            .collect::<Vec<_>>();
+        edges.sort();
+        assert_eq!(
+            edges,
+            vec![
+                (false, Edge::edge_handle(hnd(1), hnd(8))),
+                (true, Edge::edge_handle(hnd(1), hnd(10))),
+                (true, Edge::edge_handle(hnd(10), hnd(2))),
+            ]
+        );
+        assert!(delta.paths.paths.is_empty());
+        assert_eq!(delta.paths.total_steps, 1);
+    }
+
+    #[test]
+    fn transaction_rollback_on_error() {
+        let mut graph = test_graph();
+        let before = graph_state(&graph);
+
+        let ops: Vec<GraphOp> = vec![
+            CreateOp::Handle {
+                id: 10.into(),
+                seq: b"GATTACA".to_vec(),
+            }
+            .into(),
+            CreateOp::Edge {
+                edge: Edge(hnd(9), hnd(10)),
+            }
+            .into(),
+            divide(hnd(2), &[3, 7]),
+            RemoveOp::Path {
+                name: b"path1".to_vec(),
+            }
+            .into(),
+            RemoveOp::Handle { handle: hnd(5) }.into(),
+            // node 1 is on a path, so this fails
+            RemoveOp::Handle { handle: hnd(1) }.into(),
+        ];
+
+        let result = apply_transaction(&mut graph, ops);
+        assert_eq!(result, Err(TransactionError::NodeOnPath(1.into())));
+        assert_eq!(graph_state(&graph), before);
+
+        // a failing batch only rolls back its own operations
+        let mut transaction = Transaction::new(&mut graph);
+        transaction.apply(divide(hnd(2), &[5])).unwrap();
+        let after_divide = graph_state(transaction.graph());
+
+        let result = transaction.apply_batch(vec![
+            RemoveOp::Edge {
+                edge: Edge(hnd(1), hnd(8)),
+            }
+            .into(),
+            RemoveOp::Edge {
+                edge: Edge(hnd(1), hnd(8)),
+            }
+            .into(),
+        ]);
+        assert_eq!(
+            result,
+            Err(TransactionError::MissingEdge(Edge(hnd(1), hnd(8))))
+        );
+        assert_eq!(graph_state(transaction.graph()), after_divide);
+        assert_eq!(transaction.len(), 1);
+
+        transaction.abort().unwrap();
+        assert_eq!(graph_state(&graph), before);
+    }
+
+    #[test]
+    fn transaction_abort_restores_graph() {
+        let mut graph = test_graph();
+        let before = graph_state(&graph);
+
+        let mut transaction = Transaction::new(&mut graph);
+
+        // rewrite the bubble between 1 and 4 across nodes, edges and
+        // paths, including the node with self-edges
+        let ops: Vec<GraphOp> = vec![
+            divide(r_hnd(6), &[1, 3]),
+            MutHandleOp::Flip { handle: hnd(6) }.into(),
+            MutHandleOp::Flip { handle: hnd(2) }.into(),
+            divide(hnd(2), &[4]),
+            MutPathOp::FlipStep {
+                path: b"path2".to_vec(),
+                rank: 2,
+            }
+            .into(),
+            MutPathOp::RemoveStep {
+                path: b"path2".to_vec(),
+                rank: 0,
+            }
+            .into(),
+            MutPathOp::SetCircularity {
+                path: b"path1".to_vec(),
+                circular: true,
+            }
+            .into(),
+            RemoveOp::Path {
+                name: b"path2".to_vec(),
+            }
+            .into(),
+            RemoveOp::Handle { handle: r_hnd(8) }.into(),
+            CreateOp::Path {
+                name: b"path3".to_vec(),
+                circular: false,
+            }
+            .into(),
+            MutPathOp::InsertStep {
+                path: b"path3".to_vec(),
+                rank: 0,
+                handle: hnd(5),
+            }
+            .into(),
+        ];
+
+        transaction.apply_batch(ops).unwrap();
+        assert_ne!(graph_state(transaction.graph()), before);
+
+        transaction.abort().unwrap();
+        assert_eq!(graph_state(&graph), before);
+    }
+
+    #[test]
+    fn transaction_join_split() {
+        let mut graph = test_graph();
+        let before = graph_state(&graph);
+
+        let mut transaction = Transaction::new(&mut graph);
+
+        // 3 -> 9 isn't a simple chain
+        let join = |handles: Vec<Handle>| -> GraphOp {
+            let joined = handles[0];
+            MutHandleOp::Join { handles, joined }.into()
+        };
+        assert!(matches!(
+            transaction.apply(join(vec![hnd(3), hnd(9)])),
+            Err(TransactionError::InvalidJoin(_))
+        ));
+
+        // divide a node with a path in both orientations and
+        // self-edges, then join it back together
+        transaction.apply(divide(hnd(6), &[1, 2])).unwrap();
+        let parts = transaction.graph().max_node_id();
+        let parts = vec![hnd(6), hnd(u64::from(parts) - 1), hnd(parts.into())];
+
+        transaction.apply(join(parts.clone())).unwrap();
+        assert_eq!(graph_state(transaction.graph()), before);
+
+        // split the node into handles of both orientations
+        let split = MutHandleOp::Split {
+            handle: r_hnd(6),
+            parts: vec![(hnd(20), 1), (r_hnd(6), 2), (hnd(21), 1)],
+        };
+        transaction.apply(split.into()).unwrap();
+
+        let seq = |h: Handle| transaction.graph().sequence_vec(h);
+        assert_eq!(
+            [seq(hnd(20)), seq(r_hnd(6)), seq(hnd(21))].concat(),
+            crate::util::dna::rev_comp(b"GGGT")
+        );
+        assert!(transaction.graph().has_edge(hnd(21), hnd(20)));
+        assert!(transaction.graph().has_edge(r_hnd(20), hnd(20)));
+
+        let delta = transaction.delta();
+        let nodes = delta
+            .nodes_iter()
+            .map(|ad| (ad.is_add(), ad.value()))
+            .collect::<Vec<_>>();
+        assert_eq!(nodes, vec![(true, hnd(20)), (true, hnd(21))]);
+
+        transaction.undo_last().unwrap();
+        assert_eq!(graph_state(transaction.graph()), before);
+        assert!(transaction.delta().nodes.handles.is_empty());
+
+        let _ = transaction.commit();
+        assert_eq!(graph_state(&graph), before);
+    }
+
+    #[test]
+    fn transaction_rollback_failed() {
+        let mut graph = test_graph();
+
+        let mut transaction = Transaction::new(&mut graph);
+        transaction
+            .apply_batch(vec![
+                MutHandleOp::Flip { handle: hnd(2) }.into(),
+                CreateOp::Handle {
+                    id: 10.into(),
+                    seq: b"GATTACA".to_vec(),
+                }
+                .into(),
+            ])
+            .unwrap();
+
+        // a path stepping on the new node, added behind the
+        // transaction's back, keeps the node from being removed
+        let path1 = transaction.graph.get_path_id(b"path1").unwrap();
+        transaction.graph.path_append_step(path1, hnd(10));
+        let tampered = graph_state(transaction.graph());
+
+        let result = transaction.undo_last();
+        assert_eq!(
+            result,
+            Err(TransactionError::RollbackFailed(Box::new(
+                TransactionError::NodeOnPath(10.into())
+            )))
+        );
+        assert_eq!(graph_state(transaction.graph()), tampered);
+        assert_eq!(transaction.len(), 2);
+
+        assert!(matches!(
+            transaction.abort(),
+            Err(TransactionError::RollbackFailed(_))
+        ));
+        assert_eq!(graph_state(&graph), tampered);
+    }
+}
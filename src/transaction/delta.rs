@@ -0,0 +1,569 @@
+/*!
+
+Deltas that summarize the effect of a sequence of graph operations,
+as the nodes, edges, and paths that were added and removed.
+
+Besides the additions and removals, a [`GraphOpDelta`] keeps the
+state of each node, edge, and path from before the first operation
+that changed it, which is enough to derive the operations that undo
+the delta, with [`GraphOpDelta::undo_ops`].
+
+*/
+
+use crate::{
+    handle::{Edge, Handle, NodeId},
+    pathhandlegraph::PathId,
+};
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+use std::collections::hash_map::Entry;
+
+use super::{CreateOp, GraphOp, MutPathOp, RemoveOp};
+
+/// A sequence of additions and removals of values, each tagged with
+/// the count at which it happened.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AddDelDelta<T: Sized + Copy> {
+    vec: Vec<AddDel<T>>,
+}
+
+impl<T: Sized + Copy> Default for AddDelDelta<T> {
+    fn default() -> Self {
+        Self { vec: Vec::new() }
+    }
+}
+
+impl<T: Sized + Copy> AddDelDelta<T> {
+    pub fn new() -> Self {
+        Self { vec: Vec::new() }
+    }
+
+    pub fn new_add(v: T, count: &mut usize) -> Self {
+        let res = AddDelDelta {
+            vec: vec![AddDel::Add(*count, v)],
+        };
+        *count += 1;
+        res
+    }
+
+    pub fn new_del(v: T, count: &mut usize) -> Self {
+        let res = AddDelDelta {
+            vec: vec![AddDel::Del(*count, v)],
+        };
+        *count += 1;
+        res
+    }
+
+    #[inline]
+    pub fn iter(&self) -> std::slice::Iter<'_, AddDel<T>> {
+        self.vec.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Returns the delta that undoes this one, i.e. every addition
+    /// becomes a removal and vice versa, in reverse order. The counts
+    /// are kept as they are.
+    pub fn invert(&self) -> Self {
+        let vec = self
+            .vec
+            .iter()
+            .rev()
+            .map(|ad| match *ad {
+                AddDel::Add(c, v) => AddDel::Del(c, v),
+                AddDel::Del(c, v) => AddDel::Add(c, v),
+            })
+            .collect();
+        Self { vec }
+    }
+
+    #[inline]
+    pub fn add(&mut self, v: T, count: &mut usize) {
+        self.vec.push(AddDel::Add(*count, v));
+        *count += 1;
+    }
+
+    #[inline]
+    pub fn del(&mut self, v: T, count: &mut usize) {
+        self.vec.push(AddDel::Del(*count, v));
+        *count += 1;
+    }
+
+    #[inline]
+    pub fn append(&mut self, other: &Self) {
+        // TODO think about the offsetting and how/if it should occur
+
+        let offset = self.vec.last().map(|ad| ad.count()).unwrap_or(0);
+        // let new_count = self.count + other.count;
+        // let offset = self.count;
+
+        self.vec.extend(
+            other.vec.iter().copied().map(|ad| ad.offset_count(offset)),
+        );
+    }
+}
+
+impl<T: Sized + Copy> Extend<AddDel<T>> for AddDelDelta<T> {
+    fn extend<I: IntoIterator<Item = AddDel<T>>>(&mut self, iter: I) {
+        self.vec.extend(iter)
+    }
+}
+
+impl<T> AddDelDelta<T>
+where
+    T: Sized + Copy + Eq + std::hash::Hash,
+{
+    /// The net number of times each value was added, where removals
+    /// count as -1.
+    pub fn parity(&self) -> FnvHashMap<T, isize> {
+        let mut parity: FnvHashMap<T, isize> = FnvHashMap::default();
+        for &ad in self.vec.iter() {
+            let diff = if ad.is_add() { 1 } else { -1 };
+            *parity.entry(ad.value()).or_default() += diff;
+        }
+        parity
+    }
+
+    /// Reduce the delta to the net additions and removals, so that
+    /// each value occurs at most once.
+    #[inline]
+    pub fn compact(&mut self) {
+        let parity = self.parity();
+        let vec = std::mem::take(&mut self.vec);
+
+        let mut canonical: Vec<AddDel<T>> = Vec::with_capacity(vec.len());
+
+        let mut seen: FnvHashSet<T> = FnvHashSet::default();
+
+        for ad in vec.into_iter().rev() {
+            let k = ad.value();
+
+            if !seen.contains(&k) {
+                seen.insert(k);
+
+                if let Some(par) = parity.get(&k) {
+                    match par.cmp(&0) {
+                        std::cmp::Ordering::Less => {
+                            canonical.push(AddDel::Del(ad.count(), k));
+                        }
+                        std::cmp::Ordering::Equal => {
+                            // cancels out
+                        }
+                        std::cmp::Ordering::Greater => {
+                            canonical.push(AddDel::Add(ad.count(), k));
+                        }
+                    }
+                }
+            }
+        }
+        canonical.reverse();
+        canonical.shrink_to_fit();
+        self.vec = canonical;
+    }
+}
+
+/// A single addition or removal of a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AddDel<T: Sized + Copy> {
+    Add(usize, T),
+    Del(usize, T),
+}
+
+impl<T: Sized + Copy> AddDel<T> {
+    #[inline]
+    pub fn add(&self, c: usize, v: T) -> Self {
+        AddDel::Add(c, v)
+    }
+
+    #[inline]
+    pub fn del(&self, c: usize, v: T) -> Self {
+        AddDel::Del(c, v)
+    }
+
+    #[inline]
+    pub fn is_add(&self) -> bool {
+        match self {
+            AddDel::Add(_, _) => true,
+            AddDel::Del(_, _) => false,
+        }
+    }
+
+    #[inline]
+    pub fn is_del(&self) -> bool {
+        match self {
+            AddDel::Add(_, _) => false,
+            AddDel::Del(_, _) => true,
+        }
+    }
+
+    #[inline]
+    pub fn count(&self) -> usize {
+        match self {
+            AddDel::Add(c, _) => *c,
+            AddDel::Del(c, _) => *c,
+        }
+    }
+
+    #[inline]
+    pub fn value(&self) -> T {
+        match self {
+            AddDel::Add(_, v) => *v,
+            AddDel::Del(_, v) => *v,
+        }
+    }
+
+    #[inline]
+    pub fn map<F, U>(&self, f: F) -> AddDel<U>
+    where
+        U: Sized + Copy,
+        F: Fn(T) -> U,
+    {
+        match *self {
+            AddDel::Add(c, t) => AddDel::Add(c, f(t)),
+            AddDel::Del(c, t) => AddDel::Del(c, f(t)),
+        }
+    }
+
+    #[inline]
+    pub fn offset_count(&self, offset: usize) -> Self {
+        match *self {
+            AddDel::Add(c, t) => AddDel::Add(c + offset, t),
+            AddDel::Del(c, t) => AddDel::Del(c + offset, t),
+        }
+    }
+}
+
+/// The changes made to a graph by a sequence of operations.
+///
+/// Nodes are stored as forward handles, and edges in their canonical
+/// orientation, as produced by [`Edge::edge_handle`]. A node whose
+/// sequence is changed in place, e.g. by dividing or flipping it, is
+/// recorded as removed and then added again.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GraphOpDelta {
+    pub nodes: NodesDelta,
+    pub edges: EdgesDelta,
+    pub paths: PathsDelta,
+    pub count: usize,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NodesDelta {
+    pub node_count: isize,
+    pub total_len: isize,
+    pub handles: AddDelDelta<Handle>,
+    /// The sequence of each changed node from before it was first
+    /// changed, or `None` if the node didn't exist.
+    pub before: FnvHashMap<NodeId, Option<Vec<u8>>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EdgesDelta {
+    pub edge_count: isize,
+    pub edges: AddDelDelta<Edge>,
+    /// Whether each changed edge existed before it was first changed.
+    pub before: FnvHashMap<Edge, bool>,
+}
+
+/// Paths are tracked by name in `path_steps`, as a path that is
+/// destroyed and created again may get a new `PathId`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PathsDelta {
+    pub path_count: isize,
+    pub total_steps: isize,
+    pub paths: AddDelDelta<PathId>,
+    pub path_steps: FnvHashMap<Vec<u8>, PathStepsDelta>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PathStepsDelta {
+    pub step_count: isize,
+    /// The circularity and steps of the path from before it was
+    /// first changed, or `None` if the path didn't exist.
+    pub before: Option<(bool, Vec<Handle>)>,
+    /// Whether the path exists after the last change.
+    pub exists: bool,
+}
+
+impl GraphOpDelta {
+    pub fn nodes_iter(&self) -> std::slice::Iter<'_, AddDel<Handle>> {
+        self.nodes.handles.iter()
+    }
+
+    pub fn edges_iter(&self) -> std::slice::Iter<'_, AddDel<Edge>> {
+        self.edges.edges.iter()
+    }
+
+    pub fn paths_iter(&self) -> std::slice::Iter<'_, AddDel<PathId>> {
+        self.paths.paths.iter()
+    }
+
+    /// The count to be used for the next addition or removal.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.before.is_empty()
+            && self.edges.before.is_empty()
+            && self.paths.path_steps.is_empty()
+    }
+
+    /// Combine the delta with `rhs`, which must be recorded after
+    /// this one. The additions and removals of both are kept; use
+    /// [`GraphOpDelta::compact`] to reduce them to the net changes.
+    pub fn compose(mut self, rhs: Self) -> Self {
+        self.nodes.node_count += rhs.nodes.node_count;
+        self.nodes.total_len += rhs.nodes.total_len;
+        self.nodes.handles.extend(rhs.nodes.handles.iter().copied());
+        for (id, seq) in rhs.nodes.before {
+            self.nodes.before.entry(id).or_insert(seq);
+        }
+
+        self.edges.edge_count += rhs.edges.edge_count;
+        self.edges.edges.extend(rhs.edges.edges.iter().copied());
+        for (edge, existed) in rhs.edges.before {
+            self.edges.before.entry(edge).or_insert(existed);
+        }
+
+        self.paths.path_count += rhs.paths.path_count;
+        self.paths.total_steps += rhs.paths.total_steps;
+        self.paths.paths.extend(rhs.paths.paths.iter().copied());
+        for (name, steps) in rhs.paths.path_steps {
+            match self.paths.path_steps.entry(name) {
+                Entry::Occupied(mut entry) => {
+                    let entry = entry.get_mut();
+                    entry.step_count += steps.step_count;
+                    entry.exists = steps.exists;
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(steps);
+                }
+            }
+        }
+
+        self.count = self.count.max(rhs.count);
+        self
+    }
+
+    /// Reduce the additions and removals to the net changes. The
+    /// states from before the changes are kept, so the compacted
+    /// delta can still be undone.
+    pub fn compact(&mut self) {
+        self.nodes.handles.compact();
+        self.edges.edges.compact();
+        self.paths.paths.compact();
+    }
+
+    /// The operations that restore the graph to its state from
+    /// before the delta, in the order they must be applied, given
+    /// that the graph is in the state the delta left it in.
+    ///
+    /// Changed paths are emptied first, so that the nodes they step
+    /// on can be removed, and every node that was changed in place
+    /// is removed and created again with its old sequence.
+    pub fn undo_ops(&self) -> Vec<GraphOp> {
+        let mut ops: Vec<GraphOp> = Vec::new();
+
+        let node_parity = self.nodes.handles.parity();
+        let node_exists = |id: NodeId, before: bool| {
+            let parity = node_parity
+                .get(&Handle::pack(id, false))
+                .copied()
+                .unwrap_or(0);
+            before as isize + parity > 0
+        };
+
+        let edge_parity = self.edges.edges.parity();
+        let edge_exists = |edge: &Edge, before: bool| {
+            let parity = edge_parity.get(edge).copied().unwrap_or(0);
+            before as isize + parity > 0
+        };
+
+        let mut paths = self.paths.path_steps.iter().collect::<Vec<_>>();
+        paths.sort_by_key(|(name, _)| *name);
+
+        let mut nodes = self.nodes.before.iter().collect::<Vec<_>>();
+        nodes.sort_by_key(|(id, _)| **id);
+
+        let mut edges = self.edges.before.iter().collect::<Vec<_>>();
+        edges.sort();
+
+        for (name, steps) in paths.iter() {
+            if !steps.exists {
+                continue;
+            }
+            match &steps.before {
+                None => ops.push(
+                    RemoveOp::Path {
+                        name: name.to_vec(),
+                    }
+                    .into(),
+                ),
+                Some((_, old_steps)) => {
+                    let len = old_steps.len() as isize + steps.step_count;
+                    for _ in 0..len {
+                        ops.push(
+                            MutPathOp::RemoveStep {
+                                path: name.to_vec(),
+                                rank: 0,
+                            }
+                            .into(),
+                        );
+                    }
+                }
+            }
+        }
+
+        for &(&edge, &existed) in edges.iter() {
+            if !existed && edge_exists(&edge, existed) {
+                ops.push(RemoveOp::Edge { edge }.into());
+            }
+        }
+
+        for &(&id, seq) in nodes.iter() {
+            if node_exists(id, seq.is_some()) {
+                let handle = Handle::pack(id, false);
+                ops.push(RemoveOp::Handle { handle }.into());
+            }
+        }
+
+        for &(&id, seq) in nodes.iter() {
+            if let Some(seq) = seq {
+                let seq = seq.clone();
+                ops.push(CreateOp::Handle { id, seq }.into());
+            }
+        }
+
+        for &(&edge, &existed) in edges.iter() {
+            if existed {
+                ops.push(CreateOp::Edge { edge }.into());
+            }
+        }
+
+        for (name, steps) in paths.iter() {
+            let (circular, old_steps) = match &steps.before {
+                Some((circular, old_steps)) => (*circular, old_steps),
+                None => continue,
+            };
+
+            let path = name.to_vec();
+            if steps.exists {
+                ops.push(MutPathOp::SetCircularity { path, circular }.into());
+            } else {
+                ops.push(
+                    CreateOp::Path {
+                        name: path,
+                        circular,
+                    }
+                    .into(),
+                );
+            }
+
+            for (rank, &handle) in old_steps.iter().enumerate() {
+                ops.push(
+                    MutPathOp::InsertStep {
+                        path: name.to_vec(),
+                        rank,
+                        handle,
+                    }
+                    .into(),
+                );
+            }
+        }
+
+        ops
+    }
+
+    #[inline]
+    pub(crate) fn add_node(&mut self, id: NodeId, len: usize) {
+        self.nodes.before.entry(id).or_insert(None);
+        self.nodes.node_count += 1;
+        self.nodes.total_len += len as isize;
+        let handle = Handle::pack(id, false);
+        self.nodes.handles.add(handle, &mut self.count);
+    }
+
+    #[inline]
+    pub(crate) fn del_node(&mut self, id: NodeId, seq: Vec<u8>) {
+        self.nodes.node_count -= 1;
+        self.nodes.total_len -= seq.len() as isize;
+        self.nodes.before.entry(id).or_insert(Some(seq));
+        let handle = Handle::pack(id, false);
+        self.nodes.handles.del(handle, &mut self.count);
+    }
+
+    #[inline]
+    pub(crate) fn add_edge(&mut self, Edge(l, r): Edge) {
+        let edge = Edge::edge_handle(l, r);
+        self.edges.before.entry(edge).or_insert(false);
+        self.edges.edge_count += 1;
+        self.edges.edges.add(edge, &mut self.count);
+    }
+
+    #[inline]
+    pub(crate) fn del_edge(&mut self, Edge(l, r): Edge) {
+        let edge = Edge::edge_handle(l, r);
+        self.edges.before.entry(edge).or_insert(true);
+        self.edges.edge_count -= 1;
+        self.edges.edges.del(edge, &mut self.count);
+    }
+
+    #[inline]
+    pub(crate) fn add_path(&mut self, id: PathId) {
+        self.paths.path_count += 1;
+        self.paths.paths.add(id, &mut self.count);
+    }
+
+    #[inline]
+    pub(crate) fn del_path(&mut self, id: PathId) {
+        self.paths.path_count -= 1;
+        self.paths.paths.del(id, &mut self.count);
+    }
+
+    /// Record the state of the path `name` before it is changed,
+    /// unless it was already changed earlier in the delta.
+    pub(crate) fn touch_path<F>(&mut self, name: &[u8], before: F)
+    where
+        F: FnOnce() -> Option<(bool, Vec<Handle>)>,
+    {
+        if !self.paths.path_steps.contains_key(name) {
+            let before = before();
+            let exists = before.is_some();
+            self.paths.path_steps.insert(
+                name.to_vec(),
+                PathStepsDelta {
+                    step_count: 0,
+                    before,
+                    exists,
+                },
+            );
+        }
+    }
+
+    /// Record a change in the length of the path `name`, which must
+    /// have been touched, and whether it still exists.
+    pub(crate) fn update_path(
+        &mut self,
+        name: &[u8],
+        diff: isize,
+        exists: bool,
+    ) {
+        if let Some(steps) = self.paths.path_steps.get_mut(name) {
+            steps.step_count += diff;
+            steps.exists = exists;
+        }
+        self.paths.total_steps += diff;
+    }
+}
@@ -0,0 +1,767 @@
+/*!
+
+A journal of the operations applied to a graph, that supports undo
+and redo, and can be written to and read from a line-based text
+format, e.g. to keep an audit trail of the edits made to a graph, or
+to replay them onto another copy of it.
+
+Each entry in the journal is an atomic batch of operations, together
+with the operations that undo it. Undoing an entry moves it to the
+redo stack, and applying any new operation clears the redo stack.
+
+In the text format, each entry starts with a line containing only
+`E`, followed by one line per operation, starting with `O` for the
+operations of the entry, and `U` for the operations that undo it.
+Fields are tab-separated, and handles are written as the node ID
+followed by `+` or `-`. In sequences and path names, backslashes,
+tabs, and line breaks are escaped as `\\`, `\t`, `\n`, and `\r`.
+With the tabs shown as spaces, an entry that creates a node and an
+edge looks like:
+
+```text
+E
+O  +H  10  GATTACA
+O  +E  1+  10+
+U  -E  1+  10+
+U  -H  10+
+```
+
+*/
+
+use crate::{
+    handle::{Edge, Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+};
+
+use std::io::{self, BufRead, Write};
+
+use bstr::ByteSlice;
+
+use super::{
+    apply_transaction, CreateOp, GraphOp, GraphOpDelta, MutHandleOp, MutPathOp,
+    RemoveOp, Transaction, TransactionError,
+};
+
+#[derive(Debug)]
+pub enum JournalError {
+    Io(io::Error),
+    /// The line, counting from 1, is not a valid journal line.
+    Parse(usize),
+}
+
+impl std::fmt::Display for JournalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JournalError::Io(err) => write!(f, "{}", err),
+            JournalError::Parse(line) => {
+                write!(f, "invalid journal entry on line {}", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+impl From<io::Error> for JournalError {
+    fn from(err: io::Error) -> Self {
+        JournalError::Io(err)
+    }
+}
+
+/// A batch of operations that was applied to a graph, and the
+/// operations that undo it, in the order they must be applied.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    ops: Vec<GraphOp>,
+    undo: Vec<GraphOp>,
+}
+
+impl JournalEntry {
+    #[inline]
+    pub fn ops(&self) -> &[GraphOp] {
+        &self.ops
+    }
+
+    #[inline]
+    pub fn undo_ops(&self) -> &[GraphOp] {
+        &self.undo
+    }
+}
+
+/// A log of the operations applied to a graph, with undo and redo.
+///
+/// The journal doesn't hold a reference to the graph, so it's up to
+/// the caller to always pass the same graph, in the state the journal
+/// left it in.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+    redo: Vec<Vec<GraphOp>>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The entries that are currently applied, oldest first.
+    #[inline]
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn can_undo(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// All operations applied by the journal, in order.
+    pub fn ops(&self) -> impl Iterator<Item = &GraphOp> + '_ {
+        self.entries.iter().flat_map(|entry| entry.ops.iter())
+    }
+
+    /// The operations that undo every entry in the journal, in the
+    /// order they must be applied.
+    pub fn undo_ops(&self) -> Vec<GraphOp> {
+        self.entries
+            .iter()
+            .rev()
+            .flat_map(|entry| entry.undo.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Record a transaction as a single entry, committing it. Empty
+    /// transactions are not recorded.
+    pub fn record<G>(&mut self, transaction: Transaction<'_, G>) -> GraphOpDelta
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'b> &'b G:
+            HandleGraphRef + GraphPathNames + IntoNodeOccurrences + IntoPathIds,
+    {
+        if !transaction.is_empty() {
+            self.entries.push(JournalEntry {
+                ops: transaction.ops.clone(),
+                undo: transaction.undo_ops(),
+            });
+            self.redo.clear();
+        }
+        transaction.commit()
+    }
+
+    /// Apply a single operation to `graph` as a new entry.
+    pub fn apply<G>(
+        &mut self,
+        graph: &mut G,
+        op: GraphOp,
+    ) -> Result<GraphOpDelta, TransactionError>
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'b> &'b G:
+            HandleGraphRef + GraphPathNames + IntoNodeOccurrences + IntoPathIds,
+    {
+        self.apply_batch(graph, std::iter::once(op))
+    }
+
+    /// Apply the operations to `graph` as a single entry. If any of
+    /// them fails, none of them are applied, and nothing is recorded.
+    pub fn apply_batch<G, I>(
+        &mut self,
+        graph: &mut G,
+        ops: I,
+    ) -> Result<GraphOpDelta, TransactionError>
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'b> &'b G:
+            HandleGraphRef + GraphPathNames + IntoNodeOccurrences + IntoPathIds,
+        I: IntoIterator<Item = GraphOp>,
+    {
+        let mut transaction = Transaction::new(graph);
+        transaction.apply_batch(ops)?;
+        Ok(self.record(transaction))
+    }
+
+    /// Undo the most recent entry, moving it to the redo stack.
+    /// Returns `None` if there is nothing to undo.
+    pub fn undo<G>(
+        &mut self,
+        graph: &mut G,
+    ) -> Result<Option<GraphOpDelta>, TransactionError>
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'b> &'b G:
+            HandleGraphRef + GraphPathNames + IntoNodeOccurrences + IntoPathIds,
+    {
+        let entry = match self.entries.pop() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        let mut transaction = Transaction::new(graph);
+        if let Err(err) = transaction.apply_batch(entry.undo.iter().cloned()) {
+            self.entries.push(entry);
+            return Err(err);
+        }
+
+        // undoing the undo operations recreates the exact nodes the
+        // entry created, even if the entry let the graph pick the IDs
+        self.redo.push(transaction.undo_ops());
+        Ok(Some(transaction.commit()))
+    }
+
+    /// Reapply the most recently undone entry. Returns `None` if
+    /// there is nothing to redo.
+    ///
+    /// The redone entry is recorded with the operations that recreate
+    /// the exact state from before it was undone, which may differ
+    /// from the operations that were originally applied, e.g. a
+    /// `Divide` is redone by creating the nodes it produced.
+    pub fn redo<G>(
+        &mut self,
+        graph: &mut G,
+    ) -> Result<Option<GraphOpDelta>, TransactionError>
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'b> &'b G:
+            HandleGraphRef + GraphPathNames + IntoNodeOccurrences + IntoPathIds,
+    {
+        let ops = match self.redo.pop() {
+            Some(ops) => ops,
+            None => return Ok(None),
+        };
+
+        let mut transaction = Transaction::new(graph);
+        if let Err(err) = transaction.apply_batch(ops.iter().cloned()) {
+            self.redo.push(ops);
+            return Err(err);
+        }
+
+        self.entries.push(JournalEntry {
+            ops,
+            undo: transaction.undo_ops(),
+        });
+        Ok(Some(transaction.commit()))
+    }
+
+    /// Apply every operation in the journal to `graph`, as a single
+    /// transaction. For the result to match the graph the journal was
+    /// recorded on, `graph` must be in the state that graph was in
+    /// before the first entry.
+    pub fn replay<G>(
+        &self,
+        graph: &mut G,
+    ) -> Result<GraphOpDelta, TransactionError>
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'b> &'b G:
+            HandleGraphRef + GraphPathNames + IntoNodeOccurrences + IntoPathIds,
+    {
+        apply_transaction(graph, self.ops().cloned())
+    }
+
+    /// Write the entries of the journal. The redo stack is not
+    /// written.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for entry in self.entries.iter() {
+            writeln!(writer, "E")?;
+            for op in entry.ops.iter() {
+                write!(writer, "O\t")?;
+                write_op(&mut writer, op)?;
+            }
+            for op in entry.undo.iter() {
+                write!(writer, "U\t")?;
+                write_op(&mut writer, op)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a journal written by [`Journal::write`], skipping empty
+    /// lines.
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, JournalError> {
+        let mut entries: Vec<JournalEntry> = Vec::new();
+
+        for (ix, line) in reader.split(b'\n').enumerate() {
+            let line = line?;
+            let line = line.trim_end_with(|c| c == '\r');
+            if line.is_empty() {
+                continue;
+            }
+
+            let parse_err = JournalError::Parse(ix + 1);
+
+            if line == b"E" {
+                entries.push(JournalEntry::default());
+                continue;
+            }
+
+            let entry = entries.last_mut().ok_or(parse_err)?;
+            let mut fields = line.split_str("\t");
+            let list = match fields.next() {
+                Some(b"O") => &mut entry.ops,
+                Some(b"U") => &mut entry.undo,
+                _ => return Err(JournalError::Parse(ix + 1)),
+            };
+            let op = parse_op(fields).ok_or(JournalError::Parse(ix + 1))?;
+            list.push(op);
+        }
+
+        Ok(Self {
+            entries,
+            redo: Vec::new(),
+        })
+    }
+}
+
+fn oriented(handle: Handle) -> String {
+    format!(
+        "{}{}",
+        handle.id().0,
+        if handle.is_reverse() { "-" } else { "+" }
+    )
+}
+
+fn joined<T, F>(values: &[T], f: F) -> String
+where
+    F: Fn(&T) -> String,
+{
+    values.iter().map(f).collect::<Vec<_>>().join(",")
+}
+
+/// Write `field` with the characters that would break up the fields
+/// or lines of the journal escaped.
+fn write_escaped<W: Write>(writer: &mut W, field: &[u8]) -> io::Result<()> {
+    for &b in field.iter() {
+        match b {
+            b'\\' => writer.write_all(b"\\\\")?,
+            b'\t' => writer.write_all(b"\\t")?,
+            b'\n' => writer.write_all(b"\\n")?,
+            b'\r' => writer.write_all(b"\\r")?,
+            _ => writer.write_all(&[b])?,
+        }
+    }
+    Ok(())
+}
+
+fn write_op<W: Write>(writer: &mut W, op: &GraphOp) -> io::Result<()> {
+    let flag = |b: bool| if b { "1" } else { "0" };
+    match op {
+        GraphOp::Create { op } => match op {
+            CreateOp::Handle { id, seq } => {
+                write!(writer, "+H\t{}\t", id.0)?;
+                write_escaped(writer, seq)?;
+            }
+            CreateOp::Edge { edge: Edge(l, r) } => {
+                write!(writer, "+E\t{}\t{}", oriented(*l), oriented(*r))?;
+            }
+            CreateOp::EdgesIter { edges } => write!(
+                writer,
+                "+Es\t{}",
+                joined(edges, |Edge(l, r)| {
+                    format!("{}:{}", oriented(*l), oriented(*r))
+                })
+            )?,
+            CreateOp::Path { name, circular } => {
+                write!(writer, "+P\t")?;
+                write_escaped(writer, name)?;
+                write!(writer, "\t{}", flag(*circular))?;
+            }
+        },
+        GraphOp::Remove { op } => match op {
+            RemoveOp::Handle { handle } => {
+                write!(writer, "-H\t{}", oriented(*handle))?
+            }
+            RemoveOp::Edge { edge: Edge(l, r) } => {
+                write!(writer, "-E\t{}\t{}", oriented(*l), oriented(*r))?;
+            }
+            RemoveOp::Path { name } => {
+                write!(writer, "-P\t")?;
+                write_escaped(writer, name)?;
+            }
+        },
+        GraphOp::MutHandle { op } => match op {
+            MutHandleOp::Divide { handle, offsets } => write!(
+                writer,
+                "D\t{}\t{}",
+                oriented(*handle),
+                joined(offsets, |o| o.to_string())
+            )?,
+            MutHandleOp::Join { handles, joined: h } => write!(
+                writer,
+                "J\t{}\t{}",
+                oriented(*h),
+                joined(handles, |h| oriented(*h))
+            )?,
+            MutHandleOp::Split { handle, parts } => write!(
+                writer,
+                "S\t{}\t{}",
+                oriented(*handle),
+                joined(parts, |(h, len)| format!("{}:{}", oriented(*h), len))
+            )?,
+            MutHandleOp::Flip { handle } => {
+                write!(writer, "F\t{}", oriented(*handle))?
+            }
+        },
+        GraphOp::MutPath { op } => match op {
+            MutPathOp::InsertStep { path, rank, handle } => {
+                write!(writer, "IS\t")?;
+                write_escaped(writer, path)?;
+                write!(writer, "\t{}\t{}", rank, oriented(*handle))?;
+            }
+            MutPathOp::RemoveStep { path, rank } => {
+                write!(writer, "RS\t")?;
+                write_escaped(writer, path)?;
+                write!(writer, "\t{}", rank)?;
+            }
+            MutPathOp::FlipStep { path, rank } => {
+                write!(writer, "FS\t")?;
+                write_escaped(writer, path)?;
+                write!(writer, "\t{}", rank)?;
+            }
+            MutPathOp::SetCircularity { path, circular } => {
+                write!(writer, "C\t")?;
+                write_escaped(writer, path)?;
+                write!(writer, "\t{}", flag(*circular))?;
+            }
+        },
+    }
+    writeln!(writer)
+}
+
+fn parse_escaped(field: &[u8]) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(field.len());
+    let mut bytes = field.iter();
+    while let Some(&b) = bytes.next() {
+        if b != b'\\' {
+            result.push(b);
+            continue;
+        }
+        let unescaped = match bytes.next()? {
+            b'\\' => b'\\',
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'r' => b'\r',
+            _ => return None,
+        };
+        result.push(unescaped);
+    }
+    Some(result)
+}
+
+fn parse_usize(field: &[u8]) -> Option<usize> {
+    field.to_str().ok()?.parse().ok()
+}
+
+fn parse_id(field: &[u8]) -> Option<NodeId> {
+    let id: u64 = field.to_str().ok()?.parse().ok()?;
+    Some(NodeId::from(id))
+}
+
+fn parse_flag(field: &[u8]) -> Option<bool> {
+    match field {
+        b"0" => Some(false),
+        b"1" => Some(true),
+        _ => None,
+    }
+}
+
+fn parse_handle(field: &[u8]) -> Option<Handle> {
+    let (&orient, id) = field.split_last()?;
+    let is_reverse = match orient {
+        b'+' => false,
+        b'-' => true,
+        _ => return None,
+    };
+    Some(Handle::pack(parse_id(id)?, is_reverse))
+}
+
+fn parse_list<T, F>(field: &[u8], f: F) -> Option<Vec<T>>
+where
+    F: Fn(&[u8]) -> Option<T>,
+{
+    if field.is_empty() {
+        return Some(Vec::new());
+    }
+    field.split_str(",").map(f).collect()
+}
+
+fn parse_edge(field: &[u8]) -> Option<Edge> {
+    let mut split = field.split_str(":");
+    let left = parse_handle(split.next()?)?;
+    let right = parse_handle(split.next()?)?;
+    if split.next().is_some() {
+        return None;
+    }
+    Some(Edge(left, right))
+}
+
+fn parse_part(field: &[u8]) -> Option<(Handle, usize)> {
+    let mut split = field.split_str(":");
+    let handle = parse_handle(split.next()?)?;
+    let len = parse_usize(split.next()?)?;
+    if split.next().is_some() {
+        return None;
+    }
+    Some((handle, len))
+}
+
+fn parse_op<'a, I>(mut fields: I) -> Option<GraphOp>
+where
+    I: Iterator<Item = &'a [u8]>,
+{
+    let kind = fields.next()?;
+    let mut next = || fields.next();
+
+    let op: GraphOp = match kind {
+        b"+H" => CreateOp::Handle {
+            id: parse_id(next()?)?,
+            seq: parse_escaped(next()?)?,
+        }
+        .into(),
+        b"+E" => CreateOp::Edge {
+            edge: Edge(parse_handle(next()?)?, parse_handle(next()?)?),
+        }
+        .into(),
+        b"+Es" => CreateOp::EdgesIter {
+            edges: parse_list(next()?, parse_edge)?,
+        }
+        .into(),
+        b"+P" => CreateOp::Path {
+            name: parse_escaped(next()?)?,
+            circular: parse_flag(next()?)?,
+        }
+        .into(),
+        b"-H" => RemoveOp::Handle {
+            handle: parse_handle(next()?)?,
+        }
+        .into(),
+        b"-E" => RemoveOp::Edge {
+            edge: Edge(parse_handle(next()?)?, parse_handle(next()?)?),
+        }
+        .into(),
+        b"-P" => RemoveOp::Path {
+            name: parse_escaped(next()?)?,
+        }
+        .into(),
+        b"D" => MutHandleOp::Divide {
+            handle: parse_handle(next()?)?,
+            offsets: parse_list(next()?, parse_usize)?,
+        }
+        .into(),
+        b"J" => {
+            let joined = parse_handle(next()?)?;
+            let handles = parse_list(next()?, parse_handle)?;
+            MutHandleOp::Join { handles, joined }.into()
+        }
+        b"S" => MutHandleOp::Split {
+            handle: parse_handle(next()?)?,
+            parts: parse_list(next()?, parse_part)?,
+        }
+        .into(),
+        b"F" => MutHandleOp::Flip {
+            handle: parse_handle(next()?)?,
+        }
+        .into(),
+        b"IS" => MutPathOp::InsertStep {
+            path: parse_escaped(next()?)?,
+            rank: parse_usize(next()?)?,
+            handle: parse_handle(next()?)?,
+        }
+        .into(),
+        b"RS" => MutPathOp::RemoveStep {
+            path: parse_escaped(next()?)?,
+            rank: parse_usize(next()?)?,
+        }
+        .into(),
+        b"FS" => MutPathOp::FlipStep {
+            path: parse_escaped(next()?)?,
+            rank: parse_usize(next()?)?,
+        }
+        .into(),
+        b"C" => MutPathOp::SetCircularity {
+            path: parse_escaped(next()?)?,
+            circular: parse_flag(next()?)?,
+        }
+        .into(),
+        _ => return None,
+    };
+
+    if next().is_some() {
+        return None;
+    }
+    Some(op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::super::tests::{divide, graph_state, hnd, r_hnd, test_graph};
+
+    fn edits() -> Vec<Vec<GraphOp>> {
+        vec![
+            vec![
+                CreateOp::Handle {
+                    id: 10.into(),
+                    seq: b"GATTACA".to_vec(),
+                }
+                .into(),
+                CreateOp::Edge {
+                    edge: Edge(hnd(1), hnd(10)),
+                }
+                .into(),
+                MutPathOp::InsertStep {
+                    path: b"path1".to_vec(),
+                    rank: 5,
+                    handle: r_hnd(10),
+                }
+                .into(),
+            ],
+            vec![divide(hnd(3), &[1, 2])],
+            vec![
+                MutHandleOp::Flip { handle: hnd(4) }.into(),
+                RemoveOp::Path {
+                    name: b"path2".to_vec(),
+                }
+                .into(),
+                MutPathOp::SetCircularity {
+                    path: b"path1".to_vec(),
+                    circular: true,
+                }
+                .into(),
+            ],
+        ]
+    }
+
+    #[test]
+    fn journal_undo_redo() {
+        let mut graph = test_graph();
+        let mut journal = Journal::new();
+
+        let mut states = vec![graph_state(&graph)];
+        for batch in edits() {
+            journal.apply_batch(&mut graph, batch).unwrap();
+            states.push(graph_state(&graph));
+        }
+        assert_eq!(journal.len(), 3);
+        assert!(!journal.can_redo());
+
+        // a failing batch is neither applied nor recorded
+        let failing = vec![
+            MutHandleOp::Flip { handle: hnd(5) }.into(),
+            RemoveOp::Handle { handle: hnd(20) }.into(),
+        ];
+        assert_eq!(
+            journal.apply_batch(&mut graph, failing),
+            Err(TransactionError::MissingNode(20.into()))
+        );
+        assert_eq!(journal.len(), 3);
+        assert_eq!(graph_state(&graph), states[3]);
+
+        for state in states.iter().rev().skip(1) {
+            assert!(journal.undo(&mut graph).unwrap().is_some());
+            assert_eq!(&graph_state(&graph), state);
+        }
+        assert!(!journal.can_undo());
+        assert!(journal.undo(&mut graph).unwrap().is_none());
+
+        for state in states.iter().skip(1) {
+            assert!(journal.redo(&mut graph).unwrap().is_some());
+            assert_eq!(&graph_state(&graph), state);
+        }
+        assert!(journal.redo(&mut graph).unwrap().is_none());
+
+        // undoing and redoing again works with the redone entries
+        journal.undo(&mut graph).unwrap();
+        journal.undo(&mut graph).unwrap();
+        assert_eq!(graph_state(&graph), states[1]);
+
+        // applying a new edit clears the redo stack
+        journal
+            .apply(&mut graph, MutHandleOp::Flip { handle: hnd(2) }.into())
+            .unwrap();
+        assert!(!journal.can_redo());
+        assert_eq!(journal.len(), 2);
+    }
+
+    #[test]
+    fn journal_write_read_replay() {
+        let mut graph = test_graph();
+        let mut journal = Journal::new();
+        for batch in edits() {
+            journal.apply_batch(&mut graph, batch).unwrap();
+        }
+
+        let mut buf: Vec<u8> = Vec::new();
+        journal.write(&mut buf).unwrap();
+        let loaded = Journal::read(buf.as_slice()).unwrap();
+        assert_eq!(loaded.entries(), journal.entries());
+
+        let mut other = test_graph();
+        loaded.replay(&mut other).unwrap();
+        assert_eq!(graph_state(&other), graph_state(&graph));
+
+        apply_transaction(&mut other, loaded.undo_ops()).unwrap();
+        assert_eq!(graph_state(&other), graph_state(&test_graph()));
+
+        // names are escaped, so they can't break up the lines
+        let name = b"sample\t1\n\\2\r".to_vec();
+        let mut named = Journal::new();
+        named
+            .apply_batch(
+                &mut other,
+                vec![
+                    CreateOp::Path {
+                        name: name.clone(),
+                        circular: false,
+                    }
+                    .into(),
+                    MutPathOp::InsertStep {
+                        path: name.clone(),
+                        rank: 0,
+                        handle: hnd(5),
+                    }
+                    .into(),
+                ],
+            )
+            .unwrap();
+        let mut buf: Vec<u8> = Vec::new();
+        named.write(&mut buf).unwrap();
+        assert_eq!(buf.iter().filter(|&&b| b == b'\n').count(), 4);
+        let loaded = Journal::read(buf.as_slice()).unwrap();
+        assert_eq!(loaded.entries(), named.entries());
+
+        let invalid: &[u8] = b"E\nO\t+H\t10\tGATTACA\nO\t+E\t1+\n";
+        assert!(matches!(
+            Journal::read(invalid),
+            Err(JournalError::Parse(3))
+        ));
+        let bad_escape: &[u8] = b"E\nO\t-P\tpath\\x\n";
+        assert!(matches!(
+            Journal::read(bad_escape),
+            Err(JournalError::Parse(2))
+        ));
+        let no_entry: &[u8] = b"O\t-H\t10\n";
+        assert!(matches!(
+            Journal::read(no_entry),
+            Err(JournalError::Parse(1))
+        ));
+    }
+}
@@ -0,0 +1,839 @@
+/*!
+
+The graph operations that can be recorded in a transaction, and how
+they are applied.
+
+Every operation is validated against the graph before it changes
+anything, so an operation that fails leaves the graph untouched.
+Applying an operation records its changes in a [`GraphOpDelta`],
+from which the operations that undo it are derived.
+
+*/
+
+use crate::{
+    handle::{Direction, Edge, Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    pathhandlegraph::*,
+    util::dna,
+};
+
+use fnv::FnvHashSet;
+
+use super::{GraphOpDelta, TransactionError};
+
+type OpResult = Result<(), TransactionError>;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GraphOp {
+    Create { op: CreateOp },
+    Remove { op: RemoveOp },
+    MutHandle { op: MutHandleOp },
+    MutPath { op: MutPathOp },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CreateOp {
+    Handle {
+        id: NodeId,
+        seq: Vec<u8>,
+    },
+    /// Creating an edge that already exists does nothing.
+    Edge {
+        edge: Edge,
+    },
+    /// Create several edges at once, skipping those that already
+    /// exist.
+    EdgesIter {
+        edges: Vec<Edge>,
+    },
+    Path {
+        name: Vec<u8>,
+        circular: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RemoveOp {
+    /// Only nodes that are not on any path can be removed. The
+    /// orientation of the handle is ignored.
+    Handle {
+        handle: Handle,
+    },
+    Edge {
+        edge: Edge,
+    },
+    Path {
+        name: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MutHandleOp {
+    /// Divide the node at the given offsets, as with
+    /// [`MutableHandles::divide_handle`]. The IDs of the new nodes
+    /// are chosen by the graph.
+    Divide { handle: Handle, offsets: Vec<usize> },
+    /// Join a chain of handles, where each handle is only connected
+    /// to the next, into a single node, so that `joined` reads the
+    /// sequence of the chain. The node of `joined` must either be on
+    /// the chain, or not exist. Paths must traverse the entire chain.
+    Join {
+        handles: Vec<Handle>,
+        joined: Handle,
+    },
+    /// Split the node of `handle` into a chain of new nodes, with the
+    /// given handles and lengths, in the orientation of `handle`. The
+    /// nodes in `parts` must not exist, with the exception of the
+    /// node being split. This is the inverse of `Join`.
+    Split {
+        handle: Handle,
+        parts: Vec<(Handle, usize)>,
+    },
+    /// Reverse the node of `handle`.
+    Flip { handle: Handle },
+}
+
+/// Operations on paths, which are referred to by name. Steps are
+/// referred to by their rank, i.e. their position counting from the
+/// start of the path.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MutPathOp {
+    /// Insert a step so that it gets the provided `rank`; a rank
+    /// equal to the path length appends the step.
+    InsertStep {
+        path: Vec<u8>,
+        rank: usize,
+        handle: Handle,
+    },
+    RemoveStep {
+        path: Vec<u8>,
+        rank: usize,
+    },
+    FlipStep {
+        path: Vec<u8>,
+        rank: usize,
+    },
+    SetCircularity {
+        path: Vec<u8>,
+        circular: bool,
+    },
+}
+
+impl From<CreateOp> for GraphOp {
+    fn from(op: CreateOp) -> Self {
+        GraphOp::Create { op }
+    }
+}
+
+impl From<RemoveOp> for GraphOp {
+    fn from(op: RemoveOp) -> Self {
+        GraphOp::Remove { op }
+    }
+}
+
+impl From<MutHandleOp> for GraphOp {
+    fn from(op: MutHandleOp) -> Self {
+        GraphOp::MutHandle { op }
+    }
+}
+
+impl From<MutPathOp> for GraphOp {
+    fn from(op: MutPathOp) -> Self {
+        GraphOp::MutPath { op }
+    }
+}
+
+/*
+  Helpers
+*/
+
+fn require_node<G>(graph: &G, id: NodeId) -> Result<(), TransactionError>
+where
+    for<'a> &'a G: IntoHandles,
+{
+    if graph.has_node(id) {
+        Ok(())
+    } else {
+        Err(TransactionError::MissingNode(id))
+    }
+}
+
+fn require_path<G>(graph: &G, name: &[u8]) -> Result<PathId, TransactionError>
+where
+    for<'a> &'a G: GraphPathNames,
+{
+    graph
+        .get_path_id(name)
+        .ok_or_else(|| TransactionError::MissingPath(name.to_vec()))
+}
+
+/// All edges incident to the provided nodes, in canonical
+/// orientation.
+fn incident_edges<G>(graph: &G, ids: &[NodeId]) -> Vec<Edge>
+where
+    for<'a> &'a G: HandleGraphRef,
+{
+    let mut edges = Vec::new();
+    for &id in ids {
+        let handle = Handle::pack(id, false);
+        for prev in graph.neighbors(handle, Direction::Left) {
+            edges.push(Edge::edge_handle(prev, handle));
+        }
+        for next in graph.neighbors(handle, Direction::Right) {
+            edges.push(Edge::edge_handle(handle, next));
+        }
+    }
+    edges.sort();
+    edges.dedup();
+    edges
+}
+
+fn step_at_rank<G: GraphPaths>(
+    graph: &G,
+    path: PathId,
+    rank: usize,
+) -> Option<G::StepIx> {
+    if rank >= graph.path_len(path)? {
+        return None;
+    }
+    let mut step = graph.path_first_step(path)?;
+    for _ in 0..rank {
+        step = graph.path_next_step(path, step)?;
+    }
+    Some(step)
+}
+
+fn path_handles<G: GraphPaths>(graph: &G, path: PathId) -> Vec<Handle> {
+    let len = graph.path_len(path).unwrap_or(0);
+    let mut handles = Vec::with_capacity(len);
+    let mut step = graph.path_first_step(path);
+    while let Some(ix) = step {
+        if handles.len() == len {
+            break;
+        }
+        handles.extend(graph.path_handle_at_step(path, ix));
+        step = graph.path_next_step(path, ix);
+    }
+    handles
+}
+
+/// The circularity and steps of the path `name`, if it exists.
+fn path_state<G>(graph: &G, name: &[u8]) -> Option<(bool, Vec<Handle>)>
+where
+    G: GraphPaths,
+    for<'a> &'a G: GraphPathNames,
+{
+    let path = graph.get_path_id(name)?;
+    let circular = graph.path_circular(path).unwrap_or(false);
+    Some((circular, path_handles(graph, path)))
+}
+
+/// The names of the paths that step on any of the nodes.
+fn paths_on_nodes<G>(graph: &G, ids: &[NodeId]) -> Vec<Vec<u8>>
+where
+    G: GraphPaths,
+    for<'a> &'a G: GraphPathNames + IntoNodeOccurrences,
+{
+    let mut paths = ids
+        .iter()
+        .filter_map(|&id| graph.steps_on_handle(Handle::pack(id, false)))
+        .flatten()
+        .map(|(path, _)| path)
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .filter_map(|path| graph.get_path_name_vec(path))
+        .collect()
+}
+
+/// Apply `f`, recording the state of the paths in `names` in `delta`
+/// before it, and the change in their lengths after it.
+fn edit_paths<G, F>(
+    graph: &mut G,
+    delta: &mut GraphOpDelta,
+    names: &[Vec<u8>],
+    f: F,
+) where
+    G: GraphPaths,
+    for<'a> &'a G: GraphPathNames,
+    F: FnOnce(&mut G, &mut GraphOpDelta),
+{
+    let path_len = |graph: &G, name: &[u8]| {
+        graph
+            .get_path_id(name)
+            .and_then(|path| graph.path_len(path))
+    };
+
+    let mut lens = Vec::with_capacity(names.len());
+    for name in names.iter() {
+        delta.touch_path(name, || path_state(&*graph, name));
+        lens.push(path_len(graph, name).unwrap_or(0));
+    }
+
+    f(graph, delta);
+
+    for (name, len) in names.iter().zip(lens) {
+        let after = path_len(graph, name);
+        let diff = after.unwrap_or(0) as isize - len as isize;
+        delta.update_path(name, diff, after.is_some());
+    }
+}
+
+/// Remove the node `id` and its edges, recording them in `delta`.
+/// There must be no paths on the node.
+fn remove_node<G>(graph: &mut G, id: NodeId, delta: &mut GraphOpDelta)
+where
+    G: MutableHandleGraph,
+    for<'a> &'a G: HandleGraphRef,
+{
+    let handle = Handle::pack(id, false);
+    for &edge in incident_edges(graph, &[id]).iter() {
+        delta.del_edge(edge);
+    }
+    delta.del_node(id, graph.sequence_vec(handle));
+    graph.remove_handle(handle);
+}
+
+/// Replace the nodes `old` with `new`, connected by `edges`, and
+/// replace the steps of the paths in `paths`. This is used to join
+/// and split nodes, where the affected paths have to be emptied
+/// before the old nodes can be removed.
+fn replace_nodes<G>(
+    graph: &mut G,
+    old: &[NodeId],
+    new: &[(NodeId, Vec<u8>)],
+    edges: &[Edge],
+    paths: &[(PathId, Vec<Handle>)],
+    delta: &mut GraphOpDelta,
+) where
+    G: MutableHandleGraph + MutableGraphPaths,
+    for<'a> &'a G: HandleGraphRef + GraphPathNames,
+{
+    let names = paths
+        .iter()
+        .filter_map(|(path, _)| graph.get_path_name_vec(*path))
+        .collect::<Vec<_>>();
+
+    edit_paths(graph, delta, &names, |graph, delta| {
+        for (path, _) in paths.iter() {
+            let len = graph.path_len(*path).unwrap_or(0);
+            for _ in 0..len {
+                if let Some(step) = graph.path_first_step(*path) {
+                    graph.path_remove_step(*path, step);
+                }
+            }
+        }
+
+        for &id in old.iter() {
+            remove_node(graph, id, delta);
+        }
+
+        for (id, seq) in new.iter() {
+            graph.create_handle(seq, *id);
+            delta.add_node(*id, seq.len());
+        }
+
+        for &edge in edges.iter() {
+            graph.create_edge(edge);
+        }
+
+        for (path, steps) in paths.iter() {
+            for &handle in steps.iter() {
+                graph.path_append_step(*path, handle);
+            }
+        }
+
+        let new_ids = new.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        for &edge in incident_edges(graph, &new_ids).iter() {
+            delta.add_edge(edge);
+        }
+    });
+}
+
+/// Find the paths that traverse the handle `from` in either
+/// orientation, and replace each such traversal with the handles in
+/// `to`. If `from` has more than one handle, all of them must be
+/// traversed, in order, otherwise `None` is returned.
+fn rewrite_path_steps<G>(
+    graph: &G,
+    from: &[Handle],
+    to: &[Handle],
+) -> Option<Vec<(PathId, Vec<Handle>)>>
+where
+    G: GraphPaths,
+    for<'a> &'a G: IntoPathIds,
+{
+    let ids = from.iter().map(|h| h.id()).collect::<FnvHashSet<_>>();
+    let rev_from = from.iter().rev().map(|h| h.flip()).collect::<Vec<_>>();
+    let rev_to = to.iter().rev().map(|h| h.flip()).collect::<Vec<_>>();
+
+    let mut result = Vec::new();
+
+    for path in graph.path_ids() {
+        let steps = path_handles(graph, path);
+        if !steps.iter().any(|h| ids.contains(&h.id())) {
+            continue;
+        }
+
+        let mut new_steps = Vec::with_capacity(steps.len());
+        let mut ix = 0;
+        while ix < steps.len() {
+            let rest = &steps[ix..];
+            if rest.starts_with(from) {
+                new_steps.extend_from_slice(to);
+                ix += from.len();
+            } else if rest.starts_with(&rev_from) {
+                new_steps.extend_from_slice(&rev_to);
+                ix += from.len();
+            } else if ids.contains(&rest[0].id()) {
+                return None;
+            } else {
+                new_steps.push(rest[0]);
+                ix += 1;
+            }
+        }
+
+        result.push((path, new_steps));
+    }
+
+    Some(result)
+}
+
+/*
+  Applying operations
+*/
+
+impl GraphOp {
+    /// Apply the operation to `graph`, recording the changes in
+    /// `delta`.
+    pub(crate) fn apply<G>(
+        &self,
+        graph: &mut G,
+        delta: &mut GraphOpDelta,
+    ) -> OpResult
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'a> &'a G:
+            HandleGraphRef + GraphPathNames + IntoNodeOccurrences + IntoPathIds,
+    {
+        match self {
+            GraphOp::Create { op } => op.apply(graph, delta),
+            GraphOp::Remove { op } => op.apply(graph, delta),
+            GraphOp::MutHandle { op } => op.apply(graph, delta),
+            GraphOp::MutPath { op } => op.apply(graph, delta),
+        }
+    }
+}
+
+impl CreateOp {
+    fn apply<G>(&self, graph: &mut G, delta: &mut GraphOpDelta) -> OpResult
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'a> &'a G: HandleGraphRef + GraphPathNames,
+    {
+        match self {
+            CreateOp::Handle { id, seq } => {
+                let id = *id;
+                if graph.has_node(id) {
+                    return Err(TransactionError::NodeExists(id));
+                }
+                if id == NodeId::from(0) || seq.is_empty() {
+                    return Err(TransactionError::InvalidNode(id));
+                }
+
+                graph.create_handle(seq, id);
+                delta.add_node(id, seq.len());
+            }
+            CreateOp::Edge { edge } => {
+                let Edge(left, right) = *edge;
+                require_node(graph, left.id())?;
+                require_node(graph, right.id())?;
+
+                if !graph.has_edge(left, right) {
+                    graph.create_edge(*edge);
+                    delta.add_edge(*edge);
+                }
+            }
+            CreateOp::EdgesIter { edges } => {
+                for &Edge(left, right) in edges.iter() {
+                    require_node(graph, left.id())?;
+                    require_node(graph, right.id())?;
+                }
+
+                let mut seen: FnvHashSet<Edge> = FnvHashSet::default();
+                for &Edge(left, right) in edges.iter() {
+                    let canonical = Edge::edge_handle(left, right);
+                    if seen.insert(canonical) && !graph.has_edge(left, right) {
+                        graph.create_edge(Edge(left, right));
+                        delta.add_edge(canonical);
+                    }
+                }
+            }
+            CreateOp::Path { name, circular } => {
+                if graph.get_path_id(name).is_some() {
+                    return Err(TransactionError::PathExists(name.clone()));
+                }
+
+                let names = [name.clone()];
+                edit_paths(graph, delta, &names, |graph, delta| {
+                    if let Some(path) = graph.create_path(name, *circular) {
+                        delta.add_path(path);
+                    }
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RemoveOp {
+    fn apply<G>(&self, graph: &mut G, delta: &mut GraphOpDelta) -> OpResult
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'a> &'a G: HandleGraphRef + GraphPathNames + IntoNodeOccurrences,
+    {
+        match self {
+            RemoveOp::Handle { handle } => {
+                let id = handle.id();
+                require_node(graph, id)?;
+
+                let on_path = graph
+                    .steps_on_handle(handle.forward())
+                    .and_then(|mut steps| steps.next())
+                    .is_some();
+                if on_path {
+                    return Err(TransactionError::NodeOnPath(id));
+                }
+
+                remove_node(graph, id, delta);
+            }
+            RemoveOp::Edge { edge } => {
+                let Edge(left, right) = *edge;
+                if !graph.has_edge(left, right) {
+                    return Err(TransactionError::MissingEdge(*edge));
+                }
+
+                graph.remove_edge(*edge);
+                delta.del_edge(*edge);
+            }
+            RemoveOp::Path { name } => {
+                let path = require_path(graph, name)?;
+
+                let names = [name.clone()];
+                edit_paths(graph, delta, &names, |graph, delta| {
+                    graph.destroy_path(path);
+                    delta.del_path(path);
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MutHandleOp {
+    fn apply<G>(&self, graph: &mut G, delta: &mut GraphOpDelta) -> OpResult
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'a> &'a G:
+            HandleGraphRef + GraphPathNames + IntoNodeOccurrences + IntoPathIds,
+    {
+        match self {
+            MutHandleOp::Divide { handle, offsets } => {
+                let handle = *handle;
+                require_node(graph, handle.id())?;
+
+                let len = graph.node_len(handle);
+                let valid = !offsets.is_empty()
+                    && offsets.windows(2).all(|w| w[0] < w[1])
+                    && offsets.iter().all(|&o| o > 0 && o < len);
+                if !valid {
+                    return Err(TransactionError::InvalidOffsets(handle));
+                }
+
+                let names = paths_on_nodes(&*graph, &[handle.id()]);
+                edit_paths(graph, delta, &names, |graph, delta| {
+                    let id = handle.id();
+                    for &edge in incident_edges(graph, &[id]).iter() {
+                        delta.del_edge(edge);
+                    }
+                    delta.del_node(id, graph.sequence_vec(handle.forward()));
+
+                    let parts = graph.divide_handle(handle, offsets);
+
+                    let mut part_ids =
+                        parts.iter().map(|h| h.id()).collect::<Vec<_>>();
+                    part_ids.sort();
+                    for &id in part_ids.iter() {
+                        let len = graph.node_len(Handle::pack(id, false));
+                        delta.add_node(id, len);
+                    }
+                    for &edge in incident_edges(graph, &part_ids).iter() {
+                        delta.add_edge(edge);
+                    }
+                });
+            }
+            MutHandleOp::Join { handles, joined } => {
+                let joined = *joined;
+                let invalid = || TransactionError::InvalidJoin(handles.clone());
+
+                for h in handles.iter() {
+                    require_node(graph, h.id())?;
+                }
+
+                let ids = handles.iter().map(|h| h.id()).collect::<Vec<_>>();
+                let distinct = ids.iter().collect::<FnvHashSet<_>>().len();
+                if handles.len() < 2 || distinct != handles.len() {
+                    return Err(invalid());
+                }
+
+                // each handle must only be connected to the next
+                for w in handles.windows(2) {
+                    let next = graph
+                        .neighbors(w[0], Direction::Right)
+                        .collect::<Vec<_>>();
+                    let prev = graph
+                        .neighbors(w[1], Direction::Left)
+                        .collect::<Vec<_>>();
+                    if next != [w[1]] || prev != [w[0]] {
+                        return Err(invalid());
+                    }
+                }
+
+                if joined.id() == NodeId::from(0) {
+                    return Err(TransactionError::InvalidNode(joined.id()));
+                }
+                if !ids.contains(&joined.id()) && graph.has_node(joined.id()) {
+                    return Err(TransactionError::NodeExists(joined.id()));
+                }
+
+                let first = handles[0];
+                let last = *handles.last().unwrap();
+
+                let paths = rewrite_path_steps(&*graph, handles, &[joined])
+                    .ok_or_else(invalid)?;
+
+                let seq = handles
+                    .iter()
+                    .flat_map(|&h| graph.sequence(h))
+                    .collect::<Vec<_>>();
+                let seq = if joined.is_reverse() {
+                    dna::rev_comp(&seq)
+                } else {
+                    seq
+                };
+
+                // the only edges between the chain and itself that
+                // are left are self-edges of the joined node
+                let mut edges = Vec::new();
+                for prev in graph.neighbors(first, Direction::Left) {
+                    let prev = if prev == last {
+                        joined
+                    } else if prev == first.flip() {
+                        joined.flip()
+                    } else {
+                        prev
+                    };
+                    edges.push(Edge(prev, joined));
+                }
+                for next in graph.neighbors(last, Direction::Right) {
+                    let next = if next == first {
+                        joined
+                    } else if next == last.flip() {
+                        joined.flip()
+                    } else {
+                        next
+                    };
+                    edges.push(Edge(joined, next));
+                }
+
+                replace_nodes(
+                    graph,
+                    &ids,
+                    &[(joined.id(), seq)],
+                    &edges,
+                    &paths,
+                    delta,
+                );
+            }
+            MutHandleOp::Split { handle, parts } => {
+                let handle = *handle;
+                let invalid = || TransactionError::InvalidSplit(handle);
+
+                require_node(graph, handle.id())?;
+
+                let len = graph.node_len(handle);
+                let parts_len = parts.iter().map(|(_, l)| l).sum::<usize>();
+                let ids = parts.iter().map(|(h, _)| h.id()).collect::<Vec<_>>();
+                let distinct = ids.iter().collect::<FnvHashSet<_>>().len();
+
+                if parts.len() < 2
+                    || distinct != parts.len()
+                    || parts_len != len
+                    || parts.iter().any(|&(_, l)| l == 0)
+                {
+                    return Err(invalid());
+                }
+
+                for &id in ids.iter() {
+                    if id == NodeId::from(0) {
+                        return Err(TransactionError::InvalidNode(id));
+                    }
+                    if id != handle.id() && graph.has_node(id) {
+                        return Err(TransactionError::NodeExists(id));
+                    }
+                }
+
+                let chain = parts.iter().map(|(h, _)| *h).collect::<Vec<_>>();
+                let first = chain[0];
+                let last = *chain.last().unwrap();
+
+                let paths = rewrite_path_steps(&*graph, &[handle], &chain)
+                    .ok_or_else(invalid)?;
+
+                let seq = graph.sequence_vec(handle);
+                let mut offset = 0;
+                let nodes = parts
+                    .iter()
+                    .map(|&(h, l)| {
+                        let piece = &seq[offset..offset + l];
+                        offset += l;
+                        let seq = if h.is_reverse() {
+                            dna::rev_comp(piece)
+                        } else {
+                            piece.to_vec()
+                        };
+                        (h.id(), seq)
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut edges = chain
+                    .windows(2)
+                    .map(|w| Edge(w[0], w[1]))
+                    .collect::<Vec<_>>();
+                for prev in graph.neighbors(handle, Direction::Left) {
+                    let prev = if prev == handle {
+                        last
+                    } else if prev == handle.flip() {
+                        first.flip()
+                    } else {
+                        prev
+                    };
+                    edges.push(Edge(prev, first));
+                }
+                for next in graph.neighbors(handle, Direction::Right) {
+                    let next = if next == handle {
+                        first
+                    } else if next == handle.flip() {
+                        last.flip()
+                    } else {
+                        next
+                    };
+                    edges.push(Edge(last, next));
+                }
+
+                replace_nodes(
+                    graph,
+                    &[handle.id()],
+                    &nodes,
+                    &edges,
+                    &paths,
+                    delta,
+                );
+            }
+            MutHandleOp::Flip { handle } => {
+                let id = handle.id();
+                require_node(graph, id)?;
+
+                let names = paths_on_nodes(&*graph, &[id]);
+                edit_paths(graph, delta, &names, |graph, delta| {
+                    let forward = Handle::pack(id, false);
+                    for &edge in incident_edges(graph, &[id]).iter() {
+                        delta.del_edge(edge);
+                    }
+                    delta.del_node(id, graph.sequence_vec(forward));
+
+                    graph.apply_orientation(forward.flip());
+
+                    delta.add_node(id, graph.node_len(forward));
+                    for &edge in incident_edges(graph, &[id]).iter() {
+                        delta.add_edge(edge);
+                    }
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MutPathOp {
+    fn apply<G>(&self, graph: &mut G, delta: &mut GraphOpDelta) -> OpResult
+    where
+        G: MutableHandleGraph + MutableGraphPaths,
+        for<'a> &'a G: HandleGraphRef + GraphPathNames,
+    {
+        match self {
+            MutPathOp::InsertStep { path, rank, handle } => {
+                let path_id = require_path(graph, path)?;
+                require_node(graph, handle.id())?;
+
+                let rank = *rank;
+                let len = graph.path_len(path_id).unwrap_or(0);
+                if rank > len {
+                    return Err(TransactionError::InvalidRank(
+                        path.clone(),
+                        rank,
+                    ));
+                }
+
+                let names = [path.clone()];
+                edit_paths(graph, delta, &names, |graph, _| {
+                    if rank == len {
+                        graph.path_append_step(path_id, *handle);
+                    } else if rank == 0 {
+                        graph.path_prepend_step(path_id, *handle);
+                    } else {
+                        let prev =
+                            step_at_rank(&*graph, path_id, rank - 1).unwrap();
+                        graph.path_insert_step_after(path_id, prev, *handle);
+                    }
+                });
+            }
+            MutPathOp::RemoveStep { path, rank } => {
+                let path_id = require_path(graph, path)?;
+                let step =
+                    step_at_rank(&*graph, path_id, *rank).ok_or_else(|| {
+                        TransactionError::InvalidRank(path.clone(), *rank)
+                    })?;
+
+                let names = [path.clone()];
+                edit_paths(graph, delta, &names, |graph, _| {
+                    graph.path_remove_step(path_id, step);
+                });
+            }
+            MutPathOp::FlipStep { path, rank } => {
+                let path_id = require_path(graph, path)?;
+                let step =
+                    step_at_rank(&*graph, path_id, *rank).ok_or_else(|| {
+                        TransactionError::InvalidRank(path.clone(), *rank)
+                    })?;
+
+                let names = [path.clone()];
+                edit_paths(graph, delta, &names, |graph, _| {
+                    graph.path_flip_step(path_id, step);
+                });
+            }
+            MutPathOp::SetCircularity { path, circular } => {
+                let path_id = require_path(graph, path)?;
+
+                let names = [path.clone()];
+                edit_paths(graph, delta, &names, |graph, _| {
+                    graph.path_set_circularity(path_id, *circular);
+                });
+            }
+        }
+        Ok(())
+    }
+}
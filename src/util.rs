@@ -1,6 +1,7 @@
 #[cfg(test)]
 pub mod test;
 
+pub mod bed;
 pub mod validate;
 
 pub mod dna {
@@ -0,0 +1,181 @@
+/*!
+
+Minimal reading and writing of BED records, used by the algorithms
+that operate on intervals along embedded paths.
+
+Only the three mandatory columns are interpreted; any further columns
+are kept as-is, so records can be written back out unchanged.
+
+*/
+
+use std::io::{self, BufRead, Write};
+
+use bstr::ByteSlice;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BedRecord {
+    /// The name of the sequence, i.e. the path name.
+    pub chrom: Vec<u8>,
+    /// The 0-based, inclusive start of the interval.
+    pub start: usize,
+    /// The 0-based, exclusive end of the interval.
+    pub end: usize,
+    /// Any columns following `end`, without the tab separators.
+    pub fields: Vec<Vec<u8>>,
+}
+
+impl BedRecord {
+    pub fn new(chrom: &[u8], start: usize, end: usize) -> Self {
+        Self {
+            chrom: chrom.to_vec(),
+            start,
+            end,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Parse a single BED line. Returns `None` if the line has fewer
+    /// than three columns, or if the start and end columns aren't
+    /// valid integers.
+    pub fn parse_line(line: &[u8]) -> Option<Self> {
+        let mut columns = line.split_str("\t");
+
+        let chrom = columns.next()?.to_vec();
+        let start = columns.next()?.to_str().ok()?.parse().ok()?;
+        let end = columns.next()?.to_str().ok()?.parse().ok()?;
+        let fields = columns.map(|c| c.to_vec()).collect();
+
+        Some(Self {
+            chrom,
+            start,
+            end,
+            fields,
+        })
+    }
+
+    /// The name column, if present.
+    #[inline]
+    pub fn name(&self) -> Option<&[u8]> {
+        self.fields.first().map(|f| f.as_slice())
+    }
+
+    /// The strand column, if present and either `+` or `-`. Returns
+    /// `true` for the reverse strand.
+    #[inline]
+    pub fn is_reverse(&self) -> Option<bool> {
+        match self.fields.get(2).map(|f| f.as_slice()) {
+            Some(b"+") => Some(false),
+            Some(b"-") => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Set the strand column, filling in the name and score columns
+    /// with `.` and `0` if they're missing.
+    pub fn set_strand(&mut self, is_reverse: bool) {
+        if self.fields.is_empty() {
+            self.fields.push(b".".to_vec());
+        }
+        if self.fields.len() < 2 {
+            self.fields.push(b"0".to_vec());
+        }
+
+        let strand = if is_reverse { b"-" } else { b"+" };
+
+        if self.fields.len() < 3 {
+            self.fields.push(strand.to_vec());
+        } else {
+            self.fields[2] = strand.to_vec();
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(
+            out,
+            "{}\t{}\t{}",
+            self.chrom.as_bstr(),
+            self.start,
+            self.end
+        )?;
+        for field in self.fields.iter() {
+            write!(out, "\t{}", field.as_bstr())?;
+        }
+        writeln!(out)
+    }
+}
+
+/// Parse all the BED records from `reader`. Empty lines, comments,
+/// and `track` and `browser` lines are skipped, while any other line
+/// that can't be parsed results in an `InvalidData` error.
+pub fn read_bed<R: BufRead>(reader: R) -> io::Result<Vec<BedRecord>> {
+    let mut records = Vec::new();
+
+    for line in reader.split(b'\n') {
+        let line = line?;
+        let line = line.trim_end_with(|c| c == '\r');
+
+        if line.is_empty()
+            || line.starts_with(b"#")
+            || line.starts_with(b"track")
+            || line.starts_with(b"browser")
+        {
+            continue;
+        }
+
+        let record = BedRecord::parse_line(line).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid BED line: {}", line.as_bstr()),
+            )
+        })?;
+
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bed_round_trip() {
+        let bed = b"# comment\n\
+                    track name=test\n\
+                    chr1\t0\t10\n\
+                    chr2\t5\t7\tfeature\t100\t-\n";
+
+        let records = read_bed(&bed[..]).unwrap();
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0], BedRecord::new(b"chr1", 0, 10));
+        assert_eq!(records[1].name(), Some(&b"feature"[..]));
+        assert_eq!(records[1].is_reverse(), Some(true));
+
+        let mut out: Vec<u8> = Vec::new();
+        for record in records.iter() {
+            record.write(&mut out).unwrap();
+        }
+        assert_eq!(out, b"chr1\t0\t10\nchr2\t5\t7\tfeature\t100\t-\n");
+
+        let mut record = records[0].clone();
+        record.set_strand(true);
+        assert_eq!(
+            record.fields,
+            vec![b".".to_vec(), b"0".to_vec(), b"-".to_vec()]
+        );
+
+        assert!(read_bed(&b"chr1\tx\t10\n"[..]).is_err());
+    }
+}
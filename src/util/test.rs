@@ -17,11 +17,11 @@ use crate::{
 use crate::hashgraph::HashGraph;
 use crate::packedgraph::PackedGraph;
 
-fn hnd(x: u64) -> Handle {
+pub fn hnd(x: u64) -> Handle {
     Handle::pack(x, false)
 }
 
-fn r_hnd(x: u64) -> Handle {
+pub fn r_hnd(x: u64) -> Handle {
     Handle::pack(x, true)
 }
 
@@ -29,7 +29,7 @@ fn vec_hnd(v: Vec<u64>) -> Vec<Handle> {
     v.into_iter().map(hnd).collect::<Vec<_>>()
 }
 
-fn edge(l: u64, r: u64) -> Edge {
+pub fn edge(l: u64, r: u64) -> Edge {
     Edge(hnd(l), hnd(r))
 }
 fn r_edge(l: u64, r: u64) -> Edge {
@@ -105,3 +105,56 @@ where
 
     graph
 }
+
+/// Create the path `name` on `graph`, stepping through `steps`.
+pub fn add_path<G>(graph: &mut G, name: &[u8], steps: &[Handle]) -> PathId
+where
+    G: MutableGraphPaths,
+{
+    let path = graph.create_path(name, false).unwrap();
+    for &handle in steps {
+        graph.path_append_step(path, handle);
+    }
+    path
+}
+
+/// The graph from [`test_graph_no_paths`], with the paths `path1`, on
+/// `1+ 2+ 7+ 3+ 9+`, and `path2`, on `6+ 4- 8- 1-`.
+pub fn test_graph<G>() -> G
+where
+    G: AdditiveHandleGraph + MutableGraphPaths + Default,
+{
+    let mut graph: G = test_graph_no_paths();
+    add_path(&mut graph, b"path1", &vec_hnd(vec![1, 2, 7, 3, 9]));
+    add_path(
+        &mut graph,
+        b"path2",
+        &[hnd(6), r_hnd(4), r_hnd(8), r_hnd(1)],
+    );
+    graph
+}
+
+/// A graph with a node for each of `seqs`, with IDs counting from 1,
+/// and the given edges between them.
+pub fn seq_graph<G>(seqs: &[&[u8]], edges: &[Edge]) -> G
+where
+    G: AdditiveHandleGraph + Default,
+{
+    let mut graph = G::default();
+    for seq in seqs {
+        graph.append_handle(seq);
+    }
+    for &edge in edges {
+        graph.create_edge(edge);
+    }
+    graph
+}
+
+/// The nodes `1: AAAC`, `2: GG`, and `3: TCA`, joined by the edges
+/// `1+ 2+` and `2+ 3+`, and without paths.
+pub fn small_graph<G>() -> G
+where
+    G: AdditiveHandleGraph + Default,
+{
+    seq_graph(&[b"AAAC", b"GG", b"TCA"], &[edge(1, 2), edge(2, 3)])
+}
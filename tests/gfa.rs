@@ -577,3 +577,22 @@ fn gfa_mica_100507436_fast_construction() {
         "tests/gfas/MICA-100507436.sort.gfa.test",
     );
 }
+
+#[test]
+fn gfa_graph_types_isomorphic() {
+    use handlegraph::{algorithms::isomorphism::is_isomorphic, conversion};
+
+    let parser = GFAParser::new();
+    let gfa: GFA<usize, ()> =
+        parser.parse_file("tests/gfas/A-3105.gfa").unwrap();
+
+    let hash: HashGraph = conversion::from_gfa(&gfa);
+    let mut packed: PackedGraph = conversion::from_gfa(&gfa);
+    assert!(is_isomorphic(&hash, &packed));
+
+    let mut order = packed.handles().collect::<Vec<_>>();
+    order.reverse();
+    packed.apply_ordering(&order);
+    assert!(is_isomorphic(&hash, &packed));
+    assert!(is_isomorphic(&packed, &hash));
+}
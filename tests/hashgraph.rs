@@ -118,6 +118,10 @@ fn degree_is_correct() {
     assert_eq!(graph.degree(h1, Direction::Left), 2);
     assert_eq!(graph.degree(h2, Direction::Right), 2);
     assert_eq!(graph.degree(h2, Direction::Left), 1);
+
+    // the degree of a reverse handle is the opposite side's
+    assert_eq!(graph.degree(h2.flip(), Direction::Right), 1);
+    assert_eq!(graph.degree(h2.flip(), Direction::Left), 2);
 }
 
 fn path_graph() -> HashGraph {
@@ -145,6 +149,81 @@ fn path_graph() -> HashGraph {
     graph
 }
 
+#[test]
+fn remove_edges_and_handles() {
+    let mut graph = path_graph();
+    let path = graph.create_path(b"path", false).unwrap();
+    for &h in [H1, H3, H4, H6].iter() {
+        graph.path_append_step(path, h);
+    }
+
+    assert!(graph.remove_edge(Edge(H6.flip(), H5.flip())));
+    assert!(!graph.remove_edge(Edge(H5, H6)));
+    assert!(!graph.has_edge(H5, H6));
+    assert_eq!(graph.degree(H6, Direction::Left), 1);
+    assert_eq!(graph.edge_count(), 5);
+
+    assert!(graph.remove_handle(H3.flip()));
+    assert!(!graph.remove_handle(H3));
+    assert!(!graph.has_node(H3.id()));
+    assert_eq!(graph.node_count(), 5);
+    assert_eq!(graph.edge_count(), 3);
+    assert_eq!(
+        graph.neighbors(H1, Direction::Right).collect::<Vec<_>>(),
+        [H2]
+    );
+    assert_eq!(graph.degree(H4, Direction::Left), 0);
+
+    // the path on the removed node is destroyed
+    assert_eq!(graph.path_count(), 0);
+    assert!(graph.steps_on_handle(H1).unwrap().next().is_none());
+
+    graph.remove_handle(H6);
+    assert_eq!(graph.max_node_id(), NodeId::from(5));
+}
+
+#[test]
+fn path_steps_range() {
+    let mut graph = path_graph();
+    let path = graph.create_path(b"path", false).unwrap();
+    for &h in [H1, H3, H4, H6].iter() {
+        graph.path_append_step(path, h);
+    }
+    let empty = graph.create_path(b"empty", false).unwrap();
+
+    let range = |from, to| {
+        graph
+            .path_steps_range(path, from, to)
+            .unwrap()
+            .map(|s| s.handle())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(range(StepIx::Step(1), StepIx::Step(2)), [H3, H4]);
+    assert_eq!(range(StepIx::Step(2), StepIx::End), [H4, H6]);
+    // the end of the range is clamped to the last step
+    assert_eq!(range(StepIx::Front, StepIx::Step(10)), [H1, H3, H4, H6]);
+
+    let mut steps = graph
+        .path_steps_range(empty, StepIx::Front, StepIx::End)
+        .unwrap();
+    assert!(steps.next().is_none());
+}
+
+#[test]
+fn apply_ordering() {
+    let mut graph = path_graph();
+    let order = [H6, H5, H4, H3, H2, H1];
+    graph.apply_ordering(&order);
+
+    assert_eq!(graph.node_count(), 6);
+    assert_eq!(graph.sequence_vec(H1), b"6");
+    assert_eq!(graph.sequence_vec(H6), b"1");
+    assert!(graph.has_edge(H6, H5));
+    assert!(graph.has_edge(H3, H1));
+    assert!(!graph.has_edge(H1, H2));
+}
+
 #[test]
 fn graph_has_edge() {
     let graph: HashGraph = read_test_gfa();
@@ -448,3 +527,43 @@ fn graph_divide_handle() {
 
     assert_eq!(expected_handles, handles);
 }
+
+#[test]
+fn graph_divide_handle_reverse_steps() {
+    let mut graph = HashGraph::new();
+    graph.append_handle(b"AAAA");
+    graph.append_handle(b"CCGGTT");
+    graph.append_handle(b"TTTT");
+
+    graph.create_edge(Edge(H1, H2));
+    graph.create_edge(Edge(H2, H3));
+
+    let fwd = graph.create_path(b"fwd", false).unwrap();
+    let rev = graph.create_path(b"rev", false).unwrap();
+
+    for &h in [H1, H2, H3].iter() {
+        graph.path_append_step(fwd, h);
+    }
+    for &h in [H3.flip(), H2.flip(), H1.flip()].iter() {
+        graph.path_append_step(rev, h);
+    }
+
+    let walk_path = |graph: &HashGraph, path: PathId| {
+        let path_ref = graph.get_path_ref(path).unwrap();
+        path_ref.steps().map(|Step(_, h)| h).collect::<Vec<_>>()
+    };
+
+    // offsets on the reverse handle, which reads AACCGG: AA|CC|GG
+    let parts = graph.divide_handle(H2.flip(), &[2, 4]);
+    assert_eq!(parts, vec![H2, H4, H5]);
+
+    assert_eq!(graph.sequence_vec(H2), b"CC");
+    assert_eq!(graph.sequence_vec(H4), b"GG");
+    assert_eq!(graph.sequence_vec(H5), b"TT");
+
+    assert_eq!(walk_path(&graph, fwd), vec![H1, H2, H4, H5, H3]);
+    assert_eq!(
+        walk_path(&graph, rev),
+        vec![H3.flip(), H5.flip(), H4.flip(), H2.flip(), H1.flip()]
+    );
+}